@@ -0,0 +1,266 @@
+/// exact token amounts: an integer of base units and the token's decimals.
+///
+/// oracles, the gateway and ao messages publish raw integers (wei, winston,
+/// armstrongs) that are only scaled to human values for display, so the
+/// conversions never go through floating point.
+use anyhow::{Error, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// AR (winston)
+pub const AR_DECIMALS: u8 = 12;
+/// ao and pi tokens
+pub const AO_DECIMALS: u8 = 12;
+/// LSTs staked through the usds, dai and steth oracles
+pub const LST_DECIMALS: u8 = 18;
+
+/// decimals of an oracle ticker's LST
+pub fn lst_decimals(ticker: &str) -> Option<u8> {
+    match ticker.to_ascii_lowercase().as_str() {
+        "usds" | "dai" | "steth" => Some(LST_DECIMALS),
+        _ => None,
+    }
+}
+
+/// serialized as `{"raw": "1500000000000", "decimals": 12}`, `raw` as a
+/// string since it overflows JSON numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "AmountRepr", try_from = "AmountRepr")]
+pub struct Amount {
+    pub raw: u128,
+    pub decimals: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AmountRepr {
+    raw: String,
+    decimals: u8,
+}
+
+impl From<Amount> for AmountRepr {
+    fn from(value: Amount) -> Self {
+        AmountRepr {
+            raw: value.raw.to_string(),
+            decimals: value.decimals,
+        }
+    }
+}
+
+impl TryFrom<AmountRepr> for Amount {
+    type Error = Error;
+
+    fn try_from(value: AmountRepr) -> Result<Self, Error> {
+        Amount::from_raw_str(&value.raw, value.decimals)
+    }
+}
+
+/// an integer of base units whose decimals come from context, like a
+/// Set-Balances amount before its oracle's ticker is known. (de)serialized as
+/// a string of digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct RawAmount(pub u128);
+
+impl RawAmount {
+    pub fn with_decimals(self, decimals: u8) -> Amount {
+        Amount::new(self.0, decimals)
+    }
+}
+
+impl TryFrom<String> for RawAmount {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Error> {
+        Ok(RawAmount(Amount::from_raw_str(&value, 0)?.raw))
+    }
+}
+
+impl From<RawAmount> for String {
+    fn from(value: RawAmount) -> Self {
+        value.0.to_string()
+    }
+}
+
+impl fmt::Display for RawAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// serializes an amount as its human string, for `String` columns
+pub fn serialize_human<S: serde::Serializer>(
+    amount: &Amount,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(amount)
+}
+
+impl Amount {
+    pub fn new(raw: u128, decimals: u8) -> Self {
+        Amount { raw, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Amount::new(0, decimals)
+    }
+
+    /// an integer of base units, e.g. a Set-Balances amount or a `Quantity`
+    pub fn from_raw_str(raw: &str, decimals: u8) -> Result<Self, Error> {
+        let raw = raw.trim();
+        if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(anyhow!("invalid raw amount {raw:?}"));
+        }
+        let raw = raw
+            .parse::<u128>()
+            .map_err(|_| anyhow!("raw amount {raw} out of range"))?;
+        Ok(Amount::new(raw, decimals))
+    }
+
+    /// a human decimal like `1.5`, with at most `decimals` fractional digits
+    pub fn parse_human(input: &str, decimals: u8) -> Result<Self, Error> {
+        let input = input.trim();
+        let (whole, frac) = input.split_once('.').unwrap_or((input, ""));
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && frac.is_empty()) || !digits(whole) || !digits(frac) {
+            return Err(anyhow!("invalid amount format"));
+        }
+        if frac.len() > decimals as usize {
+            return Err(anyhow!("amount has more than {decimals} decimal places"));
+        }
+        let scale = Self::scale(decimals)?;
+        let whole: u128 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| anyhow!("amount out of range"))?
+        };
+        let frac: u128 = if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<u128>()
+                .map_err(|_| anyhow!("amount out of range"))?
+                * 10u128.pow(decimals as u32 - frac.len() as u32)
+        };
+        let raw = whole
+            .checked_mul(scale)
+            .and_then(|raw| raw.checked_add(frac))
+            .ok_or_else(|| anyhow!("amount out of range"))?;
+        Ok(Amount::new(raw, decimals))
+    }
+
+    fn scale(decimals: u8) -> Result<u128, Error> {
+        10u128
+            .checked_pow(decimals as u32)
+            .ok_or_else(|| anyhow!("unsupported decimals {decimals}"))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw == 0
+    }
+
+    /// the part a delegation `factor` out of `max_factor` assigns, rounded
+    /// down to a base unit
+    pub fn factored(&self, factor: u32, max_factor: u32) -> Self {
+        let (factor, max) = (factor.min(max_factor) as u128, max_factor.max(1) as u128);
+        // split to stay within u128 for any raw value
+        let raw = self.raw / max * factor + self.raw % max * factor / max;
+        Amount::new(raw, self.decimals)
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Option<Amount> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Amount::new(self.raw.checked_add(other.raw)?, self.decimals))
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Option<Amount> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Amount::new(self.raw.checked_sub(other.raw)?, self.decimals))
+    }
+
+    /// the part a `share` from 0 to 1 assigns, the share rounded to 1e-9
+    pub fn portion(&self, share: f64) -> Self {
+        const PARTS: u32 = 1_000_000_000;
+        let parts = (share.clamp(0.0, 1.0) * PARTS as f64).round() as u32;
+        self.factored(parts, PARTS)
+    }
+
+    /// the same value in base units of `decimals`, `None` when that would
+    /// drop digits or overflow
+    pub fn rescale(&self, decimals: u8) -> Option<Amount> {
+        let raw = if decimals >= self.decimals {
+            self.raw
+                .checked_mul(10u128.checked_pow((decimals - self.decimals) as u32)?)?
+        } else {
+            let scale = 10u128.checked_pow((self.decimals - decimals) as u32)?;
+            if !self.raw.is_multiple_of(scale) {
+                return None;
+            }
+            self.raw / scale
+        };
+        Some(Amount::new(raw, decimals))
+    }
+
+    /// approximate value for ratios and charts
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(0.0)
+    }
+}
+
+/// the human value, without trailing zeros
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ok(scale) = Self::scale(self.decimals) else {
+            return write!(f, "{}e-{}", self.raw, self.decimals);
+        };
+        let (whole, frac) = (self.raw / scale, self.raw % scale);
+        if frac == 0 {
+            return write!(f, "{whole}");
+        }
+        let frac = format!("{frac:0width$}", width = self.decimals as usize);
+        write!(f, "{whole}.{}", frac.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::amount::{AO_DECIMALS, Amount, LST_DECIMALS, RawAmount};
+
+    #[test]
+    fn amount_conversions_test() {
+        let amount = Amount::parse_human("1.5", AO_DECIMALS).unwrap();
+        assert_eq!(amount.raw, 1_500_000_000_000);
+        assert_eq!(amount.to_string(), "1.5");
+        assert_eq!(Amount::parse_human(".25", 6).unwrap().raw, 250_000);
+        assert_eq!(Amount::parse_human("7", 0).unwrap().to_string(), "7");
+        assert!(Amount::parse_human("0.0000000000001", AO_DECIMALS).is_err());
+        assert!(Amount::parse_human("1.2.3", AO_DECIMALS).is_err());
+        assert!(Amount::parse_human("-1", AO_DECIMALS).is_err());
+
+        // beyond f64's 53 bits of precision
+        let wei = Amount::from_raw_str("123456789012345678901234567", LST_DECIMALS).unwrap();
+        assert_eq!(wei.to_string(), "123456789.012345678901234567");
+        assert_eq!(wei.factored(2500, 10000).raw, 30864197253086419725308641);
+        assert_eq!(Amount::new(u128::MAX, 0).factored(1, 1).raw, u128::MAX);
+        assert_eq!(amount.rescale(LST_DECIMALS).unwrap().to_string(), "1.5");
+        assert_eq!(amount.rescale(1).unwrap().raw, 15);
+        assert_eq!(amount.checked_sub(&amount).unwrap().raw, 0);
+        assert_eq!(amount.portion(0.25).to_string(), "0.375");
+        assert_eq!(amount.portion(2.0), amount);
+        assert!(Amount::zero(AO_DECIMALS).checked_sub(&amount).is_none());
+        assert!(amount.rescale(0).is_none());
+
+        let json = serde_json::to_string(&wei).unwrap();
+        assert_eq!(
+            json,
+            r#"{"raw":"123456789012345678901234567","decimals":18}"#
+        );
+        assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), wei);
+
+        let raw: RawAmount = serde_json::from_str(r#""1500""#).unwrap();
+        assert_eq!(raw.with_decimals(3).to_string(), "1.5");
+        assert!(serde_json::from_str::<RawAmount>(r#""1.5e3""#).is_err());
+    }
+}
@@ -2,6 +2,7 @@ use crate::constants::{AO_AUTHORITY, AO_TOKEN_PROCESS, arweave_gateway};
 use anyhow::{Error, anyhow};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tracing::instrument;
 
 #[derive(Debug, Clone, Copy)]
 pub enum AoTokenQuery {
@@ -34,6 +35,11 @@ pub struct AoTokenMessagesPage {
     pub end_cursor: Option<String>,
 }
 
+#[instrument(
+    name = "gateway",
+    skip_all,
+    fields(call = "scan_token_msgs", process_id = %process_id, query = ?query, height = blockheight, cursor = ?after, msgs = tracing::field::Empty)
+)]
 pub fn scan_arweave_block_for_token_msgs(
     process_id: &str,
     query: AoTokenQuery,
@@ -212,6 +218,7 @@ query $querylabel {
         }
     }
 
+    tracing::Span::current().record("msgs", out.len());
     Ok(AoTokenMessagesPage {
         mappings: out,
         has_next_page,
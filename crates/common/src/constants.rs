@@ -1,6 +1,6 @@
-use crate::env::get_env_var;
+use crate::env::load_atlas_toml;
 use serde::Deserialize;
-use std::{fs, io::ErrorKind, sync::OnceLock};
+use std::sync::OnceLock;
 
 // FLP system yield oracle processes -- legacy
 pub const USDS_ORACLE_PID: &str = "qjOMZnan8Vo2gaLaOF1FXbFXOQOn_5sKbYspNSVRyNY";
@@ -31,26 +31,8 @@ pub fn arweave_gateway() -> &'static str {
 }
 
 fn load_arweave_gateway() -> String {
-    let path = get_env_var("ATLAS_CONFIG").unwrap_or_else(|_| "atlas.toml".into());
-    let contents = match fs::read_to_string(&path) {
-        Ok(contents) => contents,
-        Err(err) if err.kind() == ErrorKind::NotFound => {
-            return DEFAULT_ARWEAVE_GATEWAY.to_string();
-        }
-        Err(err) => {
-            eprintln!("failed to read atlas config {path}: {err}");
-            return DEFAULT_ARWEAVE_GATEWAY.to_string();
-        }
-    };
-    let config = match toml::from_str::<AtlasConfig>(&contents) {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("failed to parse atlas config {path}: {err}");
-            return DEFAULT_ARWEAVE_GATEWAY.to_string();
-        }
-    };
-    config
-        .primary_arweave_gateway
+    load_atlas_toml::<AtlasConfig>()
+        .and_then(|config| config.primary_arweave_gateway)
         .filter(|value| !value.trim().is_empty())
         .unwrap_or_else(|| DEFAULT_ARWEAVE_GATEWAY.to_string())
 }
@@ -3,9 +3,11 @@ use crate::projects::INTERNAL_PI_PID;
 use anyhow::{Error, anyhow};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tracing::instrument;
 
 pub const DELEGATION_PID_START_HEIGHT: u32 = 1_608_145;
 
+#[instrument(name = "gateway", skip_all, fields(call = "get_user_delegation_txid", txid = %last_delegation_txid))]
 pub fn get_user_delegation_txid(last_delegation_txid: &str) -> Result<String, Error> {
     let template = r#"
     query GetDetailedTransactions {
@@ -71,6 +73,7 @@ pub fn get_user_delegation_txid(last_delegation_txid: &str) -> Result<String, Er
     Ok(id.to_string())
 }
 
+#[instrument(name = "gateway", skip_all, fields(call = "get_user_last_delegation_txid", address = %address))]
 pub fn get_user_last_delegation_txid(address: &str) -> Result<Vec<String>, Error> {
     let template = r#"
     query GetDetailedTransactions {
@@ -163,6 +166,8 @@ pub fn get_user_last_delegation_txid(address: &str) -> Result<Vec<String>, Error
 pub struct DelegationMappingMeta {
     pub tx_id: String,
     pub height: u32,
+    /// unix seconds of the tx's block
+    pub block_timestamp: u64,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -172,6 +177,7 @@ pub struct DelegationMappingsPage {
     pub end_cursor: Option<String>,
 }
 
+#[instrument(name = "gateway", skip_all, fields(call = "get_delegation_mappings", first = ?first, cursor = ?after))]
 pub fn get_delegation_mappings(
     first: Option<u32>,
     after: Option<&str>,
@@ -202,6 +208,7 @@ $afterclause
         block {
           id
           height
+          timestamp
         }
       }
     }
@@ -264,9 +271,15 @@ $afterclause
             .and_then(|v| v.as_u64())
             .map(|v| v as u32)
             .unwrap_or(0);
+        let block_timestamp = node
+            .get("block")
+            .and_then(|v| v.get("timestamp"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
         out.push(DelegationMappingMeta {
             tx_id: id.to_string(),
             height,
+            block_timestamp,
         });
     }
 
@@ -1,7 +1,30 @@
 use anyhow::Error;
 use dotenvy::dotenv;
+use serde::de::DeserializeOwned;
+use std::{fs, io::ErrorKind};
 
 pub fn get_env_var(key: &str) -> Result<String, Error> {
     dotenv().ok();
     Ok(dotenvy::var(key)?)
 }
+
+/// reads and parses the atlas.toml pointed by `ATLAS_CONFIG` (defaults to `./atlas.toml`),
+/// returns None when the file is missing or malformed
+pub fn load_atlas_toml<T: DeserializeOwned>() -> Option<T> {
+    let path = get_env_var("ATLAS_CONFIG").unwrap_or_else(|_| "atlas.toml".into());
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return None,
+        Err(err) => {
+            tracing::warn!(%path, %err, "failed to read atlas config");
+            return None;
+        }
+    };
+    match toml::from_str::<T>(&contents) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            tracing::warn!(%path, %err, "failed to parse atlas config");
+            None
+        }
+    }
+}
@@ -0,0 +1,339 @@
+/// bulk export queries shared by the server export endpoints and the
+/// `atlas export` CLI command.
+///
+/// the rows are never decoded in rust: clickhouse formats them itself
+/// (`CSVWithNames`, `JSONEachRow`, `Parquet`) and the callers stream the
+/// raw response bytes, so an export of any size runs in constant memory.
+/// timestamps are exported as unix milliseconds, like the JSON APIs.
+use anyhow::{Error, anyhow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    Csv,
+    #[default]
+    Ndjson,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            "parquet" => Ok(Self::Parquet),
+            other => Err(anyhow!(
+                "unsupported export format {other} (expected csv, ndjson or parquet)"
+            )),
+        }
+    }
+
+    /// clickhouse output format name
+    pub fn clickhouse_format(&self) -> &'static str {
+        match self {
+            Self::Csv => "CSVWithNames",
+            Self::Ndjson => "JSONEachRow",
+            Self::Parquet => "Parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportDataset {
+    /// latest cycle positions of every ticker delegating to a FLP
+    Delegators { project: String },
+    /// one row per (mapping tx, delegated wallet)
+    DelegationMappings,
+    /// ao.TN.1 token messages with their transfer tags as columns
+    TokenTransfers { token: String },
+    /// ao.N.1 messages with their tags
+    MainnetMessages,
+    /// per day explorer totals, legacy network or mainnet
+    ExplorerDays { mainnet: bool },
+}
+
+impl ExportDataset {
+    /// short name, used for the exported file name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Delegators { .. } => "delegators",
+            Self::DelegationMappings => "delegation-mappings",
+            Self::TokenTransfers { .. } => "token-transfers",
+            Self::MainnetMessages => "mainnet-messages",
+            Self::ExplorerDays { mainnet: false } => "explorer-days",
+            Self::ExplorerDays { mainnet: true } => "mainnet-explorer-days",
+        }
+    }
+
+    pub fn query(&self, filter: &ExportFilter) -> ExportQuery {
+        match self {
+            Self::Delegators { project } => delegators_query(project, filter),
+            Self::DelegationMappings => delegation_mappings_query(filter),
+            Self::TokenTransfers { token } => token_transfers_query(token, filter),
+            Self::MainnetMessages => mainnet_messages_query(filter),
+            Self::ExplorerDays { mainnet } => explorer_days_query(*mainnet, filter),
+        }
+    }
+}
+
+/// optional filters, named after the JSON API query params. a filter that
+/// doesn't apply to the exported dataset is ignored
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub ticker: Option<String>,
+    pub wallet: Option<String>,
+    pub protocol: Option<String>,
+    pub source: Option<String>,
+    pub action: Option<String>,
+    pub sender: Option<String>,
+    pub recipient: Option<String>,
+    pub block_min: Option<u32>,
+    pub block_max: Option<u32>,
+    /// inclusive `YYYY-MM-DD` bounds of the explorer days
+    pub from_day: Option<String>,
+    pub to_day: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportBind {
+    Str(String),
+    U32(u32),
+}
+
+/// sql with `?` placeholders, bound in order
+#[derive(Debug, Clone)]
+pub struct ExportQuery {
+    pub sql: String,
+    pub binds: Vec<ExportBind>,
+}
+
+#[derive(Default)]
+struct Clauses {
+    clauses: Vec<&'static str>,
+    binds: Vec<ExportBind>,
+}
+
+impl Clauses {
+    fn push(&mut self, clause: &'static str, bind: ExportBind) {
+        self.clauses.push(clause);
+        self.binds.push(bind);
+    }
+
+    fn push_str(&mut self, clause: &'static str, value: Option<&String>) {
+        if let Some(value) = value {
+            self.push(clause, ExportBind::Str(value.clone()));
+        }
+    }
+
+    fn push_u32(&mut self, clause: &'static str, value: Option<u32>) {
+        if let Some(value) = value {
+            self.push(clause, ExportBind::U32(value));
+        }
+    }
+
+    fn sql(&self, keyword: &str) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" {keyword} {}", self.clauses.join(" and "))
+        }
+    }
+}
+
+fn delegators_query(project: &str, filter: &ExportFilter) -> ExportQuery {
+    let mut latest = Clauses::default();
+    latest.push("project = ?", ExportBind::Str(project.to_string()));
+    latest.push_str("ticker = ?", filter.ticker.as_ref());
+    let sql = format!(
+        "with latest as (\
+            select ticker, max(ts) as ts \
+            from flp_positions final{} \
+            group by ticker\
+         ) \
+         select toUnixTimestamp64Milli(p.ts) as ts, p.ticker, p.wallet, p.eoa, \
+            toString(p.project) as project, p.factor, p.amount, p.ar_amount \
+         from flp_positions p final \
+         inner join latest l on p.ticker = l.ticker and p.ts = l.ts \
+         where p.project = ? \
+         order by p.ticker, p.wallet",
+        latest.sql("where")
+    );
+    let mut binds = latest.binds;
+    binds.push(ExportBind::Str(project.to_string()));
+    ExportQuery { sql, binds }
+}
+
+fn delegation_mappings_query(filter: &ExportFilter) -> ExportQuery {
+    let mut wheres = Clauses::default();
+    wheres.push_str("wallet_from = ?", filter.wallet.as_ref());
+    wheres.push_u32("height >= ?", filter.block_min);
+    wheres.push_u32("height <= ?", filter.block_max);
+    let sql = format!(
+        "select toUnixTimestamp64Milli(ts) as ts, height, tx_id, wallet_from, wallet_to, factor \
+         from delegation_mappings final{} \
+         order by height, tx_id, wallet_to",
+        wheres.sql("where")
+    );
+    ExportQuery {
+        sql,
+        binds: wheres.binds,
+    }
+}
+
+fn token_transfers_query(token: &str, filter: &ExportFilter) -> ExportQuery {
+    let mut wheres = Clauses::default();
+    wheres.push("m.token = ?", ExportBind::Str(token.to_string()));
+    wheres.push_str("m.source = ?", filter.source.as_ref());
+    wheres.push_u32("m.block_height >= ?", filter.block_min);
+    wheres.push_u32("m.block_height <= ?", filter.block_max);
+    // tag filters apply to the pivoted columns
+    let mut havings = Clauses::default();
+    havings.push_str(
+        "lowerUTF8(tag_action) = lowerUTF8(?)",
+        filter.action.as_ref(),
+    );
+    havings.push_str("tag_sender = ?", filter.sender.as_ref());
+    havings.push_str("tag_recipient = ?", filter.recipient.as_ref());
+    let sql = format!(
+        "select toUnixTimestamp64Milli(m.ts) as ts, m.source, m.block_height, m.block_timestamp, \
+            m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, \
+            anyIf(t.tag_value, t.tag_key = 'Action') as tag_action, \
+            anyIf(t.tag_value, t.tag_key = 'Sender') as tag_sender, \
+            anyIf(t.tag_value, t.tag_key = 'Recipient') as tag_recipient, \
+            anyIf(t.tag_value, t.tag_key = 'Quantity') as tag_quantity \
+         from ao_token_messages m final \
+         left join ao_token_message_tags t \
+           on t.token = m.token and t.source = m.source and t.block_height = m.block_height and t.msg_id = m.msg_id\
+         {} \
+         group by m.ts, m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size\
+         {} \
+         order by m.block_height, m.msg_id",
+        wheres.sql("where"),
+        havings.sql("having")
+    );
+    let mut binds = wheres.binds;
+    binds.extend(havings.binds);
+    ExportQuery { sql, binds }
+}
+
+fn mainnet_messages_query(filter: &ExportFilter) -> ExportQuery {
+    let mut wheres = Clauses::default();
+    wheres.push_str("m.protocol = ?", filter.protocol.as_ref());
+    wheres.push_u32("m.block_height >= ?", filter.block_min);
+    wheres.push_u32("m.block_height <= ?", filter.block_max);
+    let sql = format!(
+        "select toUnixTimestamp64Milli(m.ts) as ts, m.protocol, m.block_height, m.block_timestamp, \
+            m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, \
+            arrayFilter(x -> x.1 != '', groupArray(tuple(ifNull(t.tag_key, ''), ifNull(t.tag_value, '')))) as tags \
+         from ao_mainnet_messages m final \
+         left join ao_mainnet_message_tags t \
+           on t.protocol = m.protocol and t.block_height = m.block_height and t.msg_id = m.msg_id\
+         {} \
+         group by m.ts, m.protocol, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size \
+         order by m.block_height, m.msg_id",
+        wheres.sql("where")
+    );
+    ExportQuery {
+        sql,
+        binds: wheres.binds,
+    }
+}
+
+fn explorer_days_query(mainnet: bool, filter: &ExportFilter) -> ExportQuery {
+    let table = if mainnet {
+        "ao_mainnet_explorer"
+    } else {
+        "atlas_explorer"
+    };
+    let mut wheres = Clauses::default();
+    wheres.push_str("toDate(ts) >= toDate(?)", filter.from_day.as_ref());
+    wheres.push_str("toDate(ts) <= toDate(?)", filter.to_day.as_ref());
+    let sql = format!(
+        "select toDate(ts) as day, count() as processed_blocks, sum(tx_count) as txs, \
+            sum(eval_count) as evals, sum(transfer_count) as transfers, \
+            sum(new_process_count) as new_processes_over_blocks, \
+            sum(new_module_count) as new_modules_over_blocks, \
+            sum(active_users) as active_users_over_blocks, \
+            sum(active_processes) as active_processes_over_blocks, \
+            max(tx_count_rolling) as txs_roll, max(processes_rolling) as processes_roll, \
+            max(modules_rolling) as modules_roll \
+         from {table} final{} \
+         group by day \
+         order by day",
+        wheres.sql("where")
+    );
+    ExportQuery {
+        sql,
+        binds: wheres.binds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::{ExportDataset, ExportFilter, ExportFormat};
+
+    #[test]
+    fn export_binds_match_placeholders_test() {
+        let filter = ExportFilter {
+            ticker: Some("usds".to_string()),
+            wallet: Some("wallet".to_string()),
+            protocol: Some("A".to_string()),
+            source: Some("transfer".to_string()),
+            action: Some("Transfer".to_string()),
+            sender: Some("sender".to_string()),
+            recipient: Some("recipient".to_string()),
+            block_min: Some(1_600_000),
+            block_max: Some(1_600_100),
+            from_day: Some("2025-01-01".to_string()),
+            to_day: Some("2025-02-01".to_string()),
+        };
+        let datasets = [
+            ExportDataset::Delegators {
+                project: "pid".to_string(),
+            },
+            ExportDataset::DelegationMappings,
+            ExportDataset::TokenTransfers {
+                token: "ao".to_string(),
+            },
+            ExportDataset::MainnetMessages,
+            ExportDataset::ExplorerDays { mainnet: true },
+        ];
+        for dataset in datasets {
+            for filter in [filter.clone(), ExportFilter::default()] {
+                let query = dataset.query(&filter);
+                assert_eq!(
+                    query.sql.matches('?').count(),
+                    query.binds.len(),
+                    "{}",
+                    dataset.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn export_format_parse_test() {
+        assert_eq!(ExportFormat::parse("CSV").unwrap(), ExportFormat::Csv);
+        assert_eq!(ExportFormat::parse("jsonl").unwrap(), ExportFormat::Ndjson);
+        assert_eq!(
+            ExportFormat::parse("parquet").unwrap().clickhouse_format(),
+            "Parquet"
+        );
+        assert!(ExportFormat::parse("xlsx").is_err());
+    }
+}
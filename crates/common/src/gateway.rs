@@ -1,18 +1,28 @@
-use crate::constants::arweave_gateway;
+use crate::{
+    amount::{AR_DECIMALS, Amount},
+    constants::arweave_gateway,
+};
 use anyhow::Error;
+use tracing::instrument;
 
 /// downloads an Arweave `txid` data and return Vec<u8> Body
+#[instrument(name = "gateway", skip_all, fields(call = "download_tx_data", txid = %txid))]
 pub fn download_tx_data(txid: &str) -> Result<Vec<u8>, Error> {
     let url = format!("{}/{txid}", arweave_gateway());
     let mut req = ureq::get(url).call()?;
     Ok(req.body_mut().read_to_vec()?)
 }
 
-/// gets the AR balance of a given Arweave address
-pub fn get_ar_balance(address: &str) -> Result<f64, Error> {
+/// gets the balance of a given Arweave address in winston
+#[instrument(name = "gateway", skip_all, fields(call = "get_ar_balance", address = %address))]
+pub fn get_ar_balance_winston(address: &str) -> Result<u128, Error> {
     let url = format!("{}/wallet/{address}/balance", arweave_gateway());
     let mut req = ureq::get(url).call()?;
     let winston = req.body_mut().read_to_string()?;
-    let winston = winston.parse::<f64>()?;
-    Ok(winston * 1e-12)
+    Ok(Amount::from_raw_str(&winston, AR_DECIMALS)?.raw)
+}
+
+/// gets the AR balance of a given Arweave address, exact to the winston
+pub fn get_ar_balance(address: &str) -> Result<Amount, Error> {
+    Ok(Amount::new(get_ar_balance_winston(address)?, AR_DECIMALS))
 }
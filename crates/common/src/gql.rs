@@ -3,8 +3,10 @@ use crate::constants::{
     USDS_ORACLE_PID, USDS_STAKING_ADDRESS, arweave_gateway,
 };
 pub use crate::delegation::{get_user_delegation_txid, get_user_last_delegation_txid};
+use crate::ids::{EvmAddress, ProcessId};
 use anyhow::{Error, anyhow};
 use serde_json::{Value, json};
+use tracing::instrument;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Oracle {
@@ -17,8 +19,8 @@ pub enum Oracle {
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct OracleMetadata {
-    pub ao_pid: String,
-    pub evm_address: String,
+    pub ao_pid: ProcessId,
+    pub evm_address: EvmAddress,
 }
 
 impl Oracle {
@@ -37,16 +39,16 @@ impl Oracle {
     pub fn metadata(&self) -> Result<OracleMetadata, Error> {
         match self {
             Oracle::USDS => Ok(OracleMetadata {
-                ao_pid: USDS_ORACLE_PID.to_string(),
-                evm_address: USDS_STAKING_ADDRESS.to_string(),
+                ao_pid: USDS_ORACLE_PID.parse()?,
+                evm_address: USDS_STAKING_ADDRESS.parse()?,
             }),
             Oracle::DAI => Ok(OracleMetadata {
-                ao_pid: DAI_ORACLE_PID.to_string(),
-                evm_address: DAI_STAKING_ADDRESS.to_string(),
+                ao_pid: DAI_ORACLE_PID.parse()?,
+                evm_address: DAI_STAKING_ADDRESS.parse()?,
             }),
             Oracle::STETH => Ok(OracleMetadata {
-                ao_pid: STETH_ORACLE_PID.to_string(),
-                evm_address: STETH_STAKING_ADDRESS.to_string(),
+                ao_pid: STETH_ORACLE_PID.parse()?,
+                evm_address: STETH_STAKING_ADDRESS.parse()?,
             }),
             _ => Err(anyhow!("metadata not supported for this oracle type")),
         }
@@ -160,6 +162,7 @@ impl OracleStakers {
         Ok(self)
     }
 
+    #[instrument(name = "gateway", skip_all, fields(call = "oracle_set_balances", oracle = ?self.oracle))]
     pub fn send(mut self) -> Result<Self, Error> {
         let url = format!("{}/graphql", arweave_gateway());
         let req = ureq::post(url)
@@ -0,0 +1,224 @@
+/// validated identifiers shared by the crates. Arweave addresses, ao process
+/// and message ids are 43 base64url chars (a base64url sha-256 digest), EVM
+/// addresses are normalized to their EIP-55 checksum form.
+///
+/// every type (de)serializes as a plain string and fails on malformed input,
+/// so a typo is an error instead of an empty result.
+use anyhow::{Error, anyhow};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
+
+/// length of a base64url encoded 32 bytes digest, without padding
+pub const BASE64URL_ID_LEN: usize = 43;
+
+fn is_base64url_id(value: &str) -> bool {
+    value.len() == BASE64URL_ID_LEN
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+macro_rules! string_id {
+    ($name:ident) => {
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = Error;
+
+            fn try_from(value: String) -> Result<Self, Error> {
+                Self::parse(&value)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(value: &str) -> Result<Self, Error> {
+                Self::parse(value)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+    };
+}
+
+macro_rules! base64url_id {
+    ($(#[$doc:meta])* $name:ident, $label:literal) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn parse(value: &str) -> Result<Self, Error> {
+                let value = value.trim();
+                if !is_base64url_id(value) {
+                    return Err(anyhow!(
+                        "invalid {} {value:?}, expected {BASE64URL_ID_LEN} base64url chars",
+                        $label
+                    ));
+                }
+                Ok(Self(value.to_string()))
+            }
+        }
+
+        string_id!($name);
+    };
+}
+
+base64url_id!(
+    /// Arweave wallet address
+    ArAddress,
+    "Arweave address"
+);
+base64url_id!(
+    /// ao process id
+    ProcessId,
+    "process id"
+);
+base64url_id!(
+    /// ao message or Arweave transaction id
+    MessageId,
+    "message id"
+);
+
+/// EVM account address in its EIP-55 checksum form
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EvmAddress(String);
+
+impl EvmAddress {
+    /// accepts all-lowercase or all-uppercase hex, and mixed case only when
+    /// it is a valid checksum
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let value = value.trim();
+        let hex = value
+            .strip_prefix("0x")
+            .filter(|hex| hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                anyhow!("invalid EVM address {value:?}, expected 0x and 40 hex chars")
+            })?;
+        let checksummed = to_checksum(&hex.to_ascii_lowercase());
+        let mixed_case = hex.bytes().any(|b| b.is_ascii_lowercase())
+            && hex.bytes().any(|b| b.is_ascii_uppercase());
+        if mixed_case && checksummed[2..] != *hex {
+            return Err(anyhow!("invalid EIP-55 checksum for EVM address {value}"));
+        }
+        Ok(Self(checksummed))
+    }
+}
+
+string_id!(EvmAddress);
+
+/// EIP-55: uppercase every hex letter whose nibble in the keccak-256 of the
+/// lowercase address is 8 or more
+fn to_checksum(lower_hex: &str) -> String {
+    let hash = keccak256(lower_hex.as_bytes());
+    let mut out = String::with_capacity(42);
+    out.push_str("0x");
+    for (idx, c) in lower_hex.chars().enumerate() {
+        let nibble = (hash[idx / 2] >> (if idx % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        out.push(if nibble >= 8 {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        });
+    }
+    out
+}
+
+/// the original keccak-256 used by ethereum, not the padding of NIST sha3
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ids::{ArAddress, EvmAddress, MessageId, keccak256};
+
+    #[test]
+    fn keccak256_test() {
+        let hex: String = keccak256(b"").iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(
+            hex,
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn ids_parse_test() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let eoa = EvmAddress::parse(&checksummed.to_ascii_lowercase()).unwrap();
+        assert_eq!(eoa, checksummed);
+        assert_eq!(
+            EvmAddress::parse("0xFB6916095CA1DF60BB79CE92CE3EA74C37C5D359").unwrap(),
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359"
+        );
+        assert!(EvmAddress::parse("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(EvmAddress::parse("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea").is_err());
+
+        let wallet = " 4hXj_E-5fAKmo4E8KjgQvuDJKAFk9P2grhycVmISDLs";
+        assert_eq!(ArAddress::parse(wallet).unwrap(), wallet.trim());
+        assert!(ArAddress::parse("4hXj_E-5fAKmo4E8KjgQvuDJKAFk9P2grhycVmISDL").is_err());
+        assert!(MessageId::parse("4hXj_E-5fAKmo4E8KjgQvuDJKAFk9P2grhycVmISDL=").is_err());
+        let json: Result<ArAddress, _> = serde_json::from_str("\"not an address\"");
+        assert!(json.is_err());
+    }
+}
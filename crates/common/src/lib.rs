@@ -1,9 +1,13 @@
+pub mod amount;
 pub mod ao_token;
 pub mod constants;
 pub mod delegation;
 pub mod env;
+pub mod export;
 pub mod gateway;
 pub mod gql;
+pub mod ids;
 pub mod mainnet;
 pub mod minting;
 pub mod projects;
+pub mod telemetry;
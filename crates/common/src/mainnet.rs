@@ -12,6 +12,7 @@
 use anyhow::{Error, anyhow};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tracing::instrument;
 
 const MAINNET_ARWEAVE_GATEWAY: &str = "https://permagate.io";
 
@@ -64,6 +65,11 @@ pub struct MainnetBlockMessagesPage {
     pub end_cursor: Option<String>,
 }
 
+#[instrument(
+    name = "gateway",
+    skip_all,
+    fields(call = "scan_mainnet_msgs", protocol = ?data_protocol, height = blockheight, cursor = ?after, msgs = tracing::field::Empty)
+)]
 pub fn scan_arweave_block_for_msgs(
     data_protocol: DataProtocol,
     blockheight: u32,
@@ -223,6 +229,7 @@ query aoMainnet {
         });
     }
 
+    tracing::Span::current().record("msgs", out.len());
     if out.is_empty() {
         return Err(anyhow!("error: no ao message id found for the given query"));
     }
@@ -238,6 +245,7 @@ struct NetworkInfo {
     height: u64,
 }
 
+#[instrument(name = "gateway", skip_all, fields(call = "get_network_height"))]
 pub fn get_network_height() -> Result<u64, Error> {
     let mut res = ureq::get("https://arweave.net/info").call()?;
     let body = res.body_mut().read_to_string()?;
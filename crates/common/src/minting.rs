@@ -1,8 +1,10 @@
 use crate::constants::{AO_AUTHORITY, arweave_gateway};
 use anyhow::{Error, anyhow};
 use serde_json::{Value, json};
+use tracing::instrument;
 
 /// Action : Add-Own-Mint-Report
+#[instrument(name = "gateway", skip_all, fields(call = "get_flp_own_minting_report", flp_id = %flp_id))]
 pub fn get_flp_own_minting_report(flp_id: &str) -> Result<String, Error> {
     let template = r#"
     query GetDetailedTransactions {
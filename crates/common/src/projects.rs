@@ -1,3 +1,4 @@
+use crate::amount::{Amount, RawAmount};
 use serde::{Deserialize, Serialize};
 
 // FLPs PIDs
@@ -46,7 +47,7 @@ pub struct Project {
     pub ticker: String,
     pub pid: String,
     pub token: String,
-    pub denomination: u32, // todo! add more metadata
+    pub denomination: u8, // todo! add more metadata
 }
 
 macro_rules! project {
@@ -70,43 +71,60 @@ impl Project {
         "PI",
         INTERNAL_PI_PID,
         PI_TOKEN,
-        12u32
+        12u8
     );
-    project!(pi, "Permaweb Index", "PI", PI_PID, PI_TOKEN, 12u32);
-    project!(load, "Load Network", "LOAD", LOAD_PID, LOAD_TOKEN, 18u32);
-    project!(apus, "Apus Network", "APUS", APUS_PID, APUS_TOKEN, 12u32);
-    project!(botega, "Botega Token", "BOTG", BOTG_PID, BOTG_TOKEN, 18u32);
-    project!(aos, "AO Strategy", "AOS", AOS_PID, AOS_TOKEN, 18u32);
-    project!(wndr, "Wander", "WNDR", WNDR_PID, WNDR_TOKEN, 18u32);
-    project!(action, "Action", "ACTION", ACTION_PID, ACTION_TOKEN, 18u32);
+    project!(pi, "Permaweb Index", "PI", PI_PID, PI_TOKEN, 12u8);
+    project!(load, "Load Network", "LOAD", LOAD_PID, LOAD_TOKEN, 18u8);
+    project!(apus, "Apus Network", "APUS", APUS_PID, APUS_TOKEN, 12u8);
+    project!(botega, "Botega Token", "BOTG", BOTG_PID, BOTG_TOKEN, 18u8);
+    project!(aos, "AO Strategy", "AOS", AOS_PID, AOS_TOKEN, 18u8);
+    project!(wndr, "Wander", "WNDR", WNDR_PID, WNDR_TOKEN, 18u8);
+    project!(action, "Action", "ACTION", ACTION_PID, ACTION_TOKEN, 18u8);
     project!(
         space,
         "Space Money",
         "SMONEY",
         SMONEY_PID,
         SMONEY_TOKEN,
-        18u32
+        18u8
     );
-    project!(lqd, "Liquid Ops", "LQD", LQD_PID, LQD_TOKEN, 18u32);
-    project!(game, "ArcAO", "GAME", GAME_PID, GAME_TOKEN, 18u32);
-    project!(nau, "Nau", "NAU", NAU_PID, NAU_TOKEN, 18u32);
+    project!(lqd, "Liquid Ops", "LQD", LQD_PID, LQD_TOKEN, 18u8);
+    project!(game, "ArcAO", "GAME", GAME_PID, GAME_TOKEN, 18u8);
+    project!(nau, "Nau", "NAU", NAU_PID, NAU_TOKEN, 18u8);
     project!(
         rella,
         "LLAMMA REBORN",
         "RELLA",
         RELLA_PID,
         RELLA_TOKEN,
-        18u32
+        18u8
     );
-    project!(ario, "AR.IO", "ARIO", ARIO_PID, ARIO_TOKEN, 6u32);
-    project!(pixl, "PIXL Token", "PIXL", PIXL_PID, PIXL_TOKEN, 6u32);
-    project!(vela, "Vela", "VELA", VELA_PID, VELA_TOKEN, 18u32);
-    project!(inf, "Influence Market", "INF", INF_PID, INF_TOKEN, 18u32);
-    project!(pi_legacy, "PI Legacy", "PI", PI_LEGACY_PID, PI_TOKEN, 12u32);
+    project!(ario, "AR.IO", "ARIO", ARIO_PID, ARIO_TOKEN, 6u8);
+    project!(pixl, "PIXL Token", "PIXL", PIXL_PID, PIXL_TOKEN, 6u8);
+    project!(vela, "Vela", "VELA", VELA_PID, VELA_TOKEN, 18u8);
+    project!(inf, "Influence Market", "INF", INF_PID, INF_TOKEN, 18u8);
+    project!(pi_legacy, "PI Legacy", "PI", PI_LEGACY_PID, PI_TOKEN, 12u8);
     // todo! add more active FLPs if any
 }
 
 impl Project {
+    /// decimals of the project token
+    pub fn decimals(&self) -> u8 {
+        self.denomination
+    }
+
+    /// a raw amount of the project token
+    pub fn amount(&self, raw: RawAmount) -> Amount {
+        raw.with_decimals(self.decimals())
+    }
+
+    /// the tracked FLP of process `pid`
+    pub fn get(pid: &str) -> Option<Project> {
+        Project::get_all()
+            .into_iter()
+            .find(|project| project.pid == pid)
+    }
+
     pub fn is_flp_project(pid: &str) -> bool {
         matches!(
             pid,
@@ -0,0 +1,84 @@
+/// tracing setup shared by the Atlas binaries (indexer, server).
+///
+/// the level filter is resolved from `RUST_LOG`, then the atlas.toml
+/// `[logging] level`, defaulting to `info`. the output format is resolved
+/// from `ATLAS_LOG_FORMAT`, then `[logging] format`: `json` (default) or `text`.
+///
+/// spans are emitted on close with their busy/idle timings, so a slow HTTP
+/// request can be correlated with the gateway calls made inside its span.
+use crate::env::{get_env_var, load_atlas_toml};
+use serde::Deserialize;
+use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
+
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    Text,
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "text" | "pretty" | "plain" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct FileConfig {
+    #[serde(default)]
+    logging: FileLoggingConfig,
+}
+
+#[derive(Deserialize, Default)]
+struct FileLoggingConfig {
+    level: Option<String>,
+    format: Option<String>,
+}
+
+/// installs the global subscriber, safe to call more than once
+/// (later calls are no-ops)
+pub fn init_tracing() {
+    let file = load_atlas_toml::<FileConfig>().unwrap_or_default().logging;
+    let filter = get_env_var("RUST_LOG")
+        .ok()
+        .or(file.level)
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string());
+    let filter = EnvFilter::try_new(&filter).unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL));
+    let format = get_env_var("ATLAS_LOG_FORMAT")
+        .ok()
+        .or(file.format)
+        .and_then(|v| LogFormat::parse(&v))
+        .unwrap_or(LogFormat::Json);
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(true);
+    // ignore the error raised when a subscriber is already installed
+    let _ = match format {
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+        LogFormat::Text => builder.try_init(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telemetry::LogFormat;
+
+    #[test]
+    fn log_format_parse_test() {
+        assert_eq!(LogFormat::parse("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse(" text "), Some(LogFormat::Text));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+}
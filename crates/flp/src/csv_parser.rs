@@ -1,43 +1,197 @@
-use crate::types::{DelegationMappingsRow, SetBalancesData};
-use anyhow::Error;
-use common::gateway::download_tx_data;
-use csv::{Reader, StringRecord};
+use crate::types::{DelegationMappingsRow, MAX_FACTOR, SetBalancesData};
+use anyhow::{Error, anyhow};
+use common::{gateway::download_tx_data, ids::ArAddress};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::io::Read;
 
-pub fn parse_flp_balances_setting_res(txid: &str) -> Result<Vec<SetBalancesData>, Error> {
-    let mut res: Vec<SetBalancesData> = Vec::new();
-    let data = download_tx_data(txid)?;
-    let str_data = String::from_utf8(data)?;
-    let mut rdr = Reader::from_reader(str_data.as_bytes());
-    // setting custom header given ao's flp Set-Balance dont have headers
-    rdr.set_headers(StringRecord::from(vec!["eoa", "amount", "ar_address"]));
-
-    for row in rdr.deserialize() {
-        let record: SetBalancesData = row?;
-        res.push(record);
+// ao's flp Set-Balances and Delegation-Mappings CSVs dont have headers
+const SET_BALANCES_HEADERS: [&str; 3] = ["eoa", "amount", "ar_address"];
+const DELEGATION_MAPPINGS_HEADERS: [&str; 3] = ["walletFrom", "walletTo", "factor"];
+
+/// a CSV row left out of a lenient parse
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RejectedRow {
+    /// 1-based line of the row in the file
+    pub line: u64,
+    pub raw: String,
+    pub reason: String,
+}
+
+/// the valid rows of a CSV and the ones rejected, in file order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LenientCsv<T> {
+    pub rows: Vec<T>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+impl<T> LenientCsv<T> {
+    /// the rows, or an error naming the first rejected line
+    pub fn strict(self) -> Result<Vec<T>, Error> {
+        match self.rejected.first() {
+            Some(row) => Err(anyhow!("line {}: {}", row.line, row.reason)),
+            None => Ok(self.rows),
+        }
     }
-    Ok(res)
+}
+
+pub fn parse_flp_balances_setting_res(txid: &str) -> Result<Vec<SetBalancesData>, Error> {
+    parse_flp_balances_setting_res_lenient(txid)?.strict()
 }
 
 pub fn parse_delegation_mappings_res(txid: &str) -> Result<Vec<DelegationMappingsRow>, Error> {
-    let mut res: Vec<DelegationMappingsRow> = Vec::new();
-    let data = download_tx_data(txid)?;
-    let str_data = String::from_utf8(data)?;
-    let mut rdr = Reader::from_reader(str_data.as_bytes());
-    // setting custom header given ao's msg Delegation-Mappings dont have headers
-    rdr.set_headers(StringRecord::from(vec!["walletFrom", "walletTo", "factor"]));
-
-    for row in rdr.deserialize() {
-        let record: DelegationMappingsRow = row?;
-        res.push(record);
+    parse_delegation_mappings_res_lenient(txid)?.strict()
+}
+
+/// Set-Balances rows of `txid`, skipping malformed ones instead of failing
+pub fn parse_flp_balances_setting_res_lenient(
+    txid: &str,
+) -> Result<LenientCsv<SetBalancesData>, Error> {
+    Ok(parse_set_balances_csv(&download_tx_data(txid)?))
+}
+
+/// Delegation-Mappings rows of `txid`, skipping malformed ones instead of failing
+pub fn parse_delegation_mappings_res_lenient(
+    txid: &str,
+) -> Result<LenientCsv<DelegationMappingsRow>, Error> {
+    Ok(parse_delegation_mappings_csv(&download_tx_data(txid)?))
+}
+
+/// EOA, raw LST amount and Arweave address per line
+pub fn parse_set_balances_csv(data: &[u8]) -> LenientCsv<SetBalancesData> {
+    set_balances_rows(data).collect()
+}
+
+/// delegator, target and factor out of `MAX_FACTOR` per line
+pub fn parse_delegation_mappings_csv(data: &[u8]) -> LenientCsv<DelegationMappingsRow> {
+    delegation_mappings_rows(data).collect()
+}
+
+/// streams the Set-Balances rows of a CSV file, cache or fixture
+pub fn set_balances_rows<R: Read>(reader: R) -> CsvRows<R, SetBalancesData> {
+    // amounts are checked as they deserialize
+    CsvRows::new(reader, &SET_BALANCES_HEADERS, |_: &SetBalancesData| Ok(()))
+}
+
+/// streams the rows of a Delegation-Mappings CSV, one preference at a time
+pub fn delegation_mappings_rows<R: Read>(reader: R) -> CsvRows<R, DelegationMappingsRow> {
+    CsvRows::new(
+        reader,
+        &DELEGATION_MAPPINGS_HEADERS,
+        |row: &DelegationMappingsRow| {
+            ArAddress::parse(&row.wallet_to)?;
+            if row.factor > MAX_FACTOR {
+                return Err(anyhow!("factor {} over {MAX_FACTOR}", row.factor));
+            }
+            Ok(())
+        },
+    )
+}
+
+/// rows of a headerless CSV parsed one record at a time, malformed ones
+/// yielded as `Err` so the caller decides to skip or stop
+pub struct CsvRows<R, T> {
+    reader: Reader<R>,
+    headers: StringRecord,
+    record: StringRecord,
+    validate: fn(&T) -> Result<(), Error>,
+    done: bool,
+}
+
+impl<R: Read, T> CsvRows<R, T> {
+    fn new(reader: R, headers: &[&str], validate: fn(&T) -> Result<(), Error>) -> Self {
+        CsvRows {
+            reader: ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(reader),
+            headers: StringRecord::from(headers.to_vec()),
+            record: StringRecord::new(),
+            validate,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> CsvRows<R, T> {
+    fn parse_record(&self) -> Result<T, Error> {
+        if self.record.len() != self.headers.len() {
+            return Err(anyhow!(
+                "expected {} fields, found {}",
+                self.headers.len(),
+                self.record.len()
+            ));
+        }
+        let row = self
+            .record
+            .deserialize::<T>(Some(&self.headers))
+            .map_err(|err| match err.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => anyhow!("{err}"),
+                _ => anyhow!("{err}"),
+            })?;
+        (self.validate)(&row)?;
+        Ok(row)
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for CsvRows<R, T> {
+    type Item = Result<T, RejectedRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let line = self.reader.position().line();
+        match self.reader.read_record(&mut self.record) {
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Ok(true) => {
+                let line = self.record.position().map_or(line, |pos| pos.line());
+                Some(self.parse_record().map_err(|err| RejectedRow {
+                    line,
+                    raw: self.record.iter().collect::<Vec<_>>().join(","),
+                    reason: err.to_string(),
+                }))
+            }
+            Err(err) => {
+                // the reader can't move past a failed read
+                self.done = matches!(err.kind(), csv::ErrorKind::Io(_));
+                Some(Err(RejectedRow {
+                    line: err.position().map_or(line, |pos| pos.line()),
+                    raw: String::new(),
+                    reason: err.to_string(),
+                }))
+            }
+        }
+    }
+}
+
+impl<T> FromIterator<Result<T, RejectedRow>> for LenientCsv<T> {
+    fn from_iter<I: IntoIterator<Item = Result<T, RejectedRow>>>(iter: I) -> Self {
+        let mut out = LenientCsv {
+            rows: Vec::new(),
+            rejected: Vec::new(),
+        };
+        for row in iter {
+            match row {
+                Ok(row) => out.rows.push(row),
+                Err(rejected) => out.rejected.push(rejected),
+            }
+        }
+        out
     }
-    Ok(res)
 }
 
 #[cfg(test)]
 
 mod tests {
-    use crate::csv_parser::{parse_delegation_mappings_res, parse_flp_balances_setting_res};
-    use common::gql::OracleStakers;
+    use crate::csv_parser::{
+        delegation_mappings_rows, parse_delegation_mappings_csv, parse_delegation_mappings_res,
+        parse_flp_balances_setting_res, parse_set_balances_csv,
+    };
+    use common::{amount::RawAmount, gql::OracleStakers};
 
     #[test]
     fn parse_flp_balances_setting_res_test() {
@@ -63,4 +217,46 @@ mod tests {
         println!(" {:?} {:?}", res, res.len());
         assert!(res.len() == 6898)
     }
+
+    #[test]
+    fn lenient_csv_test() {
+        let parsed = parse_set_balances_csv(include_bytes!("../fixtures/set_balances.csv"));
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(
+            parsed.rows[0].eoa,
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+        assert_eq!(parsed.rows[1].amount, RawAmount(250_000_000_000_000_000));
+        let lines: Vec<u64> = parsed.rejected.iter().map(|row| row.line).collect();
+        assert_eq!(lines, vec![2, 3, 5]);
+        assert!(parsed.rejected[0].reason.contains("invalid EVM address"));
+        assert!(parsed.rejected[2].reason.contains("expected 3 fields"));
+        assert!(
+            parsed
+                .clone()
+                .strict()
+                .unwrap_err()
+                .to_string()
+                .starts_with("line 2")
+        );
+    }
+
+    #[test]
+    fn delegation_mappings_stream_test() {
+        let data: &[u8] = include_bytes!("../fixtures/delegation_mappings.csv");
+        let mut rows = delegation_mappings_rows(data);
+        let first = rows.next().unwrap().unwrap();
+        assert_eq!(first.factor, 3000);
+        let rest: Vec<_> = rows.collect();
+        assert_eq!(rest.len(), 3);
+        assert_eq!(
+            rest[1].as_ref().unwrap_err().reason,
+            "factor 30000 over 10000"
+        );
+
+        let parsed = parse_delegation_mappings_csv(data);
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(parsed.rejected.len(), 2);
+        assert_eq!(parsed.rejected[1].line, 3);
+    }
 }
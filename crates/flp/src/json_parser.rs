@@ -1,18 +1,31 @@
 use crate::types::OwnMintingReport;
 use anyhow::Error;
-use common::gateway::download_tx_data;
+use common::{gateway::download_tx_data, ids::MessageId};
+use std::io::Read;
 
 pub fn parse_own_minting_report(txid: &str) -> Result<OwnMintingReport, Error> {
-    let tx_data = download_tx_data(txid)?;
-    let mut res: OwnMintingReport = serde_json::from_slice(&tx_data)?;
-    res.report_id = Some(txid.to_string());
+    let report_id = MessageId::parse(txid)?;
+    let mut res = parse_own_minting_report_bytes(&download_tx_data(txid)?)?;
+    res.report_id = Some(report_id);
     Ok(res)
 }
 
+/// a report payload already at hand, `report_id` is left to the caller
+pub fn parse_own_minting_report_bytes(data: &[u8]) -> Result<OwnMintingReport, Error> {
+    Ok(serde_json::from_slice(data)?)
+}
+
+pub fn parse_own_minting_report_reader<R: Read>(reader: R) -> Result<OwnMintingReport, Error> {
+    Ok(serde_json::from_reader(reader)?)
+}
+
 #[cfg(test)]
 
 mod tests {
-    use crate::json_parser::parse_own_minting_report;
+    use crate::json_parser::{
+        parse_own_minting_report, parse_own_minting_report_bytes, parse_own_minting_report_reader,
+    };
+    use common::{amount::RawAmount, projects::Project};
 
     #[test]
     fn parse_own_minting_report_test() {
@@ -21,4 +34,19 @@ mod tests {
         println!("{:?}", report);
         assert_eq!(report.timestamp, 1764976437232);
     }
+
+    #[test]
+    fn own_minting_report_fixture_test() {
+        let data = include_bytes!("../fixtures/own_minting_report.json");
+        let report = parse_own_minting_report_bytes(data).unwrap();
+        assert_eq!(report.distribution_tick, 42);
+        assert_eq!(report.total_minted, RawAmount(1_250_000_000_000_000));
+        assert_eq!(report.timestamp, 1764976437232);
+        assert!(report.report_id.is_none());
+        let scaled = report.clone().in_project(&Project::pi());
+        assert_eq!(scaled.minted.to_string(), "1250");
+        let from_reader = parse_own_minting_report_reader(&data[..]).unwrap();
+        assert_eq!(from_reader.ao_kept, report.ao_kept);
+        assert!(parse_own_minting_report_bytes(b"{}").is_err());
+    }
 }
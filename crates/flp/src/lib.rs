@@ -1,4 +1,6 @@
 pub mod csv_parser;
 pub mod json_parser;
+pub mod simulator;
 pub mod types;
 pub mod wallet;
+pub mod yields;
@@ -0,0 +1,248 @@
+use crate::types::{DelegationsRes, MAX_FACTOR, WalletDelegations};
+use anyhow::{Error, anyhow};
+use common::{amount::Amount, projects::Project};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// a wallet's latest oracle balance of one ticker, with its AR balance
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WalletHolding {
+    pub ticker: String,
+    pub amount: Amount,
+    pub ar_amount: Amount,
+}
+
+/// delegated totals of an FLP in the latest cycle of a ticker
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectTotals {
+    pub project: String,
+    pub ticker: String,
+    pub amount: Amount,
+    pub ar_amount: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Simulation {
+    pub wallet: Option<String>,
+    pub total_factor_before: u32,
+    pub total_factor_after: u32,
+    /// every (FLP, ticker) the current or proposed preferences touch
+    pub impacts: Vec<ProjectImpact>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProjectImpact {
+    pub project: String,
+    pub ticker: String,
+    pub factor_before: u32,
+    pub factor_after: u32,
+    pub before: SimulatedTotals,
+    pub after: SimulatedTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SimulatedTotals {
+    /// FLP totals
+    pub amount: Amount,
+    pub ar_amount: Amount,
+    /// the wallet's part of them
+    pub wallet_amount: Amount,
+    pub wallet_ar_amount: Amount,
+    /// `wallet_amount` over `amount`, 0 to 1
+    pub share: f64,
+}
+
+/// checks a hypothetical preference set the way the delegation process
+/// would: factors add up to at most `MAX_FACTOR`, one entry per target
+pub fn validate_prefs(prefs: &[WalletDelegations]) -> Result<(), Error> {
+    let mut seen = HashSet::new();
+    for pref in prefs {
+        if !seen.insert(pref.wallet_to.as_str()) {
+            return Err(anyhow!("duplicate preference for {}", pref.wallet_to));
+        }
+    }
+    let total: u32 = prefs.iter().map(|pref| pref.factor).sum();
+    if total > MAX_FACTOR {
+        return Err(anyhow!(
+            "preference factors add up to {total}, over {MAX_FACTOR}"
+        ));
+    }
+    Ok(())
+}
+
+/// recomputes the wallet's delegated amounts per ticker under `proposed` and
+/// applies the difference to the latest FLP totals, which already hold the
+/// wallet's `current` delegation
+pub fn simulate(
+    current: &DelegationsRes,
+    proposed: &[WalletDelegations],
+    holdings: &[WalletHolding],
+    totals: &[ProjectTotals],
+) -> Result<Simulation, Error> {
+    validate_prefs(proposed)?;
+    let factors = |prefs: &[WalletDelegations]| -> BTreeMap<String, u32> {
+        let mut out = BTreeMap::new();
+        for pref in prefs {
+            if Project::is_flp_project(&pref.wallet_to) {
+                *out.entry(pref.wallet_to.clone()).or_insert(0) += pref.factor;
+            }
+        }
+        out
+    };
+    let before = factors(&current.delegation_prefs);
+    let after = factors(proposed);
+    let projects: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut impacts = Vec::new();
+    for project in projects {
+        let factor_before = before.get(project).copied().unwrap_or(0);
+        let factor_after = after.get(project).copied().unwrap_or(0);
+        for holding in holdings {
+            let total = totals
+                .iter()
+                .find(|t| &t.project == project && t.ticker == holding.ticker);
+            let (amount, ar_amount) = total.map_or(
+                (
+                    Amount::zero(holding.amount.decimals),
+                    Amount::zero(holding.ar_amount.decimals),
+                ),
+                |t| (t.amount, t.ar_amount),
+            );
+            let wallet_before = (
+                holding.amount.factored(factor_before, MAX_FACTOR),
+                holding.ar_amount.factored(factor_before, MAX_FACTOR),
+            );
+            let wallet_after = (
+                holding.amount.factored(factor_after, MAX_FACTOR),
+                holding.ar_amount.factored(factor_after, MAX_FACTOR),
+            );
+            let (amount, amount_after) = swapped(amount, wallet_before.0, wallet_after.0)?;
+            let (ar_amount, ar_amount_after) = swapped(ar_amount, wallet_before.1, wallet_after.1)?;
+            impacts.push(ProjectImpact {
+                project: project.clone(),
+                ticker: holding.ticker.clone(),
+                factor_before,
+                factor_after,
+                before: totals_with(amount, ar_amount, wallet_before),
+                after: totals_with(amount_after, ar_amount_after, wallet_after),
+            });
+        }
+    }
+    Ok(Simulation {
+        wallet: current.wallet.clone(),
+        total_factor_before: current.delegation_prefs.iter().map(|p| p.factor).sum(),
+        total_factor_after: proposed.iter().map(|p| p.factor).sum(),
+        impacts,
+    })
+}
+
+/// `total` and `total` with the wallet's part moved from `before` to `after`.
+/// a snapshot older than the current preferences may not hold the wallet's
+/// part yet, the total is at least `before`
+fn swapped(total: Amount, before: Amount, after: Amount) -> Result<(Amount, Amount), Error> {
+    let total = if total.raw < before.raw {
+        before
+    } else {
+        total
+    };
+    let moved = total
+        .checked_sub(&before)
+        .and_then(|rest| rest.checked_add(&after))
+        .ok_or_else(|| {
+            anyhow!(
+                "FLP total with {} decimals doesn't match the holding's {}",
+                total.decimals,
+                before.decimals
+            )
+        })?;
+    Ok((total, moved))
+}
+
+fn totals_with(amount: Amount, ar_amount: Amount, wallet: (Amount, Amount)) -> SimulatedTotals {
+    SimulatedTotals {
+        amount,
+        ar_amount,
+        wallet_amount: wallet.0,
+        wallet_ar_amount: wallet.1,
+        share: if amount.is_zero() {
+            0.0
+        } else {
+            wallet.0.raw as f64 / amount.raw as f64
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        simulator::{ProjectTotals, WalletHolding, simulate},
+        types::{DelegationsRes, WalletDelegations},
+    };
+    use common::{
+        amount::{AR_DECIMALS, Amount, LST_DECIMALS},
+        projects::{INTERNAL_PI_PID, LOAD_PID},
+    };
+
+    fn lst(human: &str) -> Amount {
+        Amount::parse_human(human, LST_DECIMALS).unwrap()
+    }
+
+    fn ar(human: &str) -> Amount {
+        Amount::parse_human(human, AR_DECIMALS).unwrap()
+    }
+
+    fn pref(wallet_to: &str, factor: u32) -> WalletDelegations {
+        WalletDelegations {
+            wallet_to: wallet_to.to_string(),
+            factor,
+        }
+    }
+
+    #[test]
+    fn move_factor_test() {
+        let current = DelegationsRes::pi_default("wallet");
+        // move 30% from PI to LOAD
+        let proposed = vec![pref(INTERNAL_PI_PID, 7000), pref(LOAD_PID, 3000)];
+        let holdings = vec![WalletHolding {
+            ticker: "usds".to_string(),
+            amount: lst("100.000000000000000001"),
+            ar_amount: ar("10"),
+        }];
+        let totals = vec![
+            ProjectTotals {
+                project: INTERNAL_PI_PID.to_string(),
+                ticker: "usds".to_string(),
+                amount: lst("1000.000000000000000001"),
+                ar_amount: ar("50"),
+            },
+            ProjectTotals {
+                project: LOAD_PID.to_string(),
+                ticker: "usds".to_string(),
+                amount: lst("270"),
+                ar_amount: ar("0"),
+            },
+        ];
+        let sim = simulate(&current, &proposed, &holdings, &totals).unwrap();
+        assert_eq!(
+            (sim.total_factor_before, sim.total_factor_after),
+            (10000, 10000)
+        );
+        let load = sim.impacts.iter().find(|i| i.project == LOAD_PID).unwrap();
+        assert_eq!((load.factor_before, load.factor_after), (0, 3000));
+        // 30% of the wallet's last wei rounds down
+        assert_eq!(load.after.amount, lst("300"));
+        assert_eq!(load.after.wallet_ar_amount, ar("3"));
+        assert_eq!(load.after.share, 0.1);
+        let pi = sim
+            .impacts
+            .iter()
+            .find(|i| i.project == INTERNAL_PI_PID)
+            .unwrap();
+        assert_eq!(pi.before.share, 0.1);
+        assert_eq!(pi.after.amount, lst("970"));
+
+        let over = vec![pref(LOAD_PID, 8000), pref(INTERNAL_PI_PID, 3000)];
+        assert!(simulate(&current, &over, &holdings, &totals).is_err());
+        let duplicate = vec![pref(LOAD_PID, 1000), pref(LOAD_PID, 1000)];
+        assert!(simulate(&current, &duplicate, &holdings, &totals).is_err());
+    }
+}
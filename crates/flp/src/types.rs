@@ -1,4 +1,9 @@
-use common::projects::INTERNAL_PI_PID;
+use anyhow::{Error, anyhow};
+use common::{
+    amount::{Amount, RawAmount, lst_decimals},
+    ids::{ArAddress, EvmAddress, MessageId},
+    projects::{INTERNAL_PI_PID, Project},
+};
 use serde::{Deserialize, Serialize};
 
 pub const MAX_FACTOR: u32 = 10000;
@@ -13,7 +18,8 @@ pub struct WalletDelegations {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DelegationMappingsRow {
-    pub wallet_from: String,
+    pub wallet_from: ArAddress,
+    /// a FLP process id or another wallet
     pub wallet_to: String,
     pub factor: u32,
 }
@@ -32,21 +38,53 @@ pub struct DelegationsRes {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SetBalancesData {
-    pub eoa: String,
-    pub amount: String,
-    pub ar_address: String,
+    pub eoa: EvmAddress,
+    /// raw LST amount as published by the oracle, see `lst_amount`
+    pub amount: RawAmount,
+    pub ar_address: ArAddress,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct OwnMintingReport {
     pub distribution_tick: u32,
-    pub total_minted: String,
-    pub total_inflow: String,
+    /// raw project tokens, see `Project::amount`
+    pub total_minted: RawAmount,
+    pub total_inflow: RawAmount,
     pub timestamp: u64,
     pub ao_kept: String,
     pub ao_exchanged_for_pi: String,
-    pub report_id: Option<String>,
+    pub report_id: Option<MessageId>,
+}
+
+/// a minting report with its totals in the project token
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProjectMintingReport {
+    #[serde(flatten)]
+    pub report: OwnMintingReport,
+    pub minted: Amount,
+    pub inflow: Amount,
+}
+
+impl OwnMintingReport {
+    /// `project` is the FLP that published the report
+    pub fn in_project(self, project: &Project) -> ProjectMintingReport {
+        ProjectMintingReport {
+            minted: project.amount(self.total_minted),
+            inflow: project.amount(self.total_inflow),
+            report: self,
+        }
+    }
+}
+
+impl SetBalancesData {
+    /// `amount` scaled by the decimals of the oracle `ticker`'s LST
+    pub fn lst_amount(&self, ticker: &str) -> Result<Amount, Error> {
+        let decimals =
+            lst_decimals(ticker).ok_or_else(|| anyhow!("unknown oracle ticker {ticker}"))?;
+        Ok(self.amount.with_decimals(decimals))
+    }
 }
 
 impl DelegationsRes {
@@ -1,8 +1,21 @@
 use crate::types::{DelegationsRes, MAX_FACTOR};
-use anyhow::{Error, anyhow};
+use anyhow::Error;
 use common::gateway::download_tx_data;
 use common::gql::{get_user_delegation_txid, get_user_last_delegation_txid};
 use common::projects::INTERNAL_PI_PID;
+use std::fmt;
+
+/// the wallet never sent a delegation message
+#[derive(Debug)]
+pub struct NoDelegationPreferences(pub String);
+
+impl fmt::Display for NoDelegationPreferences {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no delegation preferences found for {}", self.0)
+    }
+}
+
+impl std::error::Error for NoDelegationPreferences {}
 
 /// retrieves wallet delegation preferences by making two queries:
 /// 1- gets the last delegation message ID (msg sent from user addr to DELEGATION_PID)
@@ -28,7 +41,7 @@ pub fn get_wallet_delegations(address: &str) -> Result<DelegationsRes, Error> {
         res.delegation_msg_id = Some(last_delegation_txid);
         fallback = Some(res);
     }
-    fallback.ok_or_else(|| anyhow!("error: no delegation preferences found"))
+    fallback.ok_or_else(|| NoDelegationPreferences(address.to_string()).into())
 }
 
 #[cfg(test)]
@@ -0,0 +1,302 @@
+use crate::types::{MAX_FACTOR, OwnMintingReport, SetBalancesData, WalletDelegations};
+use anyhow::{Error, anyhow};
+use common::{
+    amount::{Amount, lst_decimals},
+    projects::{INTERNAL_PI_PID, Project},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// a wallet's and the whole FLP's delegated amount of one oracle's LST at a
+/// distribution tick
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TickerStake {
+    pub ticker: String,
+    pub wallet_amount: Amount,
+    pub total_amount: Amount,
+}
+
+/// what a wallet earned from one distribution tick
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct YieldTick {
+    pub distribution_tick: u32,
+    pub timestamp: u64,
+    /// project tokens minted by the FLP in the tick
+    pub total_minted: Amount,
+    pub total_inflow: Amount,
+    /// weighted stake of the wallet and of every delegator, approximate
+    /// since it sums the LSTs of several oracles
+    pub stake: f64,
+    pub total_stake: f64,
+    /// `stake` over `total_stake`, 0 to 1
+    pub share: f64,
+    pub minted: Amount,
+    pub inflow: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WalletYield {
+    pub wallet: String,
+    pub project: String,
+    /// oldest tick first
+    pub ticks: Vec<YieldTick>,
+    pub total_minted: Amount,
+    pub total_inflow: Amount,
+}
+
+/// amount of `balance`, a Set-Balances row of `ticker`, delegated to
+/// `project`. a wallet without preferences delegates everything to PI, like
+/// `DelegationsRes::pi_default`
+pub fn delegated_amount(
+    balance: &SetBalancesData,
+    ticker: &str,
+    prefs: Option<&[WalletDelegations]>,
+    project: &str,
+) -> Result<Amount, Error> {
+    let factor: u32 = match prefs {
+        Some(prefs) => prefs
+            .iter()
+            .filter(|pref| pref.wallet_to == project)
+            .map(|pref| pref.factor)
+            .sum(),
+        None if project == INTERNAL_PI_PID => MAX_FACTOR,
+        None => 0,
+    };
+    Ok(balance.lst_amount(ticker)?.factored(factor, MAX_FACTOR))
+}
+
+/// `wallet`'s and every delegator's stake in `project` from a Set-Balances
+/// report of `ticker`, `delegations` keyed by Arweave address
+pub fn ticker_stake(
+    project: &str,
+    ticker: &str,
+    wallet: &str,
+    balances: &[SetBalancesData],
+    delegations: &HashMap<String, Vec<WalletDelegations>>,
+) -> Result<TickerStake, Error> {
+    let decimals = lst_decimals(ticker).ok_or_else(|| anyhow!("unknown oracle ticker {ticker}"))?;
+    let mut stake = TickerStake {
+        ticker: ticker.to_string(),
+        wallet_amount: Amount::zero(decimals),
+        total_amount: Amount::zero(decimals),
+    };
+    let overflow = || anyhow!("{ticker} stake in {project} overflows");
+    for balance in balances {
+        let prefs = delegations
+            .get(balance.ar_address.as_str())
+            .map(Vec::as_slice);
+        let amount = delegated_amount(balance, ticker, prefs, project)?;
+        stake.total_amount = stake
+            .total_amount
+            .checked_add(&amount)
+            .ok_or_else(overflow)?;
+        if balance.ar_address == wallet {
+            stake.wallet_amount = stake
+                .wallet_amount
+                .checked_add(&amount)
+                .ok_or_else(overflow)?;
+        }
+    }
+    Ok(stake)
+}
+
+/// attributes the tokens an FLP minted in a tick to its delegators pro-rata
+/// to their stake. LST amounts of different oracles are summed after
+/// multiplying by the ticker's weight, 1 unless set
+#[derive(Debug, Clone, Default)]
+pub struct YieldCalculator {
+    weights: HashMap<String, f64>,
+}
+
+impl YieldCalculator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// value of one unit of `ticker`'s LST, e.g. its price
+    pub fn with_weight(mut self, ticker: &str, weight: f64) -> Self {
+        self.weights.insert(ticker.to_ascii_lowercase(), weight);
+        self
+    }
+
+    fn weight(&self, ticker: &str) -> f64 {
+        self.weights.get(ticker).copied().unwrap_or(1.0)
+    }
+
+    /// `report` is one of `project`'s, its amounts in the project token
+    pub fn tick(
+        &self,
+        project: &Project,
+        report: &OwnMintingReport,
+        stakes: &[TickerStake],
+    ) -> YieldTick {
+        let total_minted = project.amount(report.total_minted);
+        let total_inflow = project.amount(report.total_inflow);
+        let stake: f64 = stakes
+            .iter()
+            .map(|s| s.wallet_amount.to_f64() * self.weight(&s.ticker))
+            .sum();
+        let total_stake: f64 = stakes
+            .iter()
+            .map(|s| s.total_amount.to_f64() * self.weight(&s.ticker))
+            .sum();
+        let share = if total_stake > 0.0 {
+            stake / total_stake
+        } else {
+            0.0
+        };
+        YieldTick {
+            distribution_tick: report.distribution_tick,
+            timestamp: report.timestamp,
+            total_minted,
+            total_inflow,
+            stake,
+            total_stake,
+            share,
+            minted: total_minted.portion(share),
+            inflow: total_inflow.portion(share),
+        }
+    }
+
+    /// per-tick history of `wallet`, `ticks` pairs every report with the
+    /// stakes in force when it was minted
+    pub fn history(
+        &self,
+        wallet: &str,
+        project: &Project,
+        ticks: &[(OwnMintingReport, Vec<TickerStake>)],
+    ) -> Result<WalletYield, Error> {
+        let mut out: Vec<_> = ticks
+            .iter()
+            .map(|(report, stakes)| self.tick(project, report, stakes))
+            .collect();
+        out.sort_by_key(|tick| (tick.timestamp, tick.distribution_tick));
+        let sum = |amount: fn(&YieldTick) -> Amount| {
+            out.iter()
+                .try_fold(Amount::zero(project.decimals()), |total, tick| {
+                    total.checked_add(&amount(tick))
+                })
+                .ok_or_else(|| anyhow!("yield of {wallet} overflows"))
+        };
+        Ok(WalletYield {
+            wallet: wallet.to_string(),
+            project: project.pid.clone(),
+            total_minted: sum(|tick| tick.minted)?,
+            total_inflow: sum(|tick| tick.inflow)?,
+            ticks: out,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        types::{OwnMintingReport, SetBalancesData, WalletDelegations},
+        yields::{TickerStake, YieldCalculator, ticker_stake},
+    };
+    use common::{
+        amount::{Amount, LST_DECIMALS, RawAmount},
+        projects::{INTERNAL_PI_PID, Project},
+    };
+    use std::collections::HashMap;
+
+    /// a valid address made of `c`, a hex letter
+    fn wallet(c: char) -> String {
+        c.to_string().repeat(43)
+    }
+
+    fn balance(c: char, raw: u128) -> SetBalancesData {
+        SetBalancesData {
+            eoa: format!("0x{}", c.to_string().repeat(40)).parse().unwrap(),
+            amount: RawAmount(raw),
+            ar_address: wallet(c).parse().unwrap(),
+        }
+    }
+
+    fn stake(ticker: &str, wallet_amount: &str, total_amount: &str) -> TickerStake {
+        TickerStake {
+            ticker: ticker.to_string(),
+            wallet_amount: Amount::parse_human(wallet_amount, LST_DECIMALS).unwrap(),
+            total_amount: Amount::parse_human(total_amount, LST_DECIMALS).unwrap(),
+        }
+    }
+
+    fn report(tick: u32, minted: u128) -> OwnMintingReport {
+        OwnMintingReport {
+            distribution_tick: tick,
+            total_minted: RawAmount(minted),
+            total_inflow: RawAmount(10),
+            timestamp: 1_764_976_437_232 + tick as u64,
+            ao_kept: "0".to_string(),
+            ao_exchanged_for_pi: "0".to_string(),
+            report_id: None,
+        }
+    }
+
+    #[test]
+    fn pro_rata_yield_test() {
+        let usds = stake("usds", "50", "350");
+        let steth = stake("steth", "0", "1");
+        let calculator = YieldCalculator::new().with_weight("steth", 50.0);
+        // 6 decimals
+        let project = Project::ario();
+        let history = calculator
+            .history(
+                "wallet",
+                &project,
+                &[
+                    (report(2, 800_000_000), vec![usds.clone(), steth]),
+                    (report(1, 700_000_000), vec![usds]),
+                ],
+            )
+            .unwrap();
+        assert_eq!(history.ticks[0].distribution_tick, 1);
+        assert_eq!(history.ticks[0].minted.to_string(), "100");
+        assert_eq!(history.ticks[0].total_minted.to_string(), "700");
+        // the weighted steth stake grows the total to 400
+        assert_eq!(history.ticks[1].share, 0.125);
+        assert_eq!(history.total_minted.to_string(), "200");
+        assert_eq!(history.project, project.pid);
+    }
+
+    #[test]
+    fn factored_ticker_stake_test() {
+        let balances = vec![
+            balance('a', 123_456_789_012_345_678_901),
+            balance('b', 7),
+            balance('c', 50),
+        ];
+        let delegations = HashMap::from([
+            (
+                wallet('a'),
+                vec![WalletDelegations {
+                    wallet_to: "flp".to_string(),
+                    factor: 3333,
+                }],
+            ),
+            (
+                wallet('b'),
+                vec![WalletDelegations {
+                    wallet_to: "flp".to_string(),
+                    factor: 3333,
+                }],
+            ),
+        ]);
+        let stake = ticker_stake("flp", "usds", &wallet('a'), &balances, &delegations).unwrap();
+        // rounded down to a wei per wallet
+        assert_eq!(stake.wallet_amount.raw, 41_148_147_777_814_814_777);
+        assert_eq!(stake.total_amount.raw, 41_148_147_777_814_814_779);
+        assert_eq!(stake.total_amount.decimals, LST_DECIMALS);
+        // no preferences, c's balance goes to PI
+        let pi = ticker_stake(
+            INTERNAL_PI_PID,
+            "usds",
+            &wallet('c'),
+            &balances,
+            &delegations,
+        )
+        .unwrap();
+        assert_eq!((pi.wallet_amount.raw, pi.total_amount.raw), (50, 50));
+        assert!(ticker_stake("flp", "btc", &wallet('a'), &balances, &delegations).is_err());
+    }
+}
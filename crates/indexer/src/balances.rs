@@ -0,0 +1,141 @@
+use common::{
+    amount::{AR_DECIMALS, Amount},
+    gateway::get_ar_balance_winston,
+    ids::ArAddress,
+};
+use futures::{StreamExt, stream};
+use std::{collections::HashMap, sync::Mutex};
+use tracing::{info, warn};
+
+use crate::indexer::spawn_blocking_in_span;
+
+/// winston balances looked up at one Arweave block height. the ticker
+/// cycles of a run share the stakers, so each wallet's balance is fetched
+/// once per block instead of once per ticker
+#[derive(Default)]
+pub struct ArBalanceCache {
+    inner: Mutex<CachedBalances>,
+}
+
+#[derive(Default)]
+struct CachedBalances {
+    height: Option<u64>,
+    winston: HashMap<ArAddress, u128>,
+}
+
+impl CachedBalances {
+    /// `addresses` not cached at `height`, dropping the cache of an older block
+    fn missing(&mut self, height: Option<u64>, addresses: &[ArAddress]) -> Vec<ArAddress> {
+        if height.is_none() || self.height != height {
+            self.height = height;
+            self.winston.clear();
+        }
+        let mut missing: Vec<ArAddress> = addresses
+            .iter()
+            .filter(|address| !self.winston.contains_key(*address))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    fn get(&self, addresses: &[ArAddress]) -> HashMap<ArAddress, Amount> {
+        addresses
+            .iter()
+            .map(|address| {
+                let winston = self.winston.get(address).copied().unwrap_or(0);
+                (address.clone(), Amount::new(winston, AR_DECIMALS))
+            })
+            .collect()
+    }
+}
+
+impl ArBalanceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// AR balances of `addresses` at `height`, the uncached ones fetched
+    /// from the gateway with `concurrency` requests in flight. without a
+    /// height nothing is cached, failed lookups count as 0 and are retried
+    /// on the next call
+    pub async fn balances(
+        &self,
+        height: Option<u64>,
+        addresses: &[ArAddress],
+        concurrency: usize,
+    ) -> HashMap<ArAddress, Amount> {
+        let missing = self.lock().missing(height, addresses);
+        let cached = addresses.len() - missing.len();
+        let fetched: Vec<(ArAddress, Option<u128>)> =
+            stream::iter(missing.into_iter().map(|address| async move {
+                let lookup = address.to_string();
+                match spawn_blocking_in_span(move || get_ar_balance_winston(&lookup)).await {
+                    Ok(winston) => (address, Some(winston)),
+                    Err(err) => {
+                        warn!(wallet = %address, error = %err, "AR balance lookup failed");
+                        (address, None)
+                    }
+                }
+            }))
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+        info!(
+            ?height,
+            cached,
+            fetched = fetched.len(),
+            "loaded AR balances"
+        );
+        let mut inner = self.lock();
+        // another ticker may have moved the cache to a newer block meanwhile
+        if inner.height == height && height.is_some() {
+            for (address, winston) in &fetched {
+                if let Some(winston) = winston {
+                    inner.winston.insert(address.clone(), *winston);
+                }
+            }
+            return inner.get(addresses);
+        }
+        let mut balances = inner.get(addresses);
+        for (address, winston) in fetched {
+            balances.insert(address, Amount::new(winston.unwrap_or(0), AR_DECIMALS));
+        }
+        balances
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CachedBalances> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::balances::CachedBalances;
+    use common::ids::ArAddress;
+
+    fn address(c: char) -> ArAddress {
+        c.to_string().repeat(43).parse().unwrap()
+    }
+
+    #[test]
+    fn ar_balance_cache_test() {
+        let mut cache = CachedBalances::default();
+        let (a, b) = (address('a'), address('b'));
+        let wallets = vec![a.clone(), b.clone(), a.clone()];
+        assert_eq!(
+            cache.missing(Some(10), &wallets),
+            vec![a.clone(), b.clone()]
+        );
+        cache.winston.insert(a.clone(), 5_000_000_000_000);
+        assert_eq!(cache.missing(Some(10), &wallets), vec![b.clone()]);
+        let balances = cache.get(&wallets);
+        assert_eq!(balances[&a].to_string(), "5");
+        assert!(balances[&b].is_zero());
+        // a new block invalidates every balance
+        assert_eq!(cache.missing(Some(11), &wallets).len(), 2);
+        assert!(cache.winston.is_empty());
+        assert_eq!(cache.missing(None, &[a]).len(), 1);
+    }
+}
@@ -0,0 +1,451 @@
+use anyhow::{Result, anyhow};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use common::{
+    export::{ExportDataset as Dataset, ExportFilter, ExportFormat},
+    mainnet::DataProtocol,
+};
+use indexer::{
+    Indexer,
+    backfill::{backfill_delegations, backfill_heights},
+    clickhouse::Clickhouse,
+    config::Config,
+    indexer::{TokenConfig, fetch_network_height},
+    repair::{RepairTarget, find_gaps, last_complete_height, repair_heights, resolve_range},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+/// admin CLI for the atlas indexer, reads the same env + atlas.toml as the indexer
+#[derive(Parser)]
+#[command(name = "atlas", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// re-indexes a height range (or delegation mapping txs) from the gateway
+    Backfill {
+        indexer: BackfillIndexer,
+        #[arg(long)]
+        from: Option<u32>,
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// finds missing or partially indexed heights and re-indexes them
+    ReindexGap {
+        /// defaults to the indexers enabled in atlas.toml
+        #[arg(value_delimiter = ',')]
+        targets: Vec<TargetArg>,
+        #[arg(long)]
+        from: Option<u32>,
+        #[arg(long)]
+        to: Option<u32>,
+        /// max heights to repair per target
+        #[arg(long)]
+        limit: Option<u64>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// truncates and rebuilds the ao mainnet explorer table
+    RebuildExplorer,
+    /// reports gaps without repairing them, exits non-zero if any are found
+    Verify {
+        #[arg(value_delimiter = ',')]
+        targets: Vec<TargetArg>,
+        #[arg(long)]
+        from: Option<u32>,
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// prints indexed heights and lag against the network tip
+    Status,
+    /// applies pending clickhouse schema migrations
+    Migrate {
+        /// list migrations and when they were applied, without applying
+        #[arg(long)]
+        status: bool,
+    },
+    /// streams an indexed dataset as CSV, NDJSON or Parquet
+    Export {
+        dataset: ExportDataset,
+        /// first block height (messages, transfers, delegation mappings)
+        #[arg(long)]
+        from: Option<u32>,
+        /// last block height
+        #[arg(long)]
+        to: Option<u32>,
+        /// first day (explorer days)
+        #[arg(long)]
+        from_day: Option<NaiveDate>,
+        /// last day (explorer days)
+        #[arg(long)]
+        to_day: Option<NaiveDate>,
+        /// FLP process id, required by `delegators`
+        #[arg(long)]
+        project: Option<String>,
+        #[arg(long)]
+        ticker: Option<String>,
+        /// delegating wallet (`delegation-mappings`)
+        #[arg(long)]
+        wallet: Option<String>,
+        /// token message `Action` tag
+        #[arg(long)]
+        action: Option<String>,
+        #[arg(long, value_enum, default_value_t = FormatArg::Ndjson)]
+        format: FormatArg,
+        /// output file, stdout when omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TargetArg {
+    Mainnet,
+    MainnetA,
+    MainnetB,
+    Ao,
+    Pi,
+    All,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BackfillIndexer {
+    MainnetA,
+    MainnetB,
+    Ao,
+    Pi,
+    Delegations,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportDataset {
+    MainnetA,
+    MainnetB,
+    Ao,
+    Pi,
+    Delegators,
+    DelegationMappings,
+    ExplorerDays,
+    MainnetExplorerDays,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+impl From<FormatArg> for ExportFormat {
+    fn from(arg: FormatArg) -> Self {
+        match arg {
+            FormatArg::Csv => ExportFormat::Csv,
+            FormatArg::Ndjson => ExportFormat::Ndjson,
+            FormatArg::Parquet => ExportFormat::Parquet,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    common::telemetry::init_tracing();
+    let cli = Cli::parse();
+    let config = Config::load();
+    let clickhouse = Clickhouse::new(&config);
+    match cli.command {
+        Command::Backfill { indexer, from, to } => backfill(&clickhouse, indexer, from, to).await,
+        Command::ReindexGap {
+            targets,
+            from,
+            to,
+            limit,
+            dry_run,
+        } => {
+            let targets = resolve_targets(&targets, &config);
+            reindex_gap(&clickhouse, &targets, from, to, limit, dry_run).await
+        }
+        Command::RebuildExplorer => {
+            Indexer::new(config, clickhouse)
+                .rebuild_mainnet_explorer()
+                .await
+        }
+        Command::Verify { targets, from, to } => {
+            let targets = resolve_targets(&targets, &config);
+            verify(&clickhouse, &targets, from, to).await
+        }
+        Command::Status => status(&clickhouse).await,
+        Command::Migrate { status } => migrate(&clickhouse, status).await,
+        Command::Export {
+            dataset,
+            from,
+            to,
+            from_day,
+            to_day,
+            project,
+            ticker,
+            wallet,
+            action,
+            format,
+            out,
+        } => {
+            let filter = ExportFilter {
+                ticker,
+                wallet,
+                action,
+                block_min: from,
+                block_max: to,
+                from_day: from_day.map(|day| day.to_string()),
+                to_day: to_day.map(|day| day.to_string()),
+                ..Default::default()
+            };
+            export(&clickhouse, dataset, project, filter, format.into(), out).await
+        }
+    }
+}
+
+fn resolve_targets(args: &[TargetArg], config: &Config) -> Vec<RepairTarget> {
+    if args.is_empty() {
+        return RepairTarget::enabled(&config.indexers);
+    }
+    let mut targets = Vec::new();
+    for arg in args {
+        match arg {
+            TargetArg::Mainnet => {
+                targets.push(RepairTarget::Mainnet(DataProtocol::A));
+                targets.push(RepairTarget::Mainnet(DataProtocol::B));
+            }
+            TargetArg::MainnetA => targets.push(RepairTarget::Mainnet(DataProtocol::A)),
+            TargetArg::MainnetB => targets.push(RepairTarget::Mainnet(DataProtocol::B)),
+            TargetArg::Ao => targets.extend(TokenConfig::from_label("ao").map(RepairTarget::Token)),
+            TargetArg::Pi => targets.extend(TokenConfig::from_label("pi").map(RepairTarget::Token)),
+            TargetArg::All => return all_targets(),
+        }
+    }
+    targets
+}
+
+fn all_targets() -> Vec<RepairTarget> {
+    let mut targets = vec![
+        RepairTarget::Mainnet(DataProtocol::A),
+        RepairTarget::Mainnet(DataProtocol::B),
+    ];
+    targets.extend(TokenConfig::from_label("ao").map(RepairTarget::Token));
+    targets.extend(TokenConfig::from_label("pi").map(RepairTarget::Token));
+    targets
+}
+
+fn token_target(label: &str) -> Result<RepairTarget> {
+    TokenConfig::from_label(label)
+        .map(RepairTarget::Token)
+        .ok_or_else(|| anyhow!("unknown token {label}"))
+}
+
+async fn backfill(
+    clickhouse: &Clickhouse,
+    indexer: BackfillIndexer,
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Result<()> {
+    let target = match indexer {
+        BackfillIndexer::Delegations => {
+            let stored =
+                backfill_delegations(clickhouse, from.unwrap_or(0), to.unwrap_or(u32::MAX)).await?;
+            println!("delegations: stored {stored} mapping txs");
+            return Ok(());
+        }
+        BackfillIndexer::MainnetA => RepairTarget::Mainnet(DataProtocol::A),
+        BackfillIndexer::MainnetB => RepairTarget::Mainnet(DataProtocol::B),
+        BackfillIndexer::Ao => token_target("ao")?,
+        BackfillIndexer::Pi => token_target("pi")?,
+    };
+    let from = from.unwrap_or(target.start_height());
+    // default to the forward worker's progress so both don't fetch the same heights
+    let to = match to {
+        Some(to) => to,
+        None => last_complete_height(clickhouse, target)
+            .await?
+            .ok_or_else(|| anyhow!("{} has no indexed heights, pass --to", target.label()))?,
+    };
+    let summary = backfill_heights(clickhouse, target, from, to).await?;
+    println!(
+        "{}: backfilled {} heights, {} msgs",
+        target.label(),
+        summary.heights,
+        summary.msgs
+    );
+    Ok(())
+}
+
+async fn reindex_gap(
+    clickhouse: &Clickhouse,
+    targets: &[RepairTarget],
+    from: Option<u32>,
+    to: Option<u32>,
+    limit: Option<u64>,
+    dry_run: bool,
+) -> Result<()> {
+    let limit = limit.unwrap_or(u64::MAX);
+    for &target in targets {
+        let Some((from, to)) = resolve_range(clickhouse, target, from, to).await? else {
+            println!("{}: nothing indexed in range", target.label());
+            continue;
+        };
+        let report = find_gaps(clickhouse, target, from, to, limit).await?;
+        println!(
+            "{}: heights {from}..={to} missing {} partial {}",
+            target.label(),
+            report.missing.len(),
+            report.partial.len()
+        );
+        if dry_run || report.is_empty() {
+            continue;
+        }
+        let heights = report.heights();
+        let heights = &heights[..heights.len().min(limit as usize)];
+        let summary = repair_heights(clickhouse, target, heights).await?;
+        println!(
+            "{}: repaired {} heights, {} msgs",
+            target.label(),
+            summary.heights,
+            summary.msgs
+        );
+    }
+    Ok(())
+}
+
+async fn verify(
+    clickhouse: &Clickhouse,
+    targets: &[RepairTarget],
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Result<()> {
+    let mut gaps = 0usize;
+    for &target in targets {
+        let Some((from, to)) = resolve_range(clickhouse, target, from, to).await? else {
+            println!("{}: nothing indexed in range", target.label());
+            continue;
+        };
+        let report = find_gaps(clickhouse, target, from, to, u64::MAX).await?;
+        println!(
+            "{}: heights {from}..={to} missing {} partial {}",
+            target.label(),
+            report.missing.len(),
+            report.partial.len()
+        );
+        for (first, last) in report.ranges() {
+            println!("{}: gap {first}..={last}", target.label());
+        }
+        gaps += report.heights().len();
+    }
+    if gaps > 0 {
+        return Err(anyhow!(
+            "found {gaps} heights to repair, run `atlas reindex-gap`"
+        ));
+    }
+    println!("no gaps found");
+    Ok(())
+}
+
+async fn status(clickhouse: &Clickhouse) -> Result<()> {
+    let tip = fetch_network_height().await.ok();
+    match tip {
+        Some(tip) => println!("network tip: {tip}"),
+        None => println!("network tip: unavailable"),
+    }
+    for target in all_targets() {
+        let height = last_complete_height(clickhouse, target).await?;
+        match (height, tip) {
+            (Some(height), Some(tip)) => println!(
+                "{}: {height} (lag {})",
+                target.label(),
+                tip.saturating_sub(height as u64)
+            ),
+            (Some(height), None) => println!("{}: {height}", target.label()),
+            (None, _) => println!("{}: not indexed", target.label()),
+        }
+    }
+    match clickhouse.latest_mainnet_explorer_row().await? {
+        Some(row) => println!("mainnet explorer: {}", row.height),
+        None => println!("mainnet explorer: empty"),
+    }
+    match clickhouse.latest_explorer_stats().await? {
+        Some(stats) => println!("atlas explorer: {}", stats.height),
+        None => println!("atlas explorer: empty"),
+    }
+    match clickhouse.latest_delegation_mapping_height().await? {
+        Some(height) => println!("delegation mappings: {height}"),
+        None => println!("delegation mappings: empty"),
+    }
+    Ok(())
+}
+
+async fn migrate(clickhouse: &Clickhouse, status: bool) -> Result<()> {
+    if status {
+        for migration in clickhouse.migration_status().await? {
+            match migration.applied_at {
+                Some(at) => println!("{} applied {at}", migration.name),
+                None => println!("{} pending", migration.name),
+            }
+        }
+        return Ok(());
+    }
+    let applied = clickhouse.migrate().await?;
+    if applied.is_empty() {
+        println!("schema up to date");
+    } else {
+        println!("applied migrations {applied:?}");
+    }
+    Ok(())
+}
+
+async fn export(
+    clickhouse: &Clickhouse,
+    dataset: ExportDataset,
+    project: Option<String>,
+    mut filter: ExportFilter,
+    format: ExportFormat,
+    out: Option<PathBuf>,
+) -> Result<()> {
+    let dataset = match dataset {
+        ExportDataset::MainnetA => {
+            filter.protocol = Some("A".to_string());
+            Dataset::MainnetMessages
+        }
+        ExportDataset::MainnetB => {
+            filter.protocol = Some("B".to_string());
+            Dataset::MainnetMessages
+        }
+        ExportDataset::Ao => Dataset::TokenTransfers {
+            token: "ao".to_string(),
+        },
+        ExportDataset::Pi => Dataset::TokenTransfers {
+            token: "pi".to_string(),
+        },
+        ExportDataset::Delegators => Dataset::Delegators {
+            project: project.ok_or_else(|| anyhow!("delegators export needs --project"))?,
+        },
+        ExportDataset::DelegationMappings => Dataset::DelegationMappings,
+        ExportDataset::ExplorerDays => Dataset::ExplorerDays { mainnet: false },
+        ExportDataset::MainnetExplorerDays => Dataset::ExplorerDays { mainnet: true },
+    };
+    let mut writer: Box<dyn Write> = match out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    let mut cursor = clickhouse.export_bytes(&dataset.query(&filter), format)?;
+    let mut bytes = 0usize;
+    while let Some(chunk) = cursor.next().await? {
+        writer.write_all(&chunk)?;
+        bytes += chunk.len();
+    }
+    writer.flush()?;
+    eprintln!("exported {} ({bytes} bytes)", dataset.name());
+    Ok(())
+}
@@ -1,7 +1,13 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use clickhouse::{Client, Row};
+use clickhouse::{Client, Row, query::BytesCursor};
+use common::{
+    amount::{Amount, RawAmount, serialize_human},
+    export::{ExportBind, ExportFormat, ExportQuery},
+    ids::{ArAddress, EvmAddress, MessageId},
+};
 use explorer::BlockStats;
+use migrations::{MigrationStatus, Migrator};
 use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
@@ -27,48 +33,27 @@ impl Clickhouse {
         }
     }
 
-    pub async fn ensure(&self) -> Result<()> {
-        let create_db = format!("create database if not exists {}", self.database);
-        self.admin.query(&create_db).execute().await?;
-        let stmts = [
-            "create table if not exists oracle_snapshots(ts DateTime64(3), ticker String, tx_id String) engine=MergeTree order by (ticker, ts)",
-            "create table if not exists wallet_balances(ts DateTime64(3), ticker String, wallet String, eoa String, amount String, tx_id String) engine=ReplacingMergeTree order by (ticker, wallet, ts)",
-            "create table if not exists wallet_delegations(ts DateTime64(3), wallet String, payload String) engine=ReplacingMergeTree order by (wallet, ts)",
-            "create table if not exists flp_positions(ts DateTime64(3), ticker String, wallet String, eoa String, project String, factor UInt32, amount String) engine=ReplacingMergeTree order by (project, wallet, ts)",
-            "create table if not exists delegation_mappings(ts DateTime64(3), height UInt32, tx_id String, wallet_from String, wallet_to String, factor UInt32) engine=ReplacingMergeTree order by (height, tx_id, wallet_from, wallet_to)",
-            "create table if not exists atlas_explorer(ts DateTime64(3), height UInt64, tx_count UInt64, eval_count UInt64, transfer_count UInt64, new_process_count UInt64, new_module_count UInt64, active_users UInt64, active_processes UInt64, tx_count_rolling UInt64, processes_rolling UInt64, modules_rolling UInt64) engine=ReplacingMergeTree order by height",
-            "create table if not exists ao_mainnet_explorer(ts DateTime64(3), height UInt64, tx_count UInt64, eval_count UInt64, transfer_count UInt64, new_process_count UInt64, new_module_count UInt64, active_users UInt64, active_processes UInt64, tx_count_rolling UInt64, processes_rolling UInt64, modules_rolling UInt64) engine=ReplacingMergeTree order by height",
-            "create table if not exists ao_mainnet_messages(ts DateTime64(3), protocol String, block_height UInt32, block_timestamp UInt64, msg_id String, owner String, recipient String, bundled_in String, data_size String) engine=ReplacingMergeTree order by (protocol, block_height, msg_id)",
-            "create table if not exists ao_mainnet_message_tags(ts DateTime64(3), protocol String, block_height UInt32, msg_id String, tag_key String, tag_value String) engine=ReplacingMergeTree order by (tag_key, tag_value, block_height, msg_id)",
-            "create table if not exists ao_mainnet_block_state(protocol String, last_complete_height UInt32, last_cursor String, updated_at DateTime64(3)) engine=ReplacingMergeTree order by protocol",
-            "create table if not exists ao_token_messages(ts DateTime64(3), token String, source String, block_height UInt32, block_timestamp UInt64, msg_id String, owner String, recipient String, bundled_in String, data_size String) engine=ReplacingMergeTree order by (token, source, block_height, msg_id)",
-            "create table if not exists ao_token_message_tags(ts DateTime64(3), token String, source String, block_height UInt32, msg_id String, tag_key String, tag_value String) engine=ReplacingMergeTree order by (token, source, tag_key, tag_value, block_height, msg_id)",
-            "create table if not exists ao_token_block_state(token String, last_complete_height UInt32, updated_at DateTime64(3)) engine=ReplacingMergeTree order by (token, updated_at)",
-        ];
-        for stmt in stmts {
-            self.client.query(stmt).execute().await?;
-        }
-        let alters = [
-            "alter table wallet_balances add column if not exists eoa String after wallet",
-            "alter table wallet_balances add column if not exists ar_balance String after amount",
-            "alter table flp_positions add column if not exists eoa String after wallet",
-            "alter table flp_positions add column if not exists ar_amount String after amount",
-            "alter table flp_positions modify column project String",
-            "alter table delegation_mappings add column if not exists ts DateTime64(3) default now()",
-            "alter table ao_token_messages add column if not exists token String default 'ao'",
-            "alter table ao_token_message_tags add column if not exists token String default 'ao'",
-            "alter table ao_token_block_state add column if not exists token String default 'ao'",
-        ];
-        for stmt in alters {
-            self.client.query(stmt).execute().await?;
-        }
-        Ok(())
+    /// applies pending schema migrations, see the `migrations` crate
+    pub async fn migrate(&self) -> Result<Vec<u32>> {
+        self.migrator().run().await
+    }
+
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        self.migrator().status().await
+    }
+
+    fn migrator(&self) -> Migrator {
+        Migrator::new(self.admin.clone(), &self.database)
     }
 
     pub async fn insert_oracles(&self, rows: &[OracleSnapshotRow]) -> Result<()> {
         self.insert_rows("oracle_snapshots", rows).await
     }
 
+    pub async fn insert_oracle_payloads(&self, rows: &[OraclePayloadRow]) -> Result<()> {
+        self.insert_rows("oracle_payloads", rows).await
+    }
+
     pub async fn insert_balances(&self, rows: &[WalletBalanceRow]) -> Result<()> {
         self.insert_rows("wallet_balances", rows).await
     }
@@ -77,12 +62,25 @@ impl Clickhouse {
         self.insert_rows("wallet_delegations", rows).await
     }
 
+    pub async fn insert_minting_reports(&self, rows: &[FlpMintingReportRow]) -> Result<()> {
+        self.insert_rows("flp_minting_reports", rows).await
+    }
+
     pub async fn insert_positions(&self, rows: &[FlpPositionRow]) -> Result<()> {
         self.insert_rows("flp_positions", rows).await
     }
+
+    pub async fn insert_cycle_totals(&self, rows: &[FlpCycleTotalRow]) -> Result<()> {
+        self.insert_rows("flp_cycle_project_totals", rows).await
+    }
+
     pub async fn insert_delegation_mappings(&self, rows: &[DelegationMappingRow]) -> Result<()> {
         self.insert_rows("delegation_mappings", rows).await
     }
+
+    pub async fn insert_csv_rejections(&self, rows: &[CsvRejectionRow]) -> Result<()> {
+        self.insert_rows("csv_rejections", rows).await
+    }
     pub async fn insert_explorer_stats(&self, rows: &[AtlasExplorerRow]) -> Result<()> {
         self.insert_rows("atlas_explorer", rows).await
     }
@@ -99,6 +97,10 @@ impl Clickhouse {
         self.insert_rows("ao_mainnet_block_state", rows).await
     }
 
+    pub async fn insert_mainnet_block_heights(&self, rows: &[MainnetBlockHeightRow]) -> Result<()> {
+        self.insert_rows("ao_mainnet_block_heights", rows).await
+    }
+
     pub async fn insert_ao_token_messages(&self, rows: &[AoTokenMessageRow]) -> Result<()> {
         self.insert_rows("ao_token_messages", rows).await
     }
@@ -200,7 +202,7 @@ impl Clickhouse {
             .client
             .query(
                 "select token, \
-                    argMax(last_complete_height, s.updated_at) as last_complete_height, \
+                    max(s.last_complete_height) as last_complete_height, \
                     max(s.updated_at) as updated_at \
                  from ao_token_block_state as s \
                  where token = ? \
@@ -212,6 +214,187 @@ impl Clickhouse {
         Ok(rows.into_iter().next())
     }
 
+    /// heights in `[from, to]` with neither a completion row nor any stored
+    /// message. empty heights indexed before `tracked_from` never got a
+    /// completion row, so only heights from it on are reported
+    pub async fn fetch_mainnet_missing_heights(
+        &self,
+        protocol: &str,
+        from: u32,
+        to: u32,
+        tracked_from: u32,
+        limit: u64,
+    ) -> Result<Vec<u32>> {
+        let query = "\
+            select toUInt32(number) as height \
+            from numbers(?, ?) \
+            where height >= ? \
+              and height not in (select height from ao_mainnet_block_heights where protocol = ?) \
+              and height not in ( \
+                select block_height from ao_mainnet_messages \
+                where protocol = ? and block_height between ? and ?) \
+            order by height \
+            limit ?";
+        let rows = self
+            .client
+            .query(query)
+            .bind(from)
+            .bind(span_len(from, to))
+            .bind(tracked_from)
+            .bind(protocol)
+            .bind(protocol)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().map(|row| row.height).collect())
+    }
+
+    /// heights in `[from, to]` that have stored messages but were not verified
+    /// complete: fewer messages than the completion row recorded, or no
+    /// completion row at all once heights started being tracked (`tracked_from`)
+    pub async fn fetch_mainnet_partial_heights(
+        &self,
+        protocol: &str,
+        from: u32,
+        to: u32,
+        tracked_from: u32,
+        limit: u64,
+    ) -> Result<Vec<u32>> {
+        let query = "\
+            select height \
+            from ( \
+                select block_height as height, uniqExact(msg_id) as stored, \
+                    toUInt64(0) as expected, toUInt8(0) as tracked \
+                from ao_mainnet_messages \
+                where protocol = ? and block_height between ? and ? \
+                group by block_height \
+                union all \
+                select height, toUInt64(0) as stored, \
+                    argMax(msg_count, updated_at) as expected, toUInt8(1) as tracked \
+                from ao_mainnet_block_heights \
+                where protocol = ? and height between ? and ? \
+                group by height \
+            ) \
+            group by height \
+            having (max(tracked) = 0 and height >= ?) or sum(stored) < sum(expected) \
+            order by height \
+            limit ?";
+        let rows = self
+            .client
+            .query(query)
+            .bind(protocol)
+            .bind(from)
+            .bind(to)
+            .bind(protocol)
+            .bind(from)
+            .bind(to)
+            .bind(tracked_from)
+            .bind(limit)
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().map(|row| row.height).collect())
+    }
+
+    /// lowest height with a completion row, heights below it predate tracking
+    pub async fn first_tracked_mainnet_height(&self, protocol: &str) -> Result<Option<u32>> {
+        let rows = self
+            .client
+            .query(
+                "select height from ao_mainnet_block_heights \
+                 where protocol = ? \
+                 order by height asc \
+                 limit 1",
+            )
+            .bind(protocol)
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().next().map(|row| row.height))
+    }
+
+    /// heights in `[from, to]` with neither a state row nor any stored message
+    pub async fn fetch_ao_token_missing_heights(
+        &self,
+        token: &str,
+        from: u32,
+        to: u32,
+        limit: u64,
+    ) -> Result<Vec<u32>> {
+        let query = "\
+            select toUInt32(number) as height \
+            from numbers(?, ?) \
+            where height not in (select last_complete_height from ao_token_block_state where token = ?) \
+              and height not in ( \
+                select block_height from ao_token_messages \
+                where token = ? and block_height between ? and ?) \
+            order by height \
+            limit ?";
+        let rows = self
+            .client
+            .query(query)
+            .bind(from)
+            .bind(span_len(from, to))
+            .bind(token)
+            .bind(token)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().map(|row| row.height).collect())
+    }
+
+    /// heights in `[from, to]` with stored messages but no state row, i.e. the
+    /// worker stopped before finishing both transfer and process queries
+    pub async fn fetch_ao_token_partial_heights(
+        &self,
+        token: &str,
+        from: u32,
+        to: u32,
+        limit: u64,
+    ) -> Result<Vec<u32>> {
+        let query = "\
+            select distinct block_height as height \
+            from ao_token_messages \
+            where token = ? and block_height between ? and ? \
+              and block_height not in (select last_complete_height from ao_token_block_state where token = ?) \
+            order by height \
+            limit ?";
+        let rows = self
+            .client
+            .query(query)
+            .bind(token)
+            .bind(from)
+            .bind(to)
+            .bind(token)
+            .bind(limit)
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().map(|row| row.height).collect())
+    }
+
+    /// raw export response, formatted by clickhouse and read chunk by chunk
+    pub fn export_bytes(&self, export: &ExportQuery, format: ExportFormat) -> Result<BytesCursor> {
+        let mut query = self.client.query(&export.sql);
+        for bind in &export.binds {
+            query = match bind {
+                ExportBind::Str(val) => query.bind(val),
+                ExportBind::U32(val) => query.bind(val),
+            };
+        }
+        Ok(query.fetch_bytes(format.clickhouse_format())?)
+    }
+
+    pub async fn latest_delegation_mapping_height(&self) -> Result<Option<u32>> {
+        let rows = self
+            .client
+            .query("select height from delegation_mappings order by height desc limit 1")
+            .fetch_all::<HeightRow>()
+            .await?;
+        Ok(rows.into_iter().next().map(|row| row.height))
+    }
+
     pub async fn has_oracle(&self, ticker: &str, tx_id: &str) -> Result<bool> {
         let query =
             "select count() as cnt from oracle_snapshots where ticker = ? and tx_id = ? limit 1"
@@ -226,6 +409,47 @@ impl Clickhouse {
         Ok(row.cnt > 0)
     }
 
+    pub async fn has_oracle_payload(&self, ticker: &str, tx_id: &str) -> Result<bool> {
+        let row = self
+            .client
+            .query("select count() as cnt from oracle_payloads where ticker = ? and tx_id = ?")
+            .bind(ticker)
+            .bind(tx_id)
+            .fetch_one::<CountRow>()
+            .await?;
+        Ok(row.cnt > 0)
+    }
+
+    pub async fn has_minting_report(&self, project: &str, report_id: &str) -> Result<bool> {
+        let row = self
+            .client
+            .query(
+                "select count() as cnt from flp_minting_reports \
+                 where project = ? and report_id = ? limit 1",
+            )
+            .bind(project)
+            .bind(report_id)
+            .fetch_one::<CountRow>()
+            .await?;
+        Ok(row.cnt > 0)
+    }
+
+    /// sets the block time on a tx's rows stored without one, re-inserting
+    /// them over their replacing key
+    pub async fn stamp_delegation_mapping(&self, tx_id: &str, block_timestamp: u64) -> Result<()> {
+        self.client
+            .query(
+                "insert into delegation_mappings (ts, height, block_timestamp, tx_id, wallet_from, wallet_to, factor) \
+                 select ts, height, ?, tx_id, wallet_from, wallet_to, factor \
+                 from delegation_mappings final where tx_id = ? and block_timestamp = 0",
+            )
+            .bind(block_timestamp)
+            .bind(tx_id)
+            .execute()
+            .await?;
+        Ok(())
+    }
+
     pub async fn has_delegation_mapping(&self, tx_id: &str) -> Result<bool> {
         let query = "select count() as cnt from delegation_mappings where tx_id = ? limit 1";
         let row = self
@@ -275,15 +499,29 @@ pub struct OracleSnapshotRow {
     pub tx_id: String,
 }
 
+/// Set-Balances rows of an oracle update as JSON, served back by the server
+#[derive(Clone, Debug, Row, Serialize)]
+pub struct OraclePayloadRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+    pub ticker: String,
+    pub tx_id: String,
+    pub payload: String,
+}
+
 #[derive(Clone, Debug, Row, Serialize)]
 pub struct WalletBalanceRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
     pub ticker: String,
-    pub wallet: String,
-    pub eoa: String,
-    pub amount: String,
-    pub ar_balance: String,
+    pub wallet: ArAddress,
+    pub eoa: EvmAddress,
+    #[serde(serialize_with = "serialize_human")]
+    pub amount: Amount,
+    /// Set-Balances amount as published by the oracle, before decimals scaling
+    pub raw_amount: RawAmount,
+    #[serde(serialize_with = "serialize_human")]
+    pub ar_balance: Amount,
     pub tx_id: String,
 }
 
@@ -291,7 +529,16 @@ pub struct WalletBalanceRow {
 pub struct WalletDelegationRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
-    pub wallet: String,
+    pub wallet: ArAddress,
+    pub payload: String,
+}
+
+#[derive(Clone, Debug, Row, Serialize)]
+pub struct FlpMintingReportRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+    pub project: String,
+    pub report_id: MessageId,
     pub payload: String,
 }
 
@@ -300,12 +547,27 @@ pub struct FlpPositionRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
     pub ticker: String,
-    pub wallet: String,
-    pub eoa: String,
+    pub wallet: ArAddress,
+    pub eoa: EvmAddress,
     pub project: String,
     pub factor: u32,
-    pub amount: String,
-    pub ar_amount: String,
+    #[serde(serialize_with = "serialize_human")]
+    pub amount: Amount,
+    #[serde(serialize_with = "serialize_human")]
+    pub ar_amount: Amount,
+}
+
+/// an FLP's delegated totals on one oracle cycle, amounts as `Decimal(38, 18)`
+/// and `Decimal(38, 12)` base units
+#[derive(Clone, Debug, Row, Serialize)]
+pub struct FlpCycleTotalRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+    pub ticker: String,
+    pub project: String,
+    pub amount: i128,
+    pub ar_amount: i128,
+    pub delegators: u64,
 }
 
 #[derive(Clone, Debug, Row, Serialize)]
@@ -313,12 +575,26 @@ pub struct DelegationMappingRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
     pub height: u32,
+    /// unix seconds of the tx's block
+    pub block_timestamp: u64,
     pub tx_id: String,
-    pub wallet_from: String,
+    pub wallet_from: ArAddress,
     pub wallet_to: String,
     pub factor: u32,
 }
 
+#[derive(Clone, Debug, Row, Serialize)]
+pub struct CsvRejectionRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+    /// oracle ticker or `delegation_mappings`
+    pub source: String,
+    pub tx_id: String,
+    pub line: u64,
+    pub raw: String,
+    pub reason: String,
+}
+
 #[derive(Clone, Debug, Row, Serialize)]
 pub struct AtlasExplorerRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
@@ -336,7 +612,7 @@ pub struct AtlasExplorerRow {
     pub modules_rolling: u64,
 }
 
-#[derive(Clone, Debug, Row, Serialize)]
+#[derive(Clone, Debug, Row, Serialize, Deserialize)]
 pub struct MainnetMessageRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
@@ -361,7 +637,7 @@ pub struct MainnetMessageTagRow {
     pub tag_value: String,
 }
 
-#[derive(Clone, Debug, Row, Serialize)]
+#[derive(Clone, Debug, Row, Serialize, Deserialize)]
 pub struct AoTokenMessageRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     pub ts: DateTime<Utc>,
@@ -405,6 +681,15 @@ pub struct MainnetBlockStateRow {
     pub last_cursor: String,
 }
 
+#[derive(Clone, Debug, Row, Serialize, Deserialize)]
+pub struct MainnetBlockHeightRow {
+    pub protocol: String,
+    pub height: u32,
+    pub msg_count: u64,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Clone, Debug, Row, Serialize, Deserialize)]
 pub struct MainnetExplorerRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
@@ -497,3 +782,12 @@ impl From<ExplorerSelectRow> for BlockStats {
 struct CountRow {
     pub cnt: u64,
 }
+
+#[derive(Debug, Row, Deserialize)]
+struct HeightRow {
+    height: u32,
+}
+
+fn span_len(from: u32, to: u32) -> u64 {
+    if to < from { 0 } else { (to - from) as u64 + 1 }
+}
@@ -1,6 +1,6 @@
-use common::env::get_env_var;
+use common::env::{get_env_var, load_atlas_toml};
 use serde::Deserialize;
-use std::{fs, io::ErrorKind, time::Duration};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Config {
@@ -11,6 +11,8 @@ pub struct Config {
     pub interval: Duration,
     pub concurrency: usize,
     pub tickers: Vec<String>,
+    pub repair_interval: Duration,
+    pub repair_batch: u64,
     pub indexers: IndexerConfig,
 }
 
@@ -21,6 +23,7 @@ pub struct IndexerConfig {
     pub explorer: bool,
     pub flp: bool,
     pub mainnet: bool,
+    pub repair: bool,
 }
 
 impl Default for IndexerConfig {
@@ -31,6 +34,7 @@ impl Default for IndexerConfig {
             explorer: true,
             flp: true,
             mainnet: true,
+            repair: false,
         }
     }
 }
@@ -48,6 +52,7 @@ struct FileIndexersConfig {
     flp: Option<bool>,
     explorer: Option<bool>,
     mainnet: Option<bool>,
+    repair: Option<bool>,
 }
 
 impl Config {
@@ -74,6 +79,16 @@ impl Config {
             .map(|v| v.trim().to_ascii_lowercase())
             .filter(|v| !v.is_empty())
             .collect();
+        let repair_interval = get_env_var("GAP_REPAIR_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| Duration::from_secs(3600));
+        let repair_batch = get_env_var("GAP_REPAIR_BATCH")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(500);
         let mut config = Config {
             clickhouse_url,
             clickhouse_user,
@@ -82,9 +97,11 @@ impl Config {
             interval,
             concurrency,
             tickers,
+            repair_interval,
+            repair_batch,
             indexers: IndexerConfig::default(),
         };
-        if let Some(file_config) = FileConfig::load() {
+        if let Some(file_config) = load_atlas_toml::<FileConfig>() {
             config.indexers.apply(file_config.indexers);
         }
         config
@@ -108,26 +125,8 @@ impl IndexerConfig {
         if let Some(value) = file.mainnet {
             self.mainnet = value;
         }
-    }
-}
-
-impl FileConfig {
-    fn load() -> Option<Self> {
-        let path = get_env_var("ATLAS_CONFIG").unwrap_or_else(|_| "atlas.toml".into());
-        let contents = match fs::read_to_string(&path) {
-            Ok(contents) => contents,
-            Err(err) if err.kind() == ErrorKind::NotFound => return None,
-            Err(err) => {
-                eprintln!("failed to read atlas config {path}: {err}");
-                return None;
-            }
-        };
-        match toml::from_str::<FileConfig>(&contents) {
-            Ok(config) => Some(config),
-            Err(err) => {
-                eprintln!("failed to parse atlas config {path}: {err}");
-                None
-            }
+        if let Some(value) = file.repair {
+            self.repair = value;
         }
     }
 }
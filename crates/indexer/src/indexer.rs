@@ -1,6 +1,7 @@
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
 use common::{
+    amount::{AR_DECIMALS, Amount, LST_DECIMALS},
     ao_token::{
         AoTokenMessageMeta, AoTokenMessagesPage, AoTokenQuery, scan_arweave_block_for_token_msgs,
     },
@@ -9,61 +10,91 @@ use common::{
         PI_TOKEN_PROCESS, PI_TOKEN_START,
     },
     delegation::{DelegationMappingMeta, DelegationMappingsPage, get_delegation_mappings},
-    gateway::get_ar_balance,
     gql::OracleStakers,
+    ids::{ArAddress, MessageId},
     mainnet::{
         DataProtocol, MainnetBlockMessagesMeta, MainnetBlockMessagesPage, get_network_height,
         scan_arweave_block_for_msgs,
     },
+    minting::get_flp_own_minting_report,
     projects::Project,
 };
 use flp::{
-    csv_parser::{parse_delegation_mappings_res, parse_flp_balances_setting_res},
+    csv_parser::{
+        LenientCsv, RejectedRow, parse_delegation_mappings_res_lenient,
+        parse_flp_balances_setting_res_lenient,
+    },
+    json_parser::parse_own_minting_report,
     types::{DelegationsRes, MAX_FACTOR, SetBalancesData},
     wallet::get_wallet_delegations,
 };
 use futures::{StreamExt, stream};
-use rust_decimal::{Decimal, prelude::FromPrimitive};
 use serde_json::to_string;
-use std::str::FromStr;
+use std::collections::BTreeMap;
 use tokio::{
     runtime::Handle,
     time::{Duration, sleep},
 };
+use tracing::{Instrument, Span, error, info, info_span, warn};
 
 use crate::{
+    balances::ArBalanceCache,
     clickhouse::{
         AoTokenBlockStateRow, AoTokenMessageRow, AoTokenMessageTagRow, AtlasExplorerRow,
-        Clickhouse, DelegationMappingRow, FlpPositionRow, MainnetBlockStateRow, MainnetExplorerRow,
-        MainnetMessageRow, MainnetMessageTagRow, OracleSnapshotRow, WalletBalanceRow,
-        WalletDelegationRow,
+        Clickhouse, CsvRejectionRow, DelegationMappingRow, FlpCycleTotalRow, FlpMintingReportRow,
+        FlpPositionRow, MainnetBlockHeightRow, MainnetBlockStateRow, MainnetExplorerRow,
+        MainnetMessageRow, MainnetMessageTagRow, OraclePayloadRow, OracleSnapshotRow,
+        WalletBalanceRow, WalletDelegationRow,
     },
     config::Config,
+    repair::{RepairTarget, run_repair_pass},
 };
 // use explorer;
 
 const ARWEAVE_TIP_SAFE_GAP: u64 = 3;
 
 #[derive(Clone, Copy)]
-struct TokenConfig {
-    label: &'static str,
-    process_id: &'static str,
-    start_height: u32,
+pub struct TokenConfig {
+    pub label: &'static str,
+    pub process_id: &'static str,
+    pub start_height: u32,
+}
+
+impl TokenConfig {
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "ao" => Some(Self {
+                label: "ao",
+                process_id: AO_TOKEN_PROCESS,
+                start_height: AO_TOKEN_START,
+            }),
+            "pi" => Some(Self {
+                label: "pi",
+                process_id: PI_TOKEN_PROCESS,
+                start_height: PI_TOKEN_START,
+            }),
+            _ => None,
+        }
+    }
 }
 
 pub struct Indexer {
     config: Config,
     clickhouse: Clickhouse,
+    ar_balances: ArBalanceCache,
 }
 
 impl Indexer {
     pub fn new(config: Config, clickhouse: Clickhouse) -> Self {
-        Indexer { config, clickhouse }
+        Indexer {
+            config,
+            clickhouse,
+            ar_balances: ArBalanceCache::new(),
+        }
     }
 
     pub async fn run(&self) -> Result<()> {
-        self.clickhouse.ensure().await?;
-        // self.reindex_mainnet_gap(1_821_500).await?;
+        self.clickhouse.migrate().await?;
         if self.config.indexers.explorer {
             self.spawn_explorer_bridge().await?;
         }
@@ -77,23 +108,25 @@ impl Indexer {
         if self.config.indexers.ao || self.config.indexers.pi {
             self.spawn_ao_token_indexer().await?;
         }
-        // self.spawn_backfill();
+        if self.config.indexers.repair {
+            self.spawn_gap_repair();
+        }
         if self.config.indexers.flp {
-            println!("indexer ready with tickers {:?}", self.config.tickers);
+            info!(tickers = ?self.config.tickers, "indexer ready");
         } else {
-            println!("indexer ready");
+            info!("indexer ready");
         }
         if self.config.indexers.flp {
             if let Err(err) = self.run_once().await {
-                eprintln!("index cycle error: {err:?}");
+                error!(error = ?err, "index cycle error");
             }
             let mut interval = tokio::time::interval(self.config.interval);
             loop {
-                println!("waiting {:?}", self.config.interval);
+                info!(interval = ?self.config.interval, "waiting for next cycle");
                 interval.tick().await;
-                println!("starting new cycle");
+                info!("starting new cycle");
                 if let Err(err) = self.run_once().await {
-                    eprintln!("index cycle error: {err:?}");
+                    error!(error = ?err, "index cycle error");
                 }
             }
         }
@@ -103,14 +136,29 @@ impl Indexer {
 
     async fn run_once(&self) -> Result<()> {
         if self.config.indexers.flp {
-            if let Err(err) = self.index_delegation_mappings().await {
-                eprintln!("delegation mapping error: {err:?}");
+            if let Err(err) = self
+                .index_delegation_mappings()
+                .instrument(info_span!("delegation_mappings"))
+                .await
+            {
+                error!(error = ?err, "delegation mapping error");
+            }
+            if let Err(err) = self
+                .index_minting_reports()
+                .instrument(info_span!("minting_reports"))
+                .await
+            {
+                error!(error = ?err, "minting reports error");
             }
         }
         if self.config.indexers.flp {
             for ticker in &self.config.tickers {
-                if let Err(err) = self.index_ticker(ticker).await {
-                    eprintln!("ticker {ticker} error: {err:?}");
+                if let Err(err) = self
+                    .index_ticker(ticker)
+                    .instrument(info_span!("ticker_cycle", ticker = %ticker))
+                    .await
+                {
+                    error!(error = ?err, "ticker cycle error");
                 }
             }
         }
@@ -125,7 +173,9 @@ impl Indexer {
             .unwrap_or_else(|| explorer::update_stats_gap::LATEST_AGG_STATS_SET.clone());
         let clickhouse = self.clickhouse.clone();
         let handle = Handle::current();
+        let span = info_span!("explorer_worker", start = start.height);
         std::thread::spawn(move || {
+            let _span = span.entered();
             if let Err(err) = explorer::run_stats_indexer_from(start, |stats| {
                 let row = match AtlasExplorerRow::from_block_stats(stats) {
                     Some(row) => row,
@@ -134,18 +184,32 @@ impl Indexer {
                 let rows = [row];
                 handle.block_on(async { clickhouse.insert_explorer_stats(&rows).await })
             }) {
-                eprintln!("atlas explorer indexer error: {err:?}");
+                error!(error = ?err, "atlas explorer indexer error");
             }
         });
         Ok(())
     }
 
-    // async fn reindex_mainnet_gap(&self, start: u32) -> Result<()> {
-    //     for protocol in [DataProtocol::A, DataProtocol::B] {
-    //         run_mainnet_gap_worker(self.clickhouse.clone(), protocol, start).await?;
-    //     }
-    //     Ok(())
-    // }
+    fn spawn_gap_repair(&self) {
+        let clickhouse = self.clickhouse.clone();
+        let targets = RepairTarget::enabled(&self.config.indexers);
+        let period = self.config.repair_interval;
+        let batch = self.config.repair_batch;
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval(period);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) =
+                        run_repair_pass(&clickhouse, &targets, None, None, batch).await
+                    {
+                        error!(error = ?err, "gap repair error");
+                    }
+                }
+            }
+            .instrument(info_span!("gap_repair_task")),
+        );
+    }
 
     async fn spawn_mainnet_indexer(&self) -> Result<()> {
         for (protocol, start) in [
@@ -153,15 +217,15 @@ impl Indexer {
             (DataProtocol::B, DATA_PROTOCOL_B_START),
         ] {
             let clickhouse = self.clickhouse.clone();
-            tokio::spawn(async move {
-                if let Err(err) = run_mainnet_worker(clickhouse, protocol, start).await {
-                    eprintln!(
-                        "mainnet indexer error protocol={} start={} err={err:?}",
-                        protocol_label(protocol),
-                        start
-                    );
+            let span = info_span!("mainnet_worker", protocol = protocol_label(protocol), start);
+            tokio::spawn(
+                async move {
+                    if let Err(err) = run_mainnet_worker(clickhouse, protocol, start).await {
+                        error!(error = ?err, "mainnet indexer error");
+                    }
                 }
-            });
+                .instrument(span),
+            );
         }
         Ok(())
     }
@@ -169,35 +233,32 @@ impl Indexer {
     async fn spawn_ao_token_indexer(&self) -> Result<()> {
         let mut tokens = Vec::new();
         if self.config.indexers.ao {
-            tokens.push(TokenConfig {
-                label: "ao",
-                process_id: AO_TOKEN_PROCESS,
-                start_height: AO_TOKEN_START,
-            });
+            tokens.extend(TokenConfig::from_label("ao"));
         }
         if self.config.indexers.pi {
-            tokens.push(TokenConfig {
-                label: "pi",
-                process_id: PI_TOKEN_PROCESS,
-                start_height: PI_TOKEN_START,
-            });
+            tokens.extend(TokenConfig::from_label("pi"));
         }
         for token in tokens {
             let clickhouse = self.clickhouse.clone();
-            tokio::spawn(async move {
-                if let Err(err) = run_token_worker(clickhouse, token).await {
-                    eprintln!(
-                        "token indexer error token={} start={} err={err:?}",
-                        token.label, token.start_height
-                    );
+            let span = info_span!(
+                "token_worker",
+                token = token.label,
+                start = token.start_height
+            );
+            tokio::spawn(
+                async move {
+                    if let Err(err) = run_token_worker(clickhouse, token).await {
+                        error!(error = ?err, "token indexer error");
+                    }
                 }
-            });
+                .instrument(span),
+            );
         }
         Ok(())
     }
 
-    async fn rebuild_mainnet_explorer(&self) -> Result<()> {
-        println!("rebuilding ao mainnet explorer table from scratch");
+    pub async fn rebuild_mainnet_explorer(&self) -> Result<()> {
+        info!("rebuilding ao mainnet explorer table from scratch");
         self.clickhouse.truncate_mainnet_explorer().await?;
         let mut last_height: u32 = 0;
         let mut tx_roll: u64 = 0;
@@ -233,42 +294,59 @@ impl Indexer {
                 });
             }
             self.clickhouse.insert_mainnet_explorer_rows(&rows).await?;
-            println!("mainnet explorer indexed up to height {last_height}");
+            info!(
+                height = last_height,
+                "mainnet explorer indexed up to height"
+            );
         }
-        println!("ao mainnet explorer rebuild complete");
+        info!("ao mainnet explorer rebuild complete");
         Ok(())
     }
 
     async fn spawn_mainnet_explorer_tail(&self) -> Result<()> {
         let clickhouse = self.clickhouse.clone();
-        tokio::spawn(async move {
-            if let Err(err) = run_mainnet_explorer_tail(clickhouse).await {
-                eprintln!("mainnet explorer tail error: {err:?}");
+        tokio::spawn(
+            async move {
+                if let Err(err) = run_mainnet_explorer_tail(clickhouse).await {
+                    error!(error = ?err, "mainnet explorer tail error");
+                }
             }
-        });
+            .instrument(info_span!("mainnet_explorer_tail")),
+        );
         Ok(())
     }
 
-    // fn spawn_backfill(&self) {
-    //     println!("backfill called");
-    //     let clickhouse = self.clickhouse.clone();
-    //     tokio::spawn(async move {
-    //         if let Err(err) = backfill::run(clickhouse).await {
-    //             eprintln!("delegation backfill error: {err:?}");
-    //         }
-    //     });
-    // }
-
     async fn index_ticker(&self, ticker: &str) -> Result<()> {
         let now = Utc::now();
         let ticker_owned = ticker.to_string();
-        let (tx_id, balances) = load_balances(ticker_owned.clone()).await?;
+        let (tx_id, parsed) = load_balances(ticker_owned.clone()).await?;
+        let payload = OraclePayloadRow {
+            ts: now,
+            ticker: ticker_owned.clone(),
+            tx_id: tx_id.clone(),
+            payload: to_string(&parsed.rows)?,
+        };
         if self.clickhouse.has_oracle(&ticker_owned, &tx_id).await? {
-            println!("ticker {ticker}: tx {tx_id} already processed, skipping");
+            // updates indexed before payloads were stored only get the payload
+            if !self
+                .clickhouse
+                .has_oracle_payload(&ticker_owned, &tx_id)
+                .await?
+            {
+                self.clickhouse.insert_oracle_payloads(&[payload]).await?;
+            }
+            info!(%tx_id, "oracle tx already processed, skipping");
             return Ok(());
         }
-        println!("ticker {ticker}: loading balances");
-        println!("ticker {ticker}: balances {}", balances.len());
+        let balances = parsed.rows;
+        info!(%tx_id, balances = balances.len(), rejected = parsed.rejected.len(), "loaded oracle balances");
+        if !parsed.rejected.is_empty() {
+            warn!(%tx_id, rejected = parsed.rejected.len(), "skipping malformed Set-Balances rows");
+            self.clickhouse
+                .insert_csv_rejections(&rejection_rows(now, &ticker_owned, &tx_id, parsed.rejected))
+                .await?;
+        }
+        self.clickhouse.insert_oracle_payloads(&[payload]).await?;
         self.clickhouse
             .insert_oracles(&[OracleSnapshotRow {
                 ts: now,
@@ -277,40 +355,64 @@ impl Indexer {
             }])
             .await?;
 
-        let pairs: Vec<(SetBalancesData, Option<DelegationsRes>, Decimal)> =
+        let height = match spawn_blocking_in_span(get_network_height).await {
+            Ok(height) => Some(height),
+            Err(err) => {
+                warn!(error = %err, "network height lookup failed, AR balances not cached");
+                None
+            }
+        };
+        let addresses: Vec<_> = balances
+            .iter()
+            .map(|entry| entry.ar_address.clone())
+            .collect();
+        let (delegations, ar_balances) = tokio::join!(
             stream::iter(balances.into_iter().map(|entry| async move {
-                let delegation = load_delegations(entry.ar_address.clone()).await;
-                let ar_balance = load_ar_balance(entry.ar_address.clone()).await;
-                (entry, delegation, ar_balance)
+                let delegation = load_delegations(entry.ar_address.to_string()).await;
+                (entry, delegation)
             }))
             .buffer_unordered(self.config.concurrency)
-            .collect()
-            .await;
+            .collect::<Vec<_>>(),
+            self.ar_balances
+                .balances(height, &addresses, self.config.concurrency),
+        );
+        let pairs: Vec<(SetBalancesData, Option<DelegationsRes>, Amount)> = delegations
+            .into_iter()
+            .map(|(entry, delegation)| {
+                let ar_balance = ar_balances
+                    .get(&entry.ar_address)
+                    .copied()
+                    .unwrap_or(Amount::zero(AR_DECIMALS));
+                (entry, delegation, ar_balance)
+            })
+            .collect();
         let delegations_count = pairs.iter().filter(|(_, d, _)| d.is_some()).count();
-        println!("ticker {ticker}: delegations {}", delegations_count);
+        info!(delegations = delegations_count, "loaded wallet delegations");
 
         let mut balance_rows = Vec::with_capacity(pairs.len());
         let mut delegation_rows = Vec::with_capacity(delegations_count);
         let mut position_rows = Vec::new();
+        // flp_positions keeps one row per (project, wallet, cycle), the last
+        // one inserted, so totals see each wallet once the same way
+        let mut stakes = BTreeMap::new();
 
         for (entry, delegation, ar_balance) in pairs {
-            let Some(delegation) = delegation else {
-                continue;
-            };
-            let Some(amount_dec) = normalize_amount(&entry.amount, &ticker_owned) else {
-                continue;
-            };
-            let amount_str = amount_dec.to_string();
-            let ar_balance_str = ar_balance.to_string();
+            let amount = entry.lst_amount(&ticker_owned)?;
+            // every Set-Balances entry is kept, positions only for wallets
+            // with delegations
             balance_rows.push(WalletBalanceRow {
                 ts: now,
                 ticker: ticker_owned.clone(),
                 wallet: entry.ar_address.clone(),
                 eoa: entry.eoa.clone(),
-                amount: amount_str.clone(),
-                ar_balance: ar_balance_str.clone(),
+                amount,
+                raw_amount: entry.amount,
+                ar_balance,
                 tx_id: tx_id.clone(),
             });
+            let Some(delegation) = delegation else {
+                continue;
+            };
             delegation_rows.push(WalletDelegationRow {
                 ts: now,
                 wallet: entry.ar_address.clone(),
@@ -318,13 +420,17 @@ impl Indexer {
             });
             for pref in delegation.delegation_prefs {
                 if Project::is_flp_project(&pref.wallet_to) {
-                    let delegated = delegated_amount(&amount_dec, pref.factor);
-                    let delegated_ar = delegated_amount(&ar_balance, pref.factor);
+                    let delegated = amount.factored(pref.factor, MAX_FACTOR);
+                    let delegated_ar = ar_balance.factored(pref.factor, MAX_FACTOR);
                     // if the delegator had interacted with the FLP Bridge, have no more staked LSTs
                     // but still delegating AR, track them
                     if delegated.is_zero() && delegated_ar.is_zero() {
                         continue;
                     }
+                    stakes.insert(
+                        (pref.wallet_to.clone(), entry.ar_address.clone()),
+                        (delegated, delegated_ar),
+                    );
                     position_rows.push(FlpPositionRow {
                         ts: now,
                         ticker: ticker_owned.clone(),
@@ -332,8 +438,8 @@ impl Indexer {
                         eoa: entry.eoa.clone(),
                         project: pref.wallet_to,
                         factor: pref.factor,
-                        amount: delegated.to_string(),
-                        ar_amount: delegated_ar.to_string(),
+                        amount: delegated,
+                        ar_amount: delegated_ar,
                     });
                 }
             }
@@ -342,11 +448,14 @@ impl Indexer {
         self.clickhouse.insert_balances(&balance_rows).await?;
         self.clickhouse.insert_delegations(&delegation_rows).await?;
         self.clickhouse.insert_positions(&position_rows).await?;
-        println!(
-            "ticker {ticker}: stored balances {} delegations {} positions {}",
-            balance_rows.len(),
-            delegation_rows.len(),
-            position_rows.len()
+        let total_rows = cycle_totals(now, &ticker_owned, &stakes)?;
+        self.clickhouse.insert_cycle_totals(&total_rows).await?;
+        info!(
+            balances = balance_rows.len(),
+            delegations = delegation_rows.len(),
+            positions = position_rows.len(),
+            projects = total_rows.len(),
+            "ticker cycle stored"
         );
         Ok(())
     }
@@ -359,98 +468,198 @@ impl Indexer {
         if self.clickhouse.has_delegation_mapping(&meta.tx_id).await? {
             return Ok(());
         }
-        println!(
-            "forward delegation mapping tx {} height {}",
-            meta.tx_id, meta.height
-        );
+        info!(tx_id = %meta.tx_id, height = meta.height, "forward delegation mapping");
         if let Err(err) = self.store_delegation_mapping(&meta).await {
-            eprintln!("forward delegation mapping tx {} error {err:?}", meta.tx_id);
+            error!(tx_id = %meta.tx_id, error = ?err, "forward delegation mapping error");
         } else {
-            println!(
-                "forward delegation mapping stored tx {} height {}",
-                meta.tx_id, meta.height
-            );
+            info!(tx_id = %meta.tx_id, height = meta.height, "forward delegation mapping stored");
         }
         Ok(())
     }
 
-    async fn store_delegation_mapping(&self, meta: &DelegationMappingMeta) -> Result<()> {
-        let rows = build_mapping_rows(meta).await?;
-        self.clickhouse.insert_delegation_mappings(&rows).await?;
+    async fn index_minting_reports(&self) -> Result<()> {
+        for project in Project::get_all() {
+            let pid = project.pid.clone();
+            let report_id = match spawn_blocking_in_span(move || {
+                get_flp_own_minting_report(&pid).and_then(|id| MessageId::parse(&id))
+            })
+            .await
+            {
+                Ok(id) => id,
+                Err(err) => {
+                    warn!(project = %project.pid, error = %err, "minting report lookup failed");
+                    continue;
+                }
+            };
+            if self
+                .clickhouse
+                .has_minting_report(&project.pid, &report_id)
+                .await?
+            {
+                continue;
+            }
+            let fetch_id = report_id.to_string();
+            let report = match spawn_blocking_in_span(move || parse_own_minting_report(&fetch_id))
+                .await
+            {
+                Ok(report) => report,
+                Err(err) => {
+                    warn!(project = %project.pid, %report_id, error = %err, "minting report parse failed");
+                    continue;
+                }
+            };
+            self.clickhouse
+                .insert_minting_reports(&[FlpMintingReportRow {
+                    ts: Utc::now(),
+                    project: project.pid.clone(),
+                    report_id: report_id.clone(),
+                    payload: to_string(&report)?,
+                }])
+                .await?;
+            info!(project = %project.pid, %report_id, "minting report stored");
+        }
         Ok(())
     }
-}
-
-fn normalize_amount(amount: &str, ticker: &str) -> Option<Decimal> {
-    let amt = Decimal::from_str(amount).ok()?;
-    Some((amt / ticker_scale(ticker)).normalize())
-}
 
-// all 3 oracles tokens are 18 decimals
-fn ticker_scale(ticker: &str) -> Decimal {
-    let key = ticker.to_ascii_lowercase();
-    match key.as_str() {
-        "usds" | "dai" | "steth" => Decimal::from_str("1000000000000000000").unwrap(),
-        _ => Decimal::ONE,
+    async fn store_delegation_mapping(&self, meta: &DelegationMappingMeta) -> Result<()> {
+        let (rows, rejected) = build_mapping_rows(meta).await?;
+        self.clickhouse.insert_csv_rejections(&rejected).await?;
+        self.clickhouse.insert_delegation_mappings(&rows).await?;
+        Ok(())
     }
 }
 
-fn delegated_amount(amount: &Decimal, factor: u32) -> Decimal {
-    (amount * Decimal::from(factor) / Decimal::from(MAX_FACTOR)).normalize()
+/// runs a blocking gateway call on tokio's blocking pool, keeping the
+/// caller's span so the `common` gateway spans nest under the worker ones
+pub async fn spawn_blocking_in_span<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let span = Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f)).await?
 }
 
-async fn load_balances(ticker: String) -> Result<(String, Vec<SetBalancesData>)> {
-    tokio::task::spawn_blocking(move || -> Result<(String, Vec<SetBalancesData>)> {
+async fn load_balances(ticker: String) -> Result<(String, LenientCsv<SetBalancesData>)> {
+    spawn_blocking_in_span(move || -> Result<(String, LenientCsv<SetBalancesData>)> {
         let oracle = OracleStakers::new(&ticker).build()?.send()?;
         let tx_id = oracle.clone().last_update()?;
-        let data = parse_flp_balances_setting_res(&tx_id)?;
+        let data = parse_flp_balances_setting_res_lenient(&tx_id)?;
         Ok((tx_id, data))
     })
-    .await?
+    .await
+}
+
+/// source of the rejected Delegation-Mappings rows
+const DELEGATION_MAPPINGS_SOURCE: &str = "delegation_mappings";
+
+fn rejection_rows(
+    ts: DateTime<Utc>,
+    source: &str,
+    tx_id: &str,
+    rejected: Vec<RejectedRow>,
+) -> Vec<CsvRejectionRow> {
+    rejected
+        .into_iter()
+        .map(|row| CsvRejectionRow {
+            ts,
+            source: source.to_string(),
+            tx_id: tx_id.to_string(),
+            line: row.line,
+            raw: row.raw,
+            reason: row.reason,
+        })
+        .collect()
+}
+
+/// per-FLP totals of a cycle's `(project, wallet)` stakes, delegators
+/// counted once per wallet
+fn cycle_totals(
+    ts: DateTime<Utc>,
+    ticker: &str,
+    stakes: &BTreeMap<(String, ArAddress), (Amount, Amount)>,
+) -> Result<Vec<FlpCycleTotalRow>> {
+    let mut totals: BTreeMap<&str, (Amount, Amount, u64)> = BTreeMap::new();
+    for ((project, _), (amount, ar_amount)) in stakes {
+        let (amount, ar_amount) = (amount.rescale(LST_DECIMALS), ar_amount.rescale(AR_DECIMALS));
+        let (Some(amount), Some(ar_amount)) = (amount, ar_amount) else {
+            return Err(anyhow!("{project} stake doesn't fit the totals decimals"));
+        };
+        let entry = totals.entry(project).or_insert((
+            Amount::zero(LST_DECIMALS),
+            Amount::zero(AR_DECIMALS),
+            0,
+        ));
+        entry.0 = entry
+            .0
+            .checked_add(&amount)
+            .ok_or_else(|| anyhow!("{project} amount total overflows"))?;
+        entry.1 = entry
+            .1
+            .checked_add(&ar_amount)
+            .ok_or_else(|| anyhow!("{project} AR total overflows"))?;
+        entry.2 += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(project, (amount, ar_amount, delegators))| {
+            Ok(FlpCycleTotalRow {
+                ts,
+                ticker: ticker.to_string(),
+                project: project.to_string(),
+                amount: i128::try_from(amount.raw)?,
+                ar_amount: i128::try_from(ar_amount.raw)?,
+                delegators,
+            })
+        })
+        .collect()
 }
 
 async fn load_delegations(address: String) -> Option<DelegationsRes> {
     let fallback = address.clone();
-    match tokio::task::spawn_blocking(move || get_wallet_delegations(&address)).await {
-        Ok(Ok(data)) => Some(data),
-        _ => {
-            eprintln!("delegation lookup failed for {fallback}, skipping");
+    match spawn_blocking_in_span(move || get_wallet_delegations(&address)).await {
+        Ok(data) => Some(data),
+        Err(err) => {
+            warn!(wallet = %fallback, error = %err, "delegation lookup failed, skipping");
             None
         }
     }
 }
 
-async fn load_ar_balance(address: String) -> Decimal {
-    match tokio::task::spawn_blocking(move || get_ar_balance(&address)).await {
-        Ok(Ok(value)) => Decimal::from_f64(value).unwrap_or(Decimal::ZERO),
-        _ => Decimal::ZERO,
-    }
-}
-
 async fn fetch_latest_mapping_page(limit: u32) -> Result<DelegationMappingsPage> {
-    tokio::task::spawn_blocking(move || get_delegation_mappings(Some(limit), None)).await?
+    spawn_blocking_in_span(move || get_delegation_mappings(Some(limit), None)).await
 }
 
-async fn build_mapping_rows(meta: &DelegationMappingMeta) -> Result<Vec<DelegationMappingRow>> {
+/// the preference rows of a Delegation-Mappings tx and its malformed lines
+pub(crate) async fn build_mapping_rows(
+    meta: &DelegationMappingMeta,
+) -> Result<(Vec<DelegationMappingRow>, Vec<CsvRejectionRow>)> {
     let tx_id = meta.tx_id.clone();
     let height = meta.height;
-    let csv_rows = tokio::task::spawn_blocking({
+    let parsed = spawn_blocking_in_span({
         let fetch_id = tx_id.clone();
-        move || parse_delegation_mappings_res(&fetch_id)
+        move || parse_delegation_mappings_res_lenient(&fetch_id)
     })
-    .await??;
+    .await?;
     let ts = Utc::now();
-    Ok(csv_rows
+    if !parsed.rejected.is_empty() {
+        warn!(%tx_id, rejected = parsed.rejected.len(), "skipping malformed Delegation-Mappings rows");
+    }
+    let rejected = rejection_rows(ts, DELEGATION_MAPPINGS_SOURCE, &tx_id, parsed.rejected);
+    let rows = parsed
+        .rows
         .into_iter()
         .map(|row| DelegationMappingRow {
             ts,
             height,
+            block_timestamp: meta.block_timestamp,
             tx_id: tx_id.clone(),
             wallet_from: row.wallet_from,
             wallet_to: row.wallet_to,
             factor: row.factor,
         })
-        .collect())
+        .collect();
+    Ok((rows, rejected))
 }
 
 async fn run_mainnet_worker(
@@ -469,29 +678,44 @@ async fn run_mainnet_worker(
             height = height.saturating_add(1);
         }
     }
-    println!("mainnet protocol {protocol_name} starting at height {height}");
+    info!(height, "mainnet worker starting");
+    // msgs stored for the current height, recorded once the height completes
+    // so the gap repair can spot heights left partially indexed
+    let mut height_msgs = 0u64;
     let mut network_tip = fetch_network_height().await.unwrap_or(height as u64);
     loop {
         while height as u64 + ARWEAVE_TIP_SAFE_GAP > network_tip {
             match fetch_network_height().await {
                 Ok(latest) => network_tip = latest,
                 Err(err) => {
-                    eprintln!("mainnet tip fetch error protocol={protocol_name} err={err:?}");
+                    warn!(error = ?err, "mainnet tip fetch error");
                 }
             }
             if height as u64 + ARWEAVE_TIP_SAFE_GAP > network_tip {
-                println!(
-                    "mainnet protocol {protocol_name} waiting, height {height} exceeds tip {network_tip} with gap {ARWEAVE_TIP_SAFE_GAP}"
+                info!(
+                    height,
+                    tip = network_tip,
+                    gap = ARWEAVE_TIP_SAFE_GAP,
+                    "mainnet worker waiting for tip"
                 );
                 sleep(Duration::from_secs(60)).await;
             }
         }
-        let page = match fetch_mainnet_page(protocol, height, cursor.clone()).await {
+        let span = info_span!(
+            "mainnet_page",
+            height,
+            cursor = cursor.as_deref().unwrap_or_default()
+        );
+        let page = match fetch_mainnet_page(protocol, height, cursor.clone())
+            .instrument(span.clone())
+            .await
+        {
             Ok(page) => page,
             Err(err) => {
+                let _span = span.enter();
                 if is_empty_block_error(&err) {
                     cursor = None;
-                    println!("mainnet protocol {protocol_name} height {height} empty");
+                    info!("mainnet height empty");
                     let state_row = MainnetBlockStateRow {
                         updated_at: Utc::now(),
                         protocol: protocol_name.clone(),
@@ -499,11 +723,11 @@ async fn run_mainnet_worker(
                         last_cursor: String::new(),
                     };
                     clickhouse.insert_mainnet_block_state(&[state_row]).await?;
+                    record_mainnet_height(&clickhouse, &protocol_name, height, height_msgs).await?;
+                    height_msgs = 0;
                     height = height.saturating_add(1);
                 } else {
-                    eprintln!(
-                        "mainnet fetch error protocol={protocol_name} height={height} err={err:?}"
-                    );
+                    warn!(error = ?err, "mainnet fetch error");
                     let delay = if is_rate_limit_error(&err) {
                         Duration::from_secs(5)
                     } else {
@@ -515,44 +739,9 @@ async fn run_mainnet_worker(
             }
         };
         let ts = Utc::now();
-        let mut message_rows = Vec::with_capacity(page.mappings.len());
-        let mut tag_rows = Vec::new();
-        for meta in page.mappings {
-            let MainnetBlockMessagesMeta {
-                msg_id,
-                owner,
-                recipient,
-                block_height,
-                block_timestamp,
-                bundled_in,
-                data_size,
-                tags,
-            } = meta;
-            let msg_id_for_tags = msg_id.clone();
-            message_rows.push(MainnetMessageRow {
-                ts,
-                protocol: protocol_name.clone(),
-                block_height,
-                block_timestamp,
-                msg_id,
-                owner,
-                recipient,
-                bundled_in,
-                data_size,
-            });
-            for tag in tags {
-                tag_rows.push(MainnetMessageTagRow {
-                    ts,
-                    protocol: protocol_name.clone(),
-                    block_height,
-                    msg_id: msg_id_for_tags.clone(),
-                    tag_key: tag.key,
-                    tag_value: tag.value,
-                });
-            }
-        }
-        clickhouse.insert_mainnet_messages(&message_rows).await?;
-        clickhouse.insert_mainnet_message_tags(&tag_rows).await?;
+        let stored = store_mainnet_page(&clickhouse, &protocol_name, page.mappings, ts)
+            .instrument(span.clone())
+            .await?;
         cursor = if page.has_next_page {
             page.end_cursor.clone()
         } else {
@@ -565,19 +754,87 @@ async fn run_mainnet_worker(
             last_cursor: cursor.clone().unwrap_or_default(),
         };
         clickhouse.insert_mainnet_block_state(&[state_row]).await?;
-        println!(
-            "mainnet protocol {} height {} stored {} msgs",
-            protocol_name,
-            height,
-            message_rows.len()
-        );
+        span.in_scope(|| {
+            info!(
+                msgs = stored,
+                has_next_page = cursor.is_some(),
+                "mainnet page stored"
+            )
+        });
+        height_msgs += stored as u64;
         if cursor.is_none() {
+            record_mainnet_height(&clickhouse, &protocol_name, height, height_msgs).await?;
+            height_msgs = 0;
             height = height.saturating_add(1);
         }
         sleep(Duration::from_secs(1)).await;
     }
 }
 
+/// marks a mainnet height as fully indexed along with its msgs count
+pub(crate) async fn record_mainnet_height(
+    clickhouse: &Clickhouse,
+    protocol_name: &str,
+    height: u32,
+    msg_count: u64,
+) -> Result<()> {
+    let row = MainnetBlockHeightRow {
+        protocol: protocol_name.to_string(),
+        height,
+        msg_count,
+        updated_at: Utc::now(),
+    };
+    clickhouse.insert_mainnet_block_heights(&[row]).await
+}
+
+/// writes a mainnet GQL page (messages + tags) and returns the stored msgs count
+pub async fn store_mainnet_page(
+    clickhouse: &Clickhouse,
+    protocol_name: &str,
+    mappings: Vec<MainnetBlockMessagesMeta>,
+    ts: DateTime<Utc>,
+) -> Result<usize> {
+    let mut message_rows = Vec::with_capacity(mappings.len());
+    let mut tag_rows = Vec::new();
+    for meta in mappings {
+        let MainnetBlockMessagesMeta {
+            msg_id,
+            owner,
+            recipient,
+            block_height,
+            block_timestamp,
+            bundled_in,
+            data_size,
+            tags,
+        } = meta;
+        let msg_id_for_tags = msg_id.clone();
+        message_rows.push(MainnetMessageRow {
+            ts,
+            protocol: protocol_name.to_string(),
+            block_height,
+            block_timestamp,
+            msg_id,
+            owner,
+            recipient,
+            bundled_in,
+            data_size,
+        });
+        for tag in tags {
+            tag_rows.push(MainnetMessageTagRow {
+                ts,
+                protocol: protocol_name.to_string(),
+                block_height,
+                msg_id: msg_id_for_tags.clone(),
+                tag_key: tag.key,
+                tag_value: tag.value,
+            });
+        }
+    }
+    clickhouse.insert_mainnet_messages(&message_rows).await?;
+    clickhouse.insert_mainnet_message_tags(&tag_rows).await?;
+    Ok(message_rows.len())
+}
+
 async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<()> {
     let mut height = token.start_height;
     if let Some(state) = clickhouse.fetch_ao_token_block_state(token.label).await? {
@@ -586,25 +843,28 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
             .max(token.start_height)
             .saturating_add(1);
     }
-    println!("token indexer {} starting at height {height}", token.label);
+    info!(height, "token worker starting");
     let mut network_tip = fetch_network_height().await.unwrap_or(height as u64);
     loop {
         while height as u64 + ARWEAVE_TIP_SAFE_GAP > network_tip {
             match fetch_network_height().await {
                 Ok(latest) => network_tip = latest,
                 Err(err) => {
-                    eprintln!("token {} tip fetch error err={err:?}", token.label);
+                    warn!(error = ?err, "token tip fetch error");
                 }
             }
             if height as u64 + ARWEAVE_TIP_SAFE_GAP > network_tip {
-                println!(
-                    "token {} waiting, height {height} exceeds tip {network_tip} with gap {ARWEAVE_TIP_SAFE_GAP}",
-                    token.label
+                info!(
+                    height,
+                    tip = network_tip,
+                    gap = ARWEAVE_TIP_SAFE_GAP,
+                    "token worker waiting for tip"
                 );
                 sleep(Duration::from_secs(60)).await;
             }
         }
 
+        let span = info_span!("token_height", height);
         let transfer_count = match ingest_token_query(
             &clickhouse,
             token,
@@ -612,6 +872,7 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
             height,
             "transfer",
         )
+        .instrument(span.clone())
         .await
         {
             Ok(count) => count,
@@ -621,10 +882,7 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
                     || is_retryable_http_error(&err)
                     || is_not_found_error(&err)
                 {
-                    eprintln!(
-                        "token {} transfer query error height={height} err={err:?}",
-                        token.label
-                    );
+                    span.in_scope(|| warn!(error = ?err, "token transfer query error"));
                     sleep(Duration::from_secs(300)).await;
                     continue;
                 }
@@ -633,6 +891,7 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
         };
         let process_count =
             match ingest_token_query(&clickhouse, token, AoTokenQuery::Process, height, "process")
+                .instrument(span.clone())
                 .await
             {
                 Ok(count) => count,
@@ -642,10 +901,7 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
                         || is_retryable_http_error(&err)
                         || is_not_found_error(&err)
                     {
-                        eprintln!(
-                            "token {} process query error height={height} err={err:?}",
-                            token.label
-                        );
+                        span.in_scope(|| warn!(error = ?err, "token process query error"));
                         sleep(Duration::from_secs(300)).await;
                         continue;
                     }
@@ -659,10 +915,13 @@ async fn run_token_worker(clickhouse: Clickhouse, token: TokenConfig) -> Result<
             updated_at: Utc::now(),
         };
         clickhouse.insert_ao_token_block_state(&[state_row]).await?;
-        println!(
-            "token {} height {height} stored {transfer_count} transfers {process_count} process msgs",
-            token.label
-        );
+        span.in_scope(|| {
+            info!(
+                transfers = transfer_count,
+                process_msgs = process_count,
+                "token height stored"
+            )
+        });
         height = height.saturating_add(1);
         sleep(Duration::from_secs(1)).await;
     }
@@ -673,10 +932,8 @@ pub async fn fetch_mainnet_page(
     height: u32,
     cursor: Option<String>,
 ) -> Result<MainnetBlockMessagesPage> {
-    tokio::task::spawn_blocking(move || {
-        scan_arweave_block_for_msgs(protocol, height, cursor.as_deref())
-    })
-    .await?
+    spawn_blocking_in_span(move || scan_arweave_block_for_msgs(protocol, height, cursor.as_deref()))
+        .await
 }
 
 pub async fn fetch_ao_token_page(
@@ -685,14 +942,14 @@ pub async fn fetch_ao_token_page(
     height: u32,
     cursor: Option<String>,
 ) -> Result<AoTokenMessagesPage> {
-    tokio::task::spawn_blocking(move || {
+    spawn_blocking_in_span(move || {
         scan_arweave_block_for_token_msgs(process_id, query, height, cursor.as_deref())
     })
-    .await?
+    .await
 }
 
 pub async fn fetch_network_height() -> Result<u64> {
-    tokio::task::spawn_blocking(|| get_network_height()).await?
+    spawn_blocking_in_span(get_network_height).await
 }
 
 pub fn protocol_label(protocol: DataProtocol) -> &'static str {
@@ -707,16 +964,16 @@ pub fn is_empty_block_error(err: &anyhow::Error) -> bool {
     msg.contains("no ao message id found")
 }
 
-fn is_rate_limit_error(err: &anyhow::Error) -> bool {
+pub(crate) fn is_rate_limit_error(err: &anyhow::Error) -> bool {
     err.to_string().contains("http status: 429")
 }
 
-fn is_timeout_error(err: &anyhow::Error) -> bool {
+pub(crate) fn is_timeout_error(err: &anyhow::Error) -> bool {
     let msg = err.to_string().to_ascii_lowercase();
     msg.contains("timeout") || msg.contains("timed out")
 }
 
-fn is_retryable_http_error(err: &anyhow::Error) -> bool {
+pub(crate) fn is_retryable_http_error(err: &anyhow::Error) -> bool {
     let msg = err.to_string();
     let Some(status_part) = msg.split("http status: ").nth(1) else {
         return false;
@@ -728,11 +985,11 @@ fn is_retryable_http_error(err: &anyhow::Error) -> bool {
     (500..600).contains(&status)
 }
 
-fn is_not_found_error(err: &anyhow::Error) -> bool {
+pub(crate) fn is_not_found_error(err: &anyhow::Error) -> bool {
     err.to_string().contains("http status: 404")
 }
 
-async fn ingest_token_query(
+pub(crate) async fn ingest_token_query(
     clickhouse: &Clickhouse,
     token: TokenConfig,
     query: AoTokenQuery,
@@ -742,7 +999,13 @@ async fn ingest_token_query(
     let mut cursor = None;
     let mut total = 0usize;
     loop {
-        let page = fetch_ao_token_page(token.process_id, query, height, cursor.clone()).await?;
+        let page = fetch_ao_token_page(token.process_id, query, height, cursor.clone())
+            .instrument(info_span!(
+                "token_page",
+                source,
+                cursor = cursor.as_deref().unwrap_or_default()
+            ))
+            .await?;
         let ts = Utc::now();
         let mut message_rows = Vec::with_capacity(page.mappings.len());
         let mut tag_rows = Vec::new();
@@ -836,3 +1099,49 @@ async fn run_mainnet_explorer_tail(clickhouse: Clickhouse) -> Result<()> {
         clickhouse.insert_mainnet_explorer_rows(&rows).await?;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use common::{
+        amount::{AR_DECIMALS, Amount, LST_DECIMALS},
+        ids::ArAddress,
+    };
+    use std::collections::BTreeMap;
+
+    use crate::indexer::cycle_totals;
+
+    #[test]
+    fn cycle_totals_test() {
+        let wallet = |w: &str| ArAddress::parse(&w.repeat(43)).unwrap();
+        let lst = |raw: u128| Amount::new(raw, LST_DECIMALS);
+        let mut stakes = BTreeMap::new();
+        stakes.insert(
+            ("flp-a".to_string(), wallet("a")),
+            (lst(1_000_000_000_000_000_001), Amount::new(5, AR_DECIMALS)),
+        );
+        // a second EOA row of the same wallet replaces the first
+        stakes.insert(
+            ("flp-a".to_string(), wallet("a")),
+            (lst(2_000_000_000_000_000_001), Amount::new(7, AR_DECIMALS)),
+        );
+        stakes.insert(
+            ("flp-a".to_string(), wallet("b")),
+            (lst(1), Amount::zero(AR_DECIMALS)),
+        );
+        stakes.insert(
+            ("flp-b".to_string(), wallet("b")),
+            (Amount::new(3, 6), Amount::new(1, AR_DECIMALS)),
+        );
+
+        let rows = cycle_totals(Utc::now(), "usds", &stakes).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].project, "flp-a");
+        assert_eq!(rows[0].amount, 2_000_000_000_000_000_002);
+        assert_eq!(rows[0].ar_amount, 7);
+        assert_eq!(rows[0].delegators, 2);
+        // rescaled to the column's 18 decimals
+        assert_eq!(rows[1].amount, 3_000_000_000_000);
+        assert_eq!(rows[1].delegators, 1);
+    }
+}
@@ -1,7 +1,9 @@
 pub mod backfill;
+pub mod balances;
 pub mod clickhouse;
 pub mod config;
 pub mod indexer;
+pub mod repair;
 
 pub use crate::clickhouse::Clickhouse;
 pub use crate::config::Config;
@@ -1,16 +1,11 @@
-mod backfill;
-mod clickhouse;
-mod config;
-mod indexer;
-
 use anyhow::Result;
-use config::Config;
-use indexer::Indexer;
+use indexer::{Clickhouse, Config, Indexer};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    common::telemetry::init_tracing();
     let config = Config::load();
-    let clickhouse = clickhouse::Clickhouse::new(&config);
+    let clickhouse = Clickhouse::new(&config);
     let indexer = Indexer::new(config, clickhouse);
     indexer.run().await
 }
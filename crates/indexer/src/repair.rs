@@ -0,0 +1,372 @@
+use anyhow::Result;
+use chrono::Utc;
+use common::{
+    ao_token::AoTokenQuery,
+    constants::{DATA_PROTOCOL_A_START, DATA_PROTOCOL_B_START},
+    mainnet::DataProtocol,
+};
+use tokio::time::{Duration, sleep};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::{
+    clickhouse::{AoTokenBlockStateRow, Clickhouse},
+    config::IndexerConfig,
+    indexer::{
+        TokenConfig, fetch_mainnet_page, ingest_token_query, is_empty_block_error,
+        is_not_found_error, is_rate_limit_error, is_retryable_http_error, is_timeout_error,
+        protocol_label, record_mainnet_height, store_mainnet_page,
+    },
+};
+
+const MAX_ATTEMPTS: u32 = 5;
+
+/// an index whose heights can be checked and repaired
+#[derive(Clone, Copy)]
+pub enum RepairTarget {
+    Mainnet(DataProtocol),
+    Token(TokenConfig),
+}
+
+impl RepairTarget {
+    /// targets matching the indexers enabled in atlas.toml
+    pub fn enabled(indexers: &IndexerConfig) -> Vec<Self> {
+        let mut targets = Vec::new();
+        if indexers.mainnet {
+            targets.push(Self::Mainnet(DataProtocol::A));
+            targets.push(Self::Mainnet(DataProtocol::B));
+        }
+        if indexers.ao {
+            targets.extend(TokenConfig::from_label("ao").map(Self::Token));
+        }
+        if indexers.pi {
+            targets.extend(TokenConfig::from_label("pi").map(Self::Token));
+        }
+        targets
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Mainnet(DataProtocol::A) => "mainnet-a",
+            Self::Mainnet(DataProtocol::B) => "mainnet-b",
+            Self::Token(token) => token.label,
+        }
+    }
+
+    pub fn start_height(&self) -> u32 {
+        match self {
+            Self::Mainnet(DataProtocol::A) => DATA_PROTOCOL_A_START,
+            Self::Mainnet(DataProtocol::B) => DATA_PROTOCOL_B_START,
+            Self::Token(token) => token.start_height,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GapReport {
+    /// heights with nothing stored for them
+    pub missing: Vec<u32>,
+    /// heights with some messages stored but never marked complete
+    pub partial: Vec<u32>,
+}
+
+impl GapReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.partial.is_empty()
+    }
+
+    pub fn heights(&self) -> Vec<u32> {
+        let mut heights: Vec<u32> = self.missing.iter().chain(&self.partial).copied().collect();
+        heights.sort_unstable();
+        heights.dedup();
+        heights
+    }
+
+    /// gap heights merged into inclusive runs of consecutive heights
+    pub fn ranges(&self) -> Vec<(u32, u32)> {
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for height in self.heights() {
+            match ranges.last_mut() {
+                Some((_, end)) if end.checked_add(1) == Some(height) => *end = height,
+                _ => ranges.push((height, height)),
+            }
+        }
+        ranges
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RepairSummary {
+    pub heights: usize,
+    pub msgs: usize,
+}
+
+/// highest height the forward worker has fully indexed, the repair never
+/// goes past it so it doesn't race the live worker
+pub async fn last_complete_height(
+    clickhouse: &Clickhouse,
+    target: RepairTarget,
+) -> Result<Option<u32>> {
+    match target {
+        RepairTarget::Mainnet(protocol) => {
+            let state = clickhouse
+                .fetch_mainnet_block_state(protocol_label(protocol))
+                .await?;
+            Ok(state.and_then(|state| {
+                if state.last_cursor.is_empty() {
+                    Some(state.last_complete_height)
+                } else {
+                    // the height is still being paginated
+                    state.last_complete_height.checked_sub(1)
+                }
+            }))
+        }
+        RepairTarget::Token(token) => Ok(clickhouse
+            .fetch_ao_token_block_state(token.label)
+            .await?
+            .map(|state| state.last_complete_height)),
+    }
+}
+
+/// resolves the `[from, to]` range to scan: defaults to the target start up to
+/// its last complete height, and clamps user ranges to that window
+pub async fn resolve_range(
+    clickhouse: &Clickhouse,
+    target: RepairTarget,
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Result<Option<(u32, u32)>> {
+    let Some(last_complete) = last_complete_height(clickhouse, target).await? else {
+        return Ok(None);
+    };
+    Ok(clamp_range(target.start_height(), last_complete, from, to))
+}
+
+fn clamp_range(
+    start: u32,
+    last_complete: u32,
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Option<(u32, u32)> {
+    let from = from.unwrap_or(0).max(start);
+    let to = to.unwrap_or(last_complete).min(last_complete);
+    (from <= to).then_some((from, to))
+}
+
+pub async fn find_gaps(
+    clickhouse: &Clickhouse,
+    target: RepairTarget,
+    from: u32,
+    to: u32,
+    limit: u64,
+) -> Result<GapReport> {
+    match target {
+        RepairTarget::Mainnet(protocol) => {
+            let protocol_name = protocol_label(protocol);
+            // heights indexed before completion rows existed can't be told
+            // apart from missing or partial ones, only check from the first
+            // tracked height
+            let tracked_from = clickhouse
+                .first_tracked_mainnet_height(protocol_name)
+                .await?
+                .unwrap_or(u32::MAX);
+            let missing = clickhouse
+                .fetch_mainnet_missing_heights(protocol_name, from, to, tracked_from, limit)
+                .await?;
+            let partial = clickhouse
+                .fetch_mainnet_partial_heights(protocol_name, from, to, tracked_from, limit)
+                .await?;
+            Ok(GapReport { missing, partial })
+        }
+        RepairTarget::Token(token) => {
+            let missing = clickhouse
+                .fetch_ao_token_missing_heights(token.label, from, to, limit)
+                .await?;
+            let partial = clickhouse
+                .fetch_ao_token_partial_heights(token.label, from, to, limit)
+                .await?;
+            Ok(GapReport { missing, partial })
+        }
+    }
+}
+
+/// re-indexes the given heights. rows land in ReplacingMergeTree tables keyed
+/// by msg id, so re-running a height never duplicates data
+pub async fn repair_heights(
+    clickhouse: &Clickhouse,
+    target: RepairTarget,
+    heights: &[u32],
+) -> Result<RepairSummary> {
+    let mut summary = RepairSummary::default();
+    for &height in heights {
+        let span = info_span!("repair_height", target = target.label(), height);
+        let msgs = with_retries(|| async {
+            match target {
+                RepairTarget::Mainnet(protocol) => {
+                    repair_mainnet_height(clickhouse, protocol, height).await
+                }
+                RepairTarget::Token(token) => repair_token_height(clickhouse, token, height).await,
+            }
+        })
+        .instrument(span.clone())
+        .await?;
+        span.in_scope(|| info!(msgs, "height repaired"));
+        summary.heights += 1;
+        summary.msgs += msgs;
+    }
+    Ok(summary)
+}
+
+/// one detect + repair round over the targets, repairing at most `limit`
+/// heights per target
+pub async fn run_repair_pass(
+    clickhouse: &Clickhouse,
+    targets: &[RepairTarget],
+    from: Option<u32>,
+    to: Option<u32>,
+    limit: u64,
+) -> Result<()> {
+    for &target in targets {
+        let span = info_span!("gap_repair", target = target.label());
+        async {
+            let Some((from, to)) = resolve_range(clickhouse, target, from, to).await? else {
+                info!("nothing indexed yet, skipping");
+                return Ok(());
+            };
+            let report = find_gaps(clickhouse, target, from, to, limit).await?;
+            info!(
+                from,
+                to,
+                missing = report.missing.len(),
+                partial = report.partial.len(),
+                "gap scan complete"
+            );
+            if report.is_empty() {
+                return Ok(());
+            }
+            let heights = report.heights();
+            let heights = &heights[..heights.len().min(limit as usize)];
+            let summary = repair_heights(clickhouse, target, heights).await?;
+            info!(
+                heights = summary.heights,
+                msgs = summary.msgs,
+                "gap repair complete"
+            );
+            Ok::<(), anyhow::Error>(())
+        }
+        .instrument(span)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn repair_mainnet_height(
+    clickhouse: &Clickhouse,
+    protocol: DataProtocol,
+    height: u32,
+) -> Result<usize> {
+    let protocol_name = protocol_label(protocol);
+    let mut cursor = None;
+    let mut total = 0usize;
+    loop {
+        let page = match fetch_mainnet_page(protocol, height, cursor.clone()).await {
+            Ok(page) => page,
+            Err(err) if is_empty_block_error(&err) => break,
+            Err(err) => return Err(err),
+        };
+        total += store_mainnet_page(clickhouse, protocol_name, page.mappings, Utc::now()).await?;
+        cursor = if page.has_next_page {
+            page.end_cursor.clone()
+        } else {
+            None
+        };
+        if cursor.is_none() {
+            break;
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+    record_mainnet_height(clickhouse, protocol_name, height, total as u64).await?;
+    Ok(total)
+}
+
+async fn repair_token_height(
+    clickhouse: &Clickhouse,
+    token: TokenConfig,
+    height: u32,
+) -> Result<usize> {
+    let transfers = ingest_token_query(
+        clickhouse,
+        token,
+        AoTokenQuery::Transfer,
+        height,
+        "transfer",
+    )
+    .await?;
+    let process_msgs =
+        ingest_token_query(clickhouse, token, AoTokenQuery::Process, height, "process").await?;
+    let state_row = AoTokenBlockStateRow {
+        token: token.label.to_string(),
+        last_complete_height: height,
+        updated_at: Utc::now(),
+    };
+    clickhouse.insert_ao_token_block_state(&[state_row]).await?;
+    Ok(transfers + process_msgs)
+}
+
+async fn with_retries<F, Fut>(mut f: F) -> Result<usize>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<usize>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < MAX_ATTEMPTS && is_transient(&err) => {
+                warn!(attempt, error = ?err, "repair fetch error, retrying");
+                let delay = if is_rate_limit_error(&err) { 30 } else { 5 };
+                sleep(Duration::from_secs(delay * attempt as u64)).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn is_transient(err: &anyhow::Error) -> bool {
+    is_rate_limit_error(err)
+        || is_timeout_error(err)
+        || is_retryable_http_error(err)
+        || is_not_found_error(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repair::{GapReport, clamp_range};
+
+    #[test]
+    fn gap_report_ranges_test() {
+        let report = GapReport {
+            missing: vec![12, 10, 20],
+            partial: vec![11, 12, 14, u32::MAX],
+        };
+        assert_eq!(report.heights(), vec![10, 11, 12, 14, 20, u32::MAX]);
+        assert_eq!(
+            report.ranges(),
+            vec![(10, 12), (14, 14), (20, 20), (u32::MAX, u32::MAX)]
+        );
+        assert!(GapReport::default().ranges().is_empty());
+        assert!(!report.is_empty());
+    }
+
+    #[test]
+    fn clamp_range_test() {
+        assert_eq!(clamp_range(100, 500, None, None), Some((100, 500)));
+        assert_eq!(clamp_range(100, 500, Some(50), Some(900)), Some((100, 500)));
+        assert_eq!(
+            clamp_range(100, 500, Some(200), Some(300)),
+            Some((200, 300))
+        );
+        assert_eq!(clamp_range(100, 500, Some(600), None), None);
+        assert_eq!(clamp_range(100, 50, None, None), None);
+    }
+}
@@ -0,0 +1,10 @@
+-- columns added after the first deployments, no-ops on fresh databases
+alter table wallet_balances add column if not exists eoa String after wallet;
+alter table wallet_balances add column if not exists ar_balance String after amount;
+alter table flp_positions add column if not exists eoa String after wallet;
+alter table flp_positions add column if not exists ar_amount String after amount;
+alter table flp_positions modify column project String;
+alter table delegation_mappings add column if not exists ts DateTime64(3) default now();
+alter table ao_token_messages add column if not exists token String default 'ao';
+alter table ao_token_message_tags add column if not exists token String default 'ao';
+alter table ao_token_block_state add column if not exists token String default 'ao';
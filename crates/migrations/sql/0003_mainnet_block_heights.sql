@@ -0,0 +1,2 @@
+-- per-height completion log used by the gap repair job
+create table if not exists ao_mainnet_block_heights(protocol String, height UInt32, msg_count UInt64, updated_at DateTime64(3)) engine=ReplacingMergeTree(updated_at) order by (protocol, height);
@@ -0,0 +1,4 @@
+-- raw Set-Balances amounts, lets the server rebuild oracle payloads from the index
+alter table wallet_balances add column if not exists raw_amount String after amount;
+-- latest Own-Minting-Report per FLP
+create table if not exists flp_minting_reports(ts DateTime64(3), project String, report_id String, payload String) engine=ReplacingMergeTree order by (project, report_id);
@@ -0,0 +1,2 @@
+-- server API keys, managed outside of atlas.toml. keys are stored as the hex sha256 of the secret
+create table if not exists api_keys(key_hash String, name String, rpm UInt32, admin Bool, enabled Bool, updated_at DateTime64(3)) engine=ReplacingMergeTree(updated_at) order by key_hash;
@@ -0,0 +1,6 @@
+-- delegated totals per (ticker, oracle cycle, FLP) so rankings read a few rows per cycle. the
+-- indexer writes each cycle's totals once, deduped per wallet like flp_positions, with exact
+-- decimals
+create table if not exists flp_cycle_project_totals(ts DateTime64(3), ticker String, project String, amount Decimal(38, 18), ar_amount Decimal(38, 12), delegators UInt64) engine=ReplacingMergeTree order by (ticker, ts, project);
+-- cycles stored before the indexer wrote totals
+insert into flp_cycle_project_totals select ts, ticker, project, sum(toDecimal128OrZero(amount, 18)) as amount, sum(toDecimal128OrZero(ar_amount, 12)) as ar_amount, uniqExact(wallet) as delegators from flp_positions final group by ts, ticker, project
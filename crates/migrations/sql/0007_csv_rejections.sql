@@ -0,0 +1,3 @@
+-- rows of oracle Set-Balances and Delegation-Mappings CSVs the indexer skipped as malformed, per source tx.
+-- `source` is the oracle ticker or `delegation_mappings`
+create table if not exists csv_rejections(ts DateTime64(3), source String, tx_id String, line UInt64, raw String, reason String) engine=ReplacingMergeTree order by (source, tx_id, line);
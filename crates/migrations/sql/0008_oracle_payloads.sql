@@ -0,0 +1,3 @@
+-- parsed Set-Balances payload of each indexed oracle update, in CSV order. wallet_balances is
+-- keyed per wallet and collapses a wallet's EOA rows, so the payload can't be rebuilt from it
+create table if not exists oracle_payloads(ts DateTime64(3), ticker String, tx_id String, payload String) engine=ReplacingMergeTree order by (ticker, tx_id);
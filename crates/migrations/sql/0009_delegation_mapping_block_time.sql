@@ -0,0 +1,3 @@
+-- block time of each delegation mapping tx, `ts` is when the indexer stored it. rows stored
+-- before this keep 0 until `backfill delegations` passes over their tx again
+alter table delegation_mappings add column if not exists block_timestamp UInt64 after height;
@@ -0,0 +1,233 @@
+/// versioned ClickHouse schema shared by the indexer and the server.
+///
+/// migrations live in `sql/NNNN_name.sql` and are embedded at build time.
+/// applied versions are recorded in `schema_migrations` with the sha256 of
+/// their SQL, so editing a migration after it shipped is caught instead of
+/// silently diverging between deployments. add a new file (and entry in
+/// `MIGRATIONS`) for any schema change, never edit an applied one.
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migration {
+    ($version:expr, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../sql/", $name, ".sql")),
+        }
+    };
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "0001_initial_schema"),
+    migration!(2, "0002_backfill_columns"),
+    migration!(3, "0003_mainnet_block_heights"),
+    migration!(4, "0004_gateway_payloads"),
+    migration!(5, "0005_api_keys"),
+    migration!(6, "0006_flp_cycle_totals"),
+    migration!(7, "0007_csv_rejections"),
+    migration!(8, "0008_oracle_payloads"),
+    migration!(9, "0009_delegation_mapping_block_time"),
+];
+
+const CREATE_MIGRATIONS_TABLE: &str = "create table if not exists schema_migrations(version UInt32, name String, checksum String, applied_at DateTime64(3)) engine=ReplacingMergeTree order by version";
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        format!("{:x}", Sha256::digest(self.sql.as_bytes()))
+    }
+
+    /// statements of the migration file, `;` separated with `--` comment lines dropped
+    pub fn statements(&self) -> Vec<String> {
+        let sql: String = self
+            .sql
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("--"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        sql.split(';')
+            .map(str::trim)
+            .filter(|stmt| !stmt.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Row, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub applied_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: u32,
+    pub name: &'static str,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Row, Deserialize)]
+struct CountRow {
+    cnt: u64,
+}
+
+#[derive(Clone)]
+pub struct Migrator {
+    admin: Client,
+    client: Client,
+    database: String,
+}
+
+impl Migrator {
+    /// `admin` is a client without a database set, used to create the database
+    pub fn new(admin: Client, database: &str) -> Self {
+        let client = admin.clone().with_database(database);
+        Migrator {
+            admin,
+            client,
+            database: database.to_string(),
+        }
+    }
+
+    /// applies pending migrations in order and returns their versions
+    pub async fn run(&self) -> Result<Vec<u32>> {
+        let create_db = format!("create database if not exists {}", self.database);
+        self.admin.query(&create_db).execute().await?;
+        self.client.query(CREATE_MIGRATIONS_TABLE).execute().await?;
+        let pending = self.pending().await?;
+        let mut applied = Vec::with_capacity(pending.len());
+        for migration in pending {
+            for stmt in migration.statements() {
+                self.client
+                    .query(&stmt)
+                    .execute()
+                    .await
+                    .map_err(|err| anyhow!("migration {} failed: {err}", migration.name))?;
+            }
+            let row = AppliedMigration {
+                version: migration.version,
+                name: migration.name.to_string(),
+                checksum: migration.checksum(),
+                applied_at: Utc::now(),
+            };
+            let mut insert = self.client.insert("schema_migrations")?;
+            insert.write(&row).await?;
+            insert.end().await?;
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "migration applied"
+            );
+            applied.push(migration.version);
+        }
+        Ok(applied)
+    }
+
+    /// migrations not applied yet, errors if an applied one was modified since
+    pub async fn pending(&self) -> Result<Vec<&'static Migration>> {
+        let applied = self.applied().await?;
+        for row in &applied {
+            match MIGRATIONS.iter().find(|m| m.version == row.version) {
+                Some(migration) if migration.checksum() != row.checksum => {
+                    return Err(anyhow!(
+                        "migration {} was modified after being applied (checksum {} != {})",
+                        migration.name,
+                        migration.checksum(),
+                        row.checksum
+                    ));
+                }
+                Some(_) => {}
+                None => warn!(
+                    version = row.version,
+                    name = %row.name,
+                    "applied migration unknown to this build"
+                ),
+            }
+        }
+        Ok(MIGRATIONS
+            .iter()
+            .filter(|m| !applied.iter().any(|row| row.version == m.version))
+            .collect())
+    }
+
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>> {
+        let applied = self.applied().await?;
+        Ok(MIGRATIONS
+            .iter()
+            .map(|m| MigrationStatus {
+                version: m.version,
+                name: m.name,
+                applied_at: applied
+                    .iter()
+                    .find(|row| row.version == m.version)
+                    .map(|row| row.applied_at),
+            })
+            .collect())
+    }
+
+    async fn applied(&self) -> Result<Vec<AppliedMigration>> {
+        // the database itself may not exist yet, check through the admin client
+        let exists = self
+            .admin
+            .query(
+                "select count() as cnt from system.tables \
+                 where database = ? and name = 'schema_migrations'",
+            )
+            .bind(&self.database)
+            .fetch_one::<CountRow>()
+            .await?;
+        if exists.cnt == 0 {
+            return Ok(Vec::new());
+        }
+        let rows = self
+            .client
+            .query(
+                "select version, argMax(m.name, m.applied_at) as name, \
+                 argMax(m.checksum, m.applied_at) as checksum, max(m.applied_at) as applied_at \
+                 from schema_migrations as m \
+                 group by version \
+                 order by version",
+            )
+            .fetch_all::<AppliedMigration>()
+            .await?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MIGRATIONS;
+
+    #[test]
+    fn migrations_are_ordered_test() {
+        for (idx, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, idx + 1);
+            assert!(
+                migration
+                    .name
+                    .starts_with(&format!("{:04}_", migration.version))
+            );
+            assert!(!migration.statements().is_empty());
+        }
+    }
+
+    #[test]
+    fn statements_skip_comments_test() {
+        let stmts = MIGRATIONS[1].statements();
+        assert!(stmts.iter().all(|stmt| stmt.starts_with("alter table")));
+        assert_eq!(stmts.len(), 9);
+    }
+}
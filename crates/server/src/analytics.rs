@@ -0,0 +1,552 @@
+use crate::{
+    errors::ServerError,
+    indexer::{AtlasIndexerClient, DelegationMappingTx, DelegationMappingTxRef},
+};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use common::projects::Project;
+use flp::types::MAX_FACTOR;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use utoipa::ToSchema;
+
+/// flow matrix bucket for the factor not delegated to any FLP
+pub const UNDELEGATED: &str = "undelegated";
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum CohortPeriod {
+    Week,
+    #[default]
+    Month,
+}
+
+impl CohortPeriod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    /// first day of the period holding `day`, weeks start on monday
+    fn start(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+            Self::Month => day.with_day(1).unwrap_or(day),
+        }
+    }
+
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => start + Duration::days(7),
+            Self::Month => start + Months::new(1),
+        }
+    }
+}
+
+/// delegator churn and retention of one FLP, from its delegation mapping history
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ProjectAnalytics {
+    pub project: String,
+    pub name: Option<String>,
+    /// wallets delegating a non-zero factor to the FLP right now
+    pub active_delegators: u64,
+    pub lifetime: DelegationLifetime,
+    /// newest cohort last
+    pub cohorts: Vec<RetentionCohort>,
+    /// oldest day first
+    pub churn: Vec<ChurnDay>,
+}
+
+/// how long delegations to the FLP last. a delegation starts when a wallet's
+/// factor to the FLP turns non-zero and ends when it drops back to zero or
+/// the wallet is missing from the next mapping tx
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct DelegationLifetime {
+    pub ended: u64,
+    pub avg_ended_days: f64,
+    pub active: u64,
+    /// age of the still running delegations
+    pub avg_active_days: f64,
+}
+
+/// wallets grouped by the period of their first delegation to the FLP
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct RetentionCohort {
+    pub start: NaiveDate,
+    pub delegators: u64,
+    /// wallets of the cohort still delegating at the end of each period,
+    /// starting with the cohort's own one
+    pub retained: Vec<u64>,
+    /// `retained` over the cohort size, 0 to 1
+    pub retention: Vec<f64>,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct ChurnDay {
+    pub day: NaiveDate,
+    /// delegations started, returning wallets included
+    pub joined: u64,
+    pub left: u64,
+    pub net: i64,
+}
+
+/// delegation factor moved between FLPs by delegation mapping changes, not
+/// weighted by the wallets' token amounts
+#[derive(Serialize, ToSchema, Debug)]
+pub struct FlowMatrix {
+    pub from_day: NaiveDate,
+    pub to_day: NaiveDate,
+    /// FLPs seen in the flows, plus `undelegated`
+    pub projects: Vec<String>,
+    pub flows: Vec<StakeFlow>,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct StakeFlow {
+    pub from: String,
+    pub to: String,
+    /// moved factor in wallet units, a wallet moving its whole delegation
+    /// (factor 10000) counts as 1 whatever its balance
+    pub wallet_units: f64,
+    /// wallets that moved stake along this edge
+    pub wallets: u64,
+}
+
+pub async fn project_analytics(
+    indexer: &AtlasIndexerClient,
+    project: &str,
+    period: CohortPeriod,
+    cohorts: usize,
+    days: u32,
+) -> Result<ProjectAnalytics, ServerError> {
+    let (txs, refs) = tokio::join!(
+        indexer.project_delegation_mapping_txs(project),
+        indexer.project_delegation_mapping_tx_refs(project),
+    );
+    let spans = delegation_spans(&txs?, &refs?, project);
+    let now = Utc::now();
+    let name = Project::get_all()
+        .into_iter()
+        .find(|p| p.pid == project)
+        .map(|p| p.name);
+    let mut cohorts_out = retention_cohorts(&spans, period, now);
+    let skip = cohorts_out.len().saturating_sub(cohorts);
+    cohorts_out.drain(..skip);
+    Ok(ProjectAnalytics {
+        project: project.to_string(),
+        name,
+        active_delegators: spans.iter().filter(|span| span.end.is_none()).count() as u64,
+        lifetime: lifetime(&spans, now),
+        cohorts: cohorts_out,
+        churn: churn_days(&spans, now.date_naive(), days),
+    })
+}
+
+pub async fn flow_matrix(
+    indexer: &AtlasIndexerClient,
+    from_day: NaiveDate,
+    to_day: NaiveDate,
+) -> Result<FlowMatrix, ServerError> {
+    let from = day_start(from_day);
+    let to = day_start(to_day + Duration::days(1));
+    let txs = indexer
+        .delegation_mapping_txs_between(from.timestamp_millis(), to.timestamp_millis())
+        .await?;
+    let flows = stake_flows(&txs, from, to, Project::is_flp_project);
+    let mut projects: BTreeSet<String> = BTreeSet::new();
+    for flow in &flows {
+        projects.insert(flow.from.clone());
+        projects.insert(flow.to.clone());
+    }
+    Ok(FlowMatrix {
+        from_day,
+        to_day,
+        projects: projects.into_iter().collect(),
+        flows,
+    })
+}
+
+fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// a run of mapping txs where the wallet kept a non-zero factor on the FLP
+#[derive(Debug, PartialEq)]
+struct DelegationSpan {
+    wallet: String,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl DelegationSpan {
+    fn active_at(&self, at: DateTime<Utc>) -> bool {
+        self.start <= at && self.end.is_none_or(|end| end > at)
+    }
+}
+
+/// `txs` grouped per wallet and oldest first, as the indexer returns them.
+/// `refs` lists every mapping tx oldest first: a wallet missing from the one
+/// after its latest tx no longer delegates
+fn delegation_spans(
+    txs: &[DelegationMappingTx],
+    refs: &[DelegationMappingTxRef],
+    project: &str,
+) -> Vec<DelegationSpan> {
+    // the first mapping tx after `tx`, unless it's the wallet's own `next`
+    let absent_at = |tx: &DelegationMappingTx, next: Option<&DelegationMappingTx>| {
+        let idx = refs
+            .partition_point(|r| (r.height, r.tx_id.as_str()) <= (tx.height, tx.tx_id.as_str()));
+        refs.get(idx)
+            .filter(|r| next.is_none_or(|next| (next.height, &next.tx_id) != (r.height, &r.tx_id)))
+            .map(|r| r.ts)
+    };
+    let mut spans = Vec::new();
+    let mut open: Option<DelegationSpan> = None;
+    for (idx, tx) in txs.iter().enumerate() {
+        let active = tx
+            .preferences
+            .iter()
+            .any(|(to, factor)| to == project && *factor > 0);
+        match open.take() {
+            Some(mut span) if !active => {
+                span.end = Some(tx.ts);
+                spans.push(span);
+            }
+            Some(span) => open = Some(span),
+            None if active => {
+                open = Some(DelegationSpan {
+                    wallet: tx.wallet.clone(),
+                    start: tx.ts,
+                    end: None,
+                });
+            }
+            None => {}
+        }
+        let next = txs.get(idx + 1).filter(|next| next.wallet == tx.wallet);
+        if let Some(mut span) = open.take() {
+            match absent_at(tx, next) {
+                Some(end) => {
+                    span.end = Some(end);
+                    spans.push(span);
+                }
+                None if next.is_none() => spans.push(span),
+                None => open = Some(span),
+            }
+        }
+    }
+    spans
+}
+
+fn lifetime(spans: &[DelegationSpan], now: DateTime<Utc>) -> DelegationLifetime {
+    let days = |from: DateTime<Utc>, to: DateTime<Utc>| (to - from).num_seconds() as f64 / 86_400.0;
+    let ended: Vec<f64> = spans
+        .iter()
+        .filter_map(|span| span.end.map(|end| days(span.start, end)))
+        .collect();
+    let active: Vec<f64> = spans
+        .iter()
+        .filter(|span| span.end.is_none())
+        .map(|span| days(span.start, now))
+        .collect();
+    DelegationLifetime {
+        ended: ended.len() as u64,
+        avg_ended_days: mean(&ended),
+        active: active.len() as u64,
+        avg_active_days: mean(&active),
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn retention_cohorts(
+    spans: &[DelegationSpan],
+    period: CohortPeriod,
+    now: DateTime<Utc>,
+) -> Vec<RetentionCohort> {
+    let mut by_wallet: HashMap<&str, Vec<&DelegationSpan>> = HashMap::new();
+    for span in spans {
+        by_wallet.entry(&span.wallet).or_default().push(span);
+    }
+    let mut cohorts: BTreeMap<NaiveDate, Vec<Vec<&DelegationSpan>>> = BTreeMap::new();
+    for wallet_spans in by_wallet.into_values() {
+        let Some(first) = wallet_spans.iter().map(|span| span.start).min() else {
+            continue;
+        };
+        let start = period.start(first.date_naive());
+        cohorts.entry(start).or_default().push(wallet_spans);
+    }
+    cohorts
+        .into_iter()
+        .map(|(start, wallets)| {
+            let mut retained = Vec::new();
+            let mut period_start = start;
+            while day_start(period_start) <= now {
+                let end = day_start(period.next(period_start)).min(now);
+                let count = wallets
+                    .iter()
+                    .filter(|spans| spans.iter().any(|span| span.active_at(end)))
+                    .count() as u64;
+                retained.push(count);
+                period_start = period.next(period_start);
+            }
+            let size = wallets.len() as u64;
+            RetentionCohort {
+                start,
+                delegators: size,
+                retention: retained
+                    .iter()
+                    .map(|count| *count as f64 / size as f64)
+                    .collect(),
+                retained,
+            }
+        })
+        .collect()
+}
+
+/// delegations started and ended per day over the `days` days up to `today`
+fn churn_days(spans: &[DelegationSpan], today: NaiveDate, days: u32) -> Vec<ChurnDay> {
+    let first = today - Duration::days(days.saturating_sub(1) as i64);
+    let mut counts: BTreeMap<NaiveDate, (u64, u64)> = first
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| (day, (0, 0)))
+        .collect();
+    for span in spans {
+        if let Some(entry) = counts.get_mut(&span.start.date_naive()) {
+            entry.0 += 1;
+        }
+        if let Some(entry) = span.end.and_then(|end| counts.get_mut(&end.date_naive())) {
+            entry.1 += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(day, (joined, left))| ChurnDay {
+            day,
+            joined,
+            left,
+            net: joined as i64 - left as i64,
+        })
+        .collect()
+}
+
+/// stake moved by the mapping txs in `[from, to)`. every tx diffs the wallet's
+/// previous preference set, each FLP losing factor sends it to the ones
+/// gaining factor in proportion to their gain
+fn stake_flows(
+    txs: &[DelegationMappingTx],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    is_project: impl Fn(&str) -> bool,
+) -> Vec<StakeFlow> {
+    let mut stake: BTreeMap<(String, String), f64> = BTreeMap::new();
+    let mut wallets: BTreeMap<(String, String), BTreeSet<&str>> = BTreeMap::new();
+    let mut previous: BTreeMap<String, f64> = BTreeMap::new();
+    for (idx, tx) in txs.iter().enumerate() {
+        let first_of_wallet = idx == 0 || txs[idx - 1].wallet != tx.wallet;
+        if first_of_wallet {
+            previous = BTreeMap::new();
+        }
+        let current = shares(&tx.preferences, &is_project);
+        if tx.ts >= from && tx.ts < to {
+            for (key, moved) in diff_flows(&previous, &current) {
+                *stake.entry(key.clone()).or_insert(0.0) += moved;
+                wallets.entry(key).or_default().insert(&tx.wallet);
+            }
+        }
+        previous = current;
+    }
+    stake
+        .into_iter()
+        .map(|(key, moved)| StakeFlow {
+            wallets: wallets.get(&key).map_or(0, |set| set.len() as u64),
+            from: key.0,
+            to: key.1,
+            wallet_units: moved,
+        })
+        .collect()
+}
+
+/// delegated share per FLP, the remainder under `undelegated`
+fn shares(
+    preferences: &[(String, u32)],
+    is_project: impl Fn(&str) -> bool,
+) -> BTreeMap<String, f64> {
+    let mut out: BTreeMap<String, f64> = BTreeMap::new();
+    for (to, factor) in preferences {
+        if *factor > 0 && is_project(to) {
+            *out.entry(to.clone()).or_insert(0.0) += *factor as f64 / MAX_FACTOR as f64;
+        }
+    }
+    let delegated: f64 = out.values().sum();
+    if delegated < 1.0 {
+        out.insert(UNDELEGATED.to_string(), 1.0 - delegated);
+    }
+    out
+}
+
+fn diff_flows(
+    previous: &BTreeMap<String, f64>,
+    current: &BTreeMap<String, f64>,
+) -> Vec<((String, String), f64)> {
+    let initial = BTreeMap::from([(UNDELEGATED.to_string(), 1.0)]);
+    let previous = if previous.is_empty() {
+        &initial
+    } else {
+        previous
+    };
+    let keys: BTreeSet<&String> = previous.keys().chain(current.keys()).collect();
+    let mut losses = Vec::new();
+    let mut gains = Vec::new();
+    for key in keys {
+        let delta =
+            current.get(key).copied().unwrap_or(0.0) - previous.get(key).copied().unwrap_or(0.0);
+        if delta < -f64::EPSILON {
+            losses.push((key, -delta));
+        } else if delta > f64::EPSILON {
+            gains.push((key, delta));
+        }
+    }
+    let total_gain: f64 = gains.iter().map(|(_, gain)| gain).sum();
+    let mut out = Vec::new();
+    for (from, loss) in &losses {
+        for (to, gain) in &gains {
+            out.push((((*from).clone(), (*to).clone()), loss * gain / total_gain));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analytics::{
+            CohortPeriod, DelegationLifetime, UNDELEGATED, churn_days, delegation_spans, lifetime,
+            retention_cohorts, stake_flows,
+        },
+        indexer::{DelegationMappingTx, DelegationMappingTxRef},
+    };
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+
+    fn tx(wallet: &str, ts: DateTime<Utc>, preferences: &[(&str, u32)]) -> DelegationMappingTx {
+        // one mapping tx per block time
+        let height = (ts.timestamp() / 120) as u32;
+        DelegationMappingTx {
+            ts,
+            height,
+            tx_id: format!("tx-{height}"),
+            wallet: wallet.to_string(),
+            preferences: preferences
+                .iter()
+                .map(|(to, factor)| (to.to_string(), *factor))
+                .collect(),
+        }
+    }
+
+    fn refs(txs: &[DelegationMappingTx]) -> Vec<DelegationMappingTxRef> {
+        let mut refs: Vec<DelegationMappingTxRef> = txs
+            .iter()
+            .map(|tx| DelegationMappingTxRef {
+                height: tx.height,
+                tx_id: tx.tx_id.clone(),
+                ts: tx.ts,
+            })
+            .collect();
+        refs.sort_by_key(|r| r.height);
+        refs.dedup_by_key(|r| r.height);
+        refs
+    }
+
+    #[test]
+    fn churn_and_retention_test() {
+        let jan = Utc.with_ymd_and_hms(2025, 1, 10, 12, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2025, 3, 15, 0, 0, 0).unwrap();
+        let txs = vec![
+            // leaves in february
+            tx("a", jan, &[("flp", 10000)]),
+            tx("a", jan + Duration::days(2), &[("flp", 10000)]),
+            tx("a", jan + Duration::days(30), &[("other", 10000)]),
+            // stays
+            tx(
+                "b",
+                jan + Duration::days(2),
+                &[("flp", 5000), ("other", 5000)],
+            ),
+            tx("b", jan + Duration::days(30), &[("flp", 5000)]),
+            tx("b", jan + Duration::days(40), &[("flp", 2000)]),
+            tx("b", now - Duration::days(1), &[("flp", 2000)]),
+            // joins in march
+            tx("c", now - Duration::days(1), &[("flp", 10000)]),
+            // missing from the february tx, left then
+            tx("d", jan, &[("flp", 10000)]),
+            tx("d", jan + Duration::days(2), &[("flp", 10000)]),
+        ];
+        let spans = delegation_spans(&txs, &refs(&txs), "flp");
+        assert_eq!(spans.len(), 4);
+        assert_eq!(spans[3].end, Some(jan + Duration::days(30)));
+
+        assert_eq!(
+            lifetime(&spans, now),
+            DelegationLifetime {
+                ended: 2,
+                avg_ended_days: 30.0,
+                active: 2,
+                // 61.5 days for b, 1 for c
+                avg_active_days: 31.25,
+            }
+        );
+
+        let cohorts = retention_cohorts(&spans, CohortPeriod::Month, now);
+        let starts: Vec<NaiveDate> = cohorts.iter().map(|c| c.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+            ]
+        );
+        assert_eq!(cohorts[0].retained, vec![3, 1, 1]);
+        assert_eq!(cohorts[0].retention[0], 1.0);
+        assert_eq!(cohorts[1].retained, vec![1]);
+
+        let churn = churn_days(&spans, now.date_naive(), 3);
+        assert_eq!(churn.len(), 3);
+        assert_eq!((churn[1].joined, churn[1].left, churn[1].net), (1, 0, 1));
+    }
+
+    #[test]
+    fn stake_flows_test() {
+        let from = Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap();
+        let to = from + Duration::days(28);
+        let txs = vec![
+            // before the window, only sets the starting state
+            tx("a", from - Duration::days(5), &[("x", 10000)]),
+            tx("a", from + Duration::days(1), &[("y", 5000), ("z", 5000)]),
+            tx(
+                "b",
+                from + Duration::days(2),
+                &[("x", 2500), ("self", 7500)],
+            ),
+        ];
+        let flows = stake_flows(&txs, from, to, |pid| pid != "self");
+        let edges: Vec<(&str, &str, f64, u64)> = flows
+            .iter()
+            .map(|f| (f.from.as_str(), f.to.as_str(), f.wallet_units, f.wallets))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (UNDELEGATED, "x", 0.25, 1),
+                ("x", "y", 0.5, 1),
+                ("x", "z", 0.5, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    errors::ServerError,
+    indexer::{AtlasIndexerClient, IndexHeights},
+    state::AppState,
+};
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{MatchedPath, Request, State},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{Instrument, info_span, warn};
+
+const MAX_ENTRIES: usize = 2048;
+const PROGRESS_REFRESH: Duration = Duration::from_secs(15);
+
+/// indexer progress a cached route depends on, the entry is dropped as soon
+/// as it advances even if the TTL didn't expire yet
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Progress {
+    MainnetExplorer,
+    AtlasExplorer,
+    /// the `{token}` path segment picks the ao or pi indexer
+    Token,
+    /// oracle cycles, `flp_positions` only change when one is stored
+    Flp,
+    DelegationMappings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CachePolicy {
+    ttl: Duration,
+    progress: Progress,
+}
+
+/// routes worth caching: heavy aggregates over tables that only change when
+/// the matching indexer moves
+fn policy_for(route: &str) -> Option<CachePolicy> {
+    let (secs, progress) = match route {
+        "/token/{token}/top/richlist" => (300, Progress::Token),
+        "/token/{token}/top/frequency" => (300, Progress::Token),
+        "/token/{token}/info" => (30, Progress::Token),
+        "/mainnet/explorer/days" => (300, Progress::MainnetExplorer),
+        "/explorer/days" => (300, Progress::AtlasExplorer),
+        "/flp/delegators/multi" => (600, Progress::Flp),
+        "/flp/leaderboard" => (600, Progress::Flp),
+        "/flp/overlap" => (600, Progress::Flp),
+        "/flp/{project}/yield/{wallet}" => (600, Progress::Flp),
+        "/flp/{project}/analytics" => (900, Progress::DelegationMappings),
+        "/flp/flows" => (900, Progress::DelegationMappings),
+        _ => return None,
+    };
+    Some(CachePolicy {
+        ttl: Duration::from_secs(secs),
+        progress,
+    })
+}
+
+#[derive(Clone)]
+struct CachedResponse {
+    body: Bytes,
+    content_type: Option<HeaderValue>,
+    etag: String,
+    stored_at: Instant,
+    ttl: Duration,
+    version: u64,
+}
+
+impl CachedResponse {
+    fn is_fresh(&self, version: u64) -> bool {
+        self.version == version && self.stored_at.elapsed() < self.ttl
+    }
+
+    fn respond(&self, if_none_match: Option<&HeaderValue>, status: &'static str) -> Response {
+        let max_age = self.ttl.saturating_sub(self.stored_at.elapsed()).as_secs();
+        let mut headers = HeaderMap::new();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(cache_control) = HeaderValue::from_str(&format!("public, max-age={max_age}")) {
+            headers.insert(CACHE_CONTROL, cache_control);
+        }
+        headers.insert("x-cache", HeaderValue::from_static(status));
+        if if_none_match.is_some_and(|value| etag_matches(value, &self.etag)) {
+            return (StatusCode::NOT_MODIFIED, headers).into_response();
+        }
+        if let Some(content_type) = &self.content_type {
+            headers.insert(CONTENT_TYPE, content_type.clone());
+        }
+        (headers, Body::from(self.body.clone())).into_response()
+    }
+}
+
+/// in-process cache of GET responses, shared through `AppState`
+#[derive(Clone, Default)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+    heights: Arc<RwLock<IndexHeights>>,
+}
+
+impl ResponseCache {
+    /// polls the indexer progress used to invalidate entries
+    pub fn spawn_progress_refresh(&self, indexer: AtlasIndexerClient) {
+        let heights = self.heights.clone();
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval(PROGRESS_REFRESH);
+                loop {
+                    interval.tick().await;
+                    match indexer.index_heights().await {
+                        Ok(latest) => *heights.write().unwrap() = latest,
+                        Err(err) => warn!(error = ?err, "index progress refresh failed"),
+                    }
+                }
+            }
+            .instrument(info_span!("cache_progress_refresh")),
+        );
+    }
+
+    fn version(&self, progress: Progress, path: &str) -> u64 {
+        let heights = self.heights.read().unwrap();
+        match progress {
+            Progress::MainnetExplorer => heights.mainnet_explorer,
+            Progress::AtlasExplorer => heights.atlas_explorer,
+            Progress::Flp => heights.flp,
+            Progress::DelegationMappings => heights.delegation_mappings,
+            Progress::Token => match path.split('/').nth(2) {
+                Some("ao") => heights.ao,
+                Some("pi") => heights.pi,
+                _ => 0,
+            },
+        }
+    }
+
+    fn get(&self, key: &str, version: u64) -> Option<CachedResponse> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|entry| entry.is_fresh(version))
+            .cloned()
+    }
+
+    fn insert(&self, key: String, entry: CachedResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, cached| cached.stored_at.elapsed() < cached.ttl);
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(key, entry);
+    }
+}
+
+/// route layer serving cached bodies for the routes of `policy_for`, with
+/// `ETag`/`If-None-Match` revalidation and `Cache-Control` max-age
+pub async fn cache_responses(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let policy = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| policy_for(route.as_str()));
+    let Some(policy) = policy.filter(|_| req.method() == Method::GET) else {
+        return next.run(req).await;
+    };
+    let cache = &state.cache;
+    let version = cache.version(policy.progress, req.uri().path());
+    let key = req.uri().to_string();
+    let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+    if let Some(hit) = cache.get(&key, version) {
+        return hit.respond(if_none_match.as_ref(), "hit");
+    }
+
+    let res = next.run(req).await;
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+    let (parts, body) = res.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => return ServerError::from(anyhow::Error::new(err)).into_response(),
+    };
+    let entry = CachedResponse {
+        etag: etag_of(&body),
+        content_type: parts.headers.get(CONTENT_TYPE).cloned(),
+        body,
+        stored_at: Instant::now(),
+        ttl: policy.ttl,
+        version,
+    };
+    let res = entry.respond(if_none_match.as_ref(), "miss");
+    cache.insert(key, entry);
+    res
+}
+
+fn etag_of(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("\"{hex}\"")
+}
+
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    value
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::{etag_matches, etag_of, policy_for},
+        router::api_routes,
+    };
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn cached_routes_exist_test() {
+        let routes: Vec<&str> = api_routes().into_iter().map(|(path, _)| path).collect();
+        for route in [
+            "/token/{token}/top/richlist",
+            "/token/{token}/top/frequency",
+            "/token/{token}/info",
+            "/mainnet/explorer/days",
+            "/explorer/days",
+            "/flp/delegators/multi",
+            "/flp/leaderboard",
+            "/flp/overlap",
+            "/flp/{project}/yield/{wallet}",
+            "/flp/{project}/analytics",
+            "/flp/flows",
+        ] {
+            assert!(policy_for(route).is_some());
+            assert!(routes.contains(&route), "{route} is not routed");
+        }
+        assert!(policy_for("/mainnet/messages/recent").is_none());
+    }
+
+    #[test]
+    fn etag_matches_test() {
+        let etag = etag_of(b"{\"data\":[]}");
+        let header = HeaderValue::from_str(&format!("\"other\", W/{etag}")).unwrap();
+        assert!(etag_matches(&header, &etag));
+        assert!(etag_matches(&HeaderValue::from_static("*"), &etag));
+        assert!(!etag_matches(&HeaderValue::from_static("\"other\""), &etag));
+    }
+}
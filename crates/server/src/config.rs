@@ -0,0 +1,104 @@
+use common::env::{get_env_var, load_atlas_toml};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// server settings, loaded once at startup and shared through `AppState`
+pub struct ServerConfig {
+    pub port: String,
+    pub clickhouse_url: String,
+    pub clickhouse_user: String,
+    pub clickhouse_password: String,
+    pub clickhouse_database: String,
+    pub atlas: Option<AtlasConfig>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct AtlasConfig {
+    #[serde(default)]
+    indexers: AtlasIndexersConfig,
+    #[serde(rename = "PRIMARY_ARWEAVE_GATEWAY", alias = "primary_arweave_gateway")]
+    primary_arweave_gateway: Option<String>,
+    #[serde(default)]
+    pub api: ApiConfig,
+}
+
+/// `[api]` section: API keys and rate limits, limits are in cost units per
+/// minute (a route costs 1 unless weighted in `route_costs`)
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// reject requests without a valid key
+    pub require_key: bool,
+    /// budget of each anonymous client IP
+    pub ip_rpm: u32,
+    /// budget of keys that don't set their own `rpm`
+    pub key_rpm: u32,
+    /// take the client IP from `X-Forwarded-For`, only behind a trusted proxy
+    pub trust_forwarded_for: bool,
+    /// per-route cost overrides, keyed by route pattern e.g. `/token/{token}/txs`
+    pub route_costs: HashMap<String, u32>,
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    /// never echoed back by the health route
+    #[serde(skip_serializing)]
+    pub key: String,
+    pub rpm: Option<u32>,
+    /// grants access to the `/admin` routes
+    #[serde(default)]
+    pub admin: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            require_key: false,
+            ip_rpm: 120,
+            key_rpm: 1200,
+            trust_forwarded_for: false,
+            route_costs: HashMap::new(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct AtlasIndexersConfig {
+    ao: Option<bool>,
+    pi: Option<bool>,
+    flp: Option<bool>,
+    explorer: Option<bool>,
+    mainnet: Option<bool>,
+    repair: Option<bool>,
+}
+
+impl ServerConfig {
+    pub fn load() -> Self {
+        // 12 titans :D
+        let port = get_env_var("SERVER_PORT").unwrap_or_else(|_| "1212".to_string());
+        let clickhouse_url =
+            get_env_var("CLICKHOUSE_URL").unwrap_or_else(|_| "http://localhost:8123".into());
+        let clickhouse_user = get_env_var("CLICKHOUSE_USER").unwrap_or_else(|_| "default".into());
+        let clickhouse_password = get_env_var("CLICKHOUSE_PASSWORD").unwrap_or_default();
+        let clickhouse_database =
+            get_env_var("CLICKHOUSE_DATABASE").unwrap_or_else(|_| "atlas_oracles".into());
+        ServerConfig {
+            port,
+            clickhouse_url,
+            clickhouse_user,
+            clickhouse_password,
+            clickhouse_database,
+            atlas: load_atlas_toml::<AtlasConfig>(),
+        }
+    }
+
+    pub fn api(&self) -> ApiConfig {
+        self.atlas
+            .as_ref()
+            .map(|atlas| atlas.api.clone())
+            .unwrap_or_default()
+    }
+}
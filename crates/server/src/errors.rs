@@ -1,22 +1,231 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
-use serde_json::json;
+use flp::wallet::NoDelegationPreferences;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fmt;
+use utoipa::ToSchema;
 
-pub struct ServerError(anyhow::Error);
+/// HTTP facing error. every variant renders the same JSON body:
+/// `{"error": {"code": "...", "message": "...", "details": ...}}`
+#[derive(Debug)]
+pub enum ServerError {
+    /// malformed or missing request parameter (400)
+    BadRequest {
+        message: String,
+        details: Option<Value>,
+    },
+    /// unknown resource or nothing indexed for it (404)
+    NotFound {
+        message: String,
+        details: Option<Value>,
+    },
+    /// well formed input that can't be processed, e.g. an amount precision (422)
+    Unprocessable {
+        message: String,
+        details: Option<Value>,
+    },
+    /// missing or unknown API key (401)
+    Unauthorized(String),
+    /// valid API key without access to the route (403)
+    Forbidden(String),
+    /// the client spent its rate limit budget (429)
+    TooManyRequests {
+        retry_after: u64,
+    },
+    /// the upstream gateway rate limited us (429)
+    RateLimited(anyhow::Error),
+    /// the Arweave gateway failed or returned garbage (502)
+    Upstream(anyhow::Error),
+    /// clickhouse is unreachable (503)
+    Unavailable(anyhow::Error),
+    /// the gateway or clickhouse timed out (504)
+    Timeout(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+/// JSON body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// stable machine readable code, e.g. `bad_request` or `upstream_error`
+    pub code: &'static str,
+    pub message: String,
+    /// extra context such as the offending `param`
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+}
+
+/// raised by the data layer when a lookup has no rows, maps to a 404
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+impl ServerError {
+    /// 400 pointing at the offending query/path parameter
+    pub fn bad_param(param: &str, message: impl Into<String>) -> Self {
+        Self::BadRequest {
+            message: message.into(),
+            details: Some(json!({ "param": param })),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn unprocessable(message: impl Into<String>, details: Value) -> Self {
+        Self::Unprocessable {
+            message: message.into(),
+            details: Some(details),
+        }
+    }
+
+    /// classifies an error coming from a gateway call (`common`, `flp`): HTTP
+    /// and transport failures keep their meaning, anything else is reported
+    /// as a bad upstream response instead of an internal error
+    pub fn upstream(err: impl Into<anyhow::Error>) -> Self {
+        match Self::classify(err.into()) {
+            Self::Internal(err) => Self::Upstream(err),
+            other => other,
+        }
+    }
+
+    fn classify(err: anyhow::Error) -> Self {
+        if let Some(not_found) = err.downcast_ref::<NotFound>() {
+            return Self::not_found(not_found.0.clone());
+        }
+        if let Some(no_prefs) = err.downcast_ref::<NoDelegationPreferences>() {
+            return Self::not_found(no_prefs.to_string());
+        }
+        if let Some(ureq_err) = err.downcast_ref::<ureq::Error>() {
+            return match ureq_err {
+                ureq::Error::StatusCode(404) => Self::NotFound {
+                    message: "not found on the arweave gateway".to_string(),
+                    details: Some(json!({ "upstream_status": 404 })),
+                },
+                ureq::Error::StatusCode(429) => Self::RateLimited(err),
+                ureq::Error::Timeout(_) => Self::Timeout(err),
+                _ => Self::Upstream(err),
+            };
+        }
+        if let Some(ch_err) = err.downcast_ref::<clickhouse::error::Error>() {
+            return match ch_err {
+                clickhouse::error::Error::Network(_) => Self::Unavailable(err),
+                clickhouse::error::Error::TimedOut => Self::Timeout(err),
+                clickhouse::error::Error::RowNotFound => Self::not_found("not found"),
+                _ => Self::Internal(err),
+            };
+        }
+        // gateway helpers in `common` report non-2xx responses as text
+        let msg = err.to_string();
+        if msg.contains("http status: 429") {
+            return Self::RateLimited(err);
+        }
+        let lower = msg.to_ascii_lowercase();
+        if lower.contains("timed out") || lower.contains("timeout") {
+            return Self::Timeout(err);
+        }
+        Self::Internal(err)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            Self::NotFound { .. } => StatusCode::NOT_FOUND,
+            Self::Unprocessable { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::TooManyRequests { .. } | Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Upstream(_) => StatusCode::BAD_GATEWAY,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest { .. } => "bad_request",
+            Self::NotFound { .. } => "not_found",
+            Self::Unprocessable { .. } => "unprocessable",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::TooManyRequests { .. } => "rate_limited",
+            Self::RateLimited(_) => "upstream_rate_limited",
+            Self::Upstream(_) => "upstream_error",
+            Self::Unavailable(_) => "database_unavailable",
+            Self::Timeout(_) => "timeout",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadRequest { message, .. }
+            | Self::NotFound { message, .. }
+            | Self::Unprocessable { message, .. }
+            | Self::Unauthorized(message)
+            | Self::Forbidden(message) => f.write_str(message),
+            Self::TooManyRequests { retry_after } => {
+                write!(f, "rate limit exceeded, retry in {retry_after}s")
+            }
+            Self::RateLimited(err)
+            | Self::Upstream(err)
+            | Self::Unavailable(err)
+            | Self::Timeout(err)
+            | Self::Internal(err) => write!(f, "{err}"),
+        }
+    }
+}
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
-        eprintln!("server error: {:?}", self.0);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": self.0.to_string()
-            })),
-        )
-            .into_response()
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!(code = self.code(), error = ?self, "server error");
+        } else {
+            tracing::debug!(code = self.code(), error = %self, "request rejected");
+        }
+        let details = match &self {
+            Self::BadRequest { details, .. }
+            | Self::NotFound { details, .. }
+            | Self::Unprocessable { details, .. } => details.clone(),
+            Self::TooManyRequests { retry_after } => Some(json!({ "retry_after": retry_after })),
+            _ => None,
+        };
+        let body = ErrorBody {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.to_string(),
+                details,
+            },
+        };
+        let mut res = (status, Json(body)).into_response();
+        if let Self::TooManyRequests { retry_after } = self {
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        res
     }
 }
 
@@ -25,6 +234,35 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self(err.into())
+        Self::classify(err.into())
+    }
+}
+
+/// a lookup with nothing found resolves to `None` instead of an error, e.g.
+/// an empty portfolio section or a null GraphQL field
+pub fn optional<T>(res: Result<T, anyhow::Error>) -> Result<Option<T>, ServerError> {
+    match res {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => match ServerError::from(err) {
+            ServerError::NotFound { .. } => Ok(None),
+            err => Err(err),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{NotFound, ServerError, optional};
+    use flp::wallet::NoDelegationPreferences;
+
+    #[test]
+    fn optional_test() {
+        assert_eq!(optional(Ok::<_, anyhow::Error>(1)).unwrap(), Some(1));
+        let missing: anyhow::Error = NotFound("no rows".to_string()).into();
+        assert!(optional::<u32>(Err(missing)).unwrap().is_none());
+        let no_prefs: anyhow::Error = NoDelegationPreferences("wallet".to_string()).into();
+        assert!(optional::<u32>(Err(no_prefs)).unwrap().is_none());
+        let failed = optional::<u32>(Err(anyhow::anyhow!("boom"))).unwrap_err();
+        assert!(matches!(failed, ServerError::Internal(_)));
     }
 }
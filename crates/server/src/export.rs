@@ -0,0 +1,61 @@
+use crate::{errors::ServerError, indexer::AtlasIndexerClient};
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use common::export::{ExportDataset, ExportFilter, ExportFormat};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// chunks buffered between clickhouse and a slow client
+const EXPORT_BUFFER_CHUNKS: usize = 8;
+
+/// streams clickhouse's formatted response straight into the body. the first
+/// chunk is awaited so a failing query still gets a JSON error; a failure
+/// after that can only cut the body short
+pub async fn export_response(
+    indexer: &AtlasIndexerClient,
+    dataset: &ExportDataset,
+    filter: &ExportFilter,
+    format: ExportFormat,
+) -> Result<Response, ServerError> {
+    let mut cursor = indexer.export_bytes(&dataset.query(filter), format)?;
+    let first = cursor.next().await?;
+    let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(EXPORT_BUFFER_CHUNKS);
+    let name = dataset.name();
+    tokio::spawn(async move {
+        let Some(first) = first else {
+            return;
+        };
+        if tx.send(Ok(first)).await.is_err() {
+            return;
+        }
+        loop {
+            let chunk = match cursor.next().await {
+                Ok(Some(chunk)) => Ok(chunk),
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!(dataset = name, error = ?err, "export stream failed");
+                    Err(std::io::Error::other(err))
+                }
+            };
+            let failed = chunk.is_err();
+            // the client went away
+            if tx.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    let disposition = format!("attachment; filename=\"{name}.{}\"", format.extension());
+    let mut res = Body::from_stream(ReceiverStream::new(rx)).into_response();
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(res)
+}
@@ -0,0 +1,816 @@
+use crate::{
+    errors::{ServerError, optional},
+    indexer::{
+        AoTokenFrequencyInfo, AoTokenIndexingInfo, AoTokenMessage, AoTokenRichlist,
+        AtlasIndexerClient, DelegationMappingHistory, ExplorerBlock, ExplorerDayStats,
+        IdentityLink, MainnetMessage, MultiDelegator, OracleSnapshot, ProjectCycleTotal,
+        ProjectSnapshot, WalletPosition,
+    },
+    pagination::{Cursor, Page, PageParams},
+    routes::{build_tag_key_variants, day_cursor, parse_protocol, parse_token},
+};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, Json, Object, OutputType,
+    Result, Schema, SimpleObject,
+    dataloader::{DataLoader, Loader},
+};
+use chrono::{NaiveDate, Utc};
+use common::{gql::OracleStakers, projects::Project};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// queries costing more are rejected before they run. a field costs 1 plus
+/// its children, list fields cost their page size times one item
+const MAX_COMPLEXITY: usize = 5000;
+const MAX_DEPTH: usize = 10;
+/// `first` cap, lower than the REST one since pages nest
+const MAX_FIRST: i32 = 100;
+const DEFAULT_FIRST: i32 = 25;
+
+pub type AtlasSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn build_schema(indexer: AtlasIndexerClient) -> AtlasSchema {
+    let loader = DataLoader::new(WalletLoader(indexer.clone()), tokio::spawn);
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(indexer)
+        .data(loader)
+        .limit_complexity(MAX_COMPLEXITY)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+/// same envelope as the REST list endpoints, `nextCursor` goes back as `after`
+#[derive(SimpleObject)]
+#[graphql(
+    concrete(name = "MainnetMessagePage", params(MainnetMessage)),
+    concrete(name = "TokenMessagePage", params(AoTokenMessage)),
+    concrete(name = "ProjectCyclePage", params(ProjectCycleTotal)),
+    concrete(name = "MultiDelegatorPage", params(MultiDelegator)),
+    concrete(name = "ExplorerBlockPage", params(ExplorerBlock)),
+    concrete(name = "ExplorerDayPage", params(ExplorerDayStats))
+)]
+pub struct GqlPage<T: OutputType> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub limit: u64,
+}
+
+impl<T: OutputType> From<Page<T>> for GqlPage<T> {
+    fn from(page: Page<T>) -> Self {
+        GqlPage {
+            data: page.data,
+            next_cursor: page.next_cursor,
+            limit: page.limit,
+        }
+    }
+}
+
+fn page_params(first: Option<i32>, after: Option<String>) -> Result<PageParams> {
+    let limit = first_limit(first) as u64;
+    let cursor = after
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| Cursor::decode(&v))
+        .transpose()
+        .map_err(gql_error)?;
+    Ok(PageParams { limit, cursor })
+}
+
+/// GraphQL error carrying the REST error code in `extensions.code`
+fn gql_error(err: impl Into<ServerError>) -> async_graphql::Error {
+    let err = err.into();
+    if err.status().is_server_error() {
+        tracing::error!(code = err.code(), error = ?err, "graphql resolver error");
+    }
+    let code = err.code();
+    async_graphql::Error::new(err.to_string()).extend_with(|_, ext| ext.set("code", code))
+}
+
+fn indexer<'a>(ctx: &Context<'a>) -> &'a AtlasIndexerClient {
+    ctx.data_unchecked::<AtlasIndexerClient>()
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<WalletLoader> {
+    ctx.data_unchecked::<DataLoader<WalletLoader>>()
+}
+
+fn first_limit(first: Option<i32>) -> usize {
+    first.unwrap_or(DEFAULT_FIRST).clamp(1, MAX_FIRST) as usize
+}
+
+/// batches the per-wallet and per-EOA lookups of sibling nodes into one
+/// ClickHouse query per field, so nested lists don't run a query per item
+pub struct WalletLoader(AtlasIndexerClient);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct WalletIdentities(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct EoaIdentities(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct WalletPositions(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct WalletMappings(String);
+
+impl Loader<WalletIdentities> for WalletLoader {
+    type Value = Vec<IdentityLink>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[WalletIdentities],
+    ) -> Result<HashMap<WalletIdentities, Self::Value>> {
+        let wallets: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let links = self
+            .0
+            .wallets_identity_history(&wallets)
+            .await
+            .map_err(gql_error)?;
+        let mut out: HashMap<WalletIdentities, Self::Value> = HashMap::new();
+        for link in links {
+            out.entry(WalletIdentities(link.wallet.clone()))
+                .or_default()
+                .push(link);
+        }
+        Ok(out)
+    }
+}
+
+impl Loader<EoaIdentities> for WalletLoader {
+    type Value = Vec<IdentityLink>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[EoaIdentities]) -> Result<HashMap<EoaIdentities, Self::Value>> {
+        let eoas: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let links = self
+            .0
+            .eoas_identity_history(&eoas)
+            .await
+            .map_err(gql_error)?;
+        // EOAs match case-insensitively, the keys keep the requested spelling
+        Ok(keys
+            .iter()
+            .map(|key| {
+                let matching = links
+                    .iter()
+                    .filter(|link| link.eoa.eq_ignore_ascii_case(&key.0))
+                    .cloned()
+                    .collect();
+                (key.clone(), matching)
+            })
+            .collect())
+    }
+}
+
+impl Loader<WalletPositions> for WalletLoader {
+    type Value = Vec<WalletPosition>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[WalletPositions],
+    ) -> Result<HashMap<WalletPositions, Self::Value>> {
+        let wallets: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let positions = self
+            .0
+            .wallets_positions(&wallets)
+            .await
+            .map_err(gql_error)?;
+        Ok(positions
+            .into_iter()
+            .map(|(wallet, positions)| (WalletPositions(wallet), positions))
+            .collect())
+    }
+}
+
+impl Loader<WalletMappings> for WalletLoader {
+    type Value = Vec<DelegationMappingHistory>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[WalletMappings]) -> Result<HashMap<WalletMappings, Self::Value>> {
+        let wallets: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let history = self
+            .0
+            .wallets_delegation_mappings(&wallets)
+            .await
+            .map_err(gql_error)?;
+        Ok(history
+            .into_iter()
+            .map(|(wallet, history)| (WalletMappings(wallet), history))
+            .collect())
+    }
+}
+
+async fn load_list<K, T>(ctx: &Context<'_>, key: K) -> Result<Vec<T>>
+where
+    K: Send + Sync + std::hash::Hash + Eq + Clone + 'static,
+    WalletLoader: Loader<K, Value = Vec<T>, Error = async_graphql::Error>,
+{
+    Ok(loader(ctx).load_one(key).await?.unwrap_or_default())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Arweave wallet
+    async fn wallet(&self, address: String) -> Wallet {
+        Wallet { address }
+    }
+
+    /// EVM address linked to Arweave wallets through the bridge
+    async fn eoa(&self, address: String) -> Eoa {
+        Eoa { address }
+    }
+
+    /// tracked FLPs
+    async fn projects(&self) -> Vec<FlpProject> {
+        Project::get_all().into_iter().map(FlpProject).collect()
+    }
+
+    /// FLP by process id or ticker
+    async fn project(&self, id: String) -> Option<FlpProject> {
+        Project::get_all()
+            .into_iter()
+            .find(|p| p.pid == id || p.ticker.eq_ignore_ascii_case(&id))
+            .map(FlpProject)
+    }
+
+    /// LST oracle: `usds`, `dai` or `steth`
+    async fn oracle(&self, ticker: String) -> Result<Oracle> {
+        let ticker = ticker.trim().to_ascii_lowercase();
+        let metadata = OracleStakers::new(&ticker).oracle.metadata().map_err(|_| {
+            gql_error(ServerError::not_found(format!(
+                "unknown oracle ticker {ticker}"
+            )))
+        })?;
+        Ok(Oracle {
+            ticker,
+            pid: metadata.ao_pid.into(),
+            evm_address: metadata.evm_address.into(),
+        })
+    }
+
+    /// ao.TN.1 token: `ao` or `pi`
+    async fn token(&self, token: String) -> Result<Token> {
+        let token = parse_token(&token).map_err(gql_error)?;
+        Ok(Token { token })
+    }
+
+    /// mainnet (ao.N.1) messages, newest first. `tagKey` and `tagValue` go together
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn mainnet_messages(
+        &self,
+        ctx: &Context<'_>,
+        protocol: Option<String>,
+        tag_key: Option<String>,
+        tag_value: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<MainnetMessage>> {
+        let page = page_params(first, after)?;
+        let protocol = parse_protocol(protocol.as_ref()).map_err(gql_error)?;
+        let client = indexer(ctx);
+        let rows = match (tag_key, tag_value) {
+            (Some(key), Some(value)) => {
+                let tag_keys = build_tag_key_variants(protocol.as_deref(), &key);
+                if tag_keys.is_empty() {
+                    return Err(gql_error(ServerError::bad_param(
+                        "tagKey",
+                        "invalid tag key",
+                    )));
+                }
+                client
+                    .mainnet_messages_by_tag(
+                        protocol.as_deref(),
+                        &tag_keys,
+                        value.trim(),
+                        page.fetch_limit(),
+                        page.cursor.as_ref(),
+                    )
+                    .await
+            }
+            (None, None) => {
+                client
+                    .recent_mainnet_messages(
+                        protocol.as_deref(),
+                        page.fetch_limit(),
+                        page.cursor.as_ref(),
+                    )
+                    .await
+            }
+            (None, Some(_)) => {
+                return Err(gql_error(ServerError::bad_param(
+                    "tagKey",
+                    "missing tag key",
+                )));
+            }
+            (Some(_), None) => {
+                return Err(gql_error(ServerError::bad_param(
+                    "tagValue",
+                    "missing tag value",
+                )));
+            }
+        }
+        .map_err(gql_error)?;
+        Ok(Page::from_rows(rows, page.limit, |row| {
+            Cursor::new(row.block_height, &row.msg_id)
+        })
+        .into())
+    }
+
+    /// wallets delegating to at least 2 FLPs
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn multi_delegators(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<MultiDelegator>> {
+        let page = page_params(first, after)?;
+        let rows = indexer(ctx)
+            .multi_project_delegators(page.fetch_limit(), page.cursor.as_ref())
+            .await
+            .map_err(gql_error)?;
+        Ok(Page::from_rows(rows, page.limit, |row| {
+            Cursor::new(row.project_count, &row.wallet)
+        })
+        .into())
+    }
+
+    /// legacy network (ao.TN.1) explorer stats
+    async fn explorer(&self) -> Explorer {
+        Explorer { mainnet: false }
+    }
+
+    /// mainnet (ao.N.1) explorer stats
+    async fn mainnet_explorer(&self) -> Explorer {
+        Explorer { mainnet: true }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum TransferDirection {
+    /// the wallet is the `Sender`
+    Sent,
+    /// the wallet is the `Recipient`
+    Received,
+}
+
+pub struct Wallet {
+    address: String,
+}
+
+#[Object]
+impl Wallet {
+    async fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// EOA links recorded per oracle cycle, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn identities(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<Vec<IdentityLink>> {
+        let mut items = load_list(ctx, WalletIdentities(self.address.clone())).await?;
+        items.truncate(first_limit(first));
+        Ok(items)
+    }
+
+    /// distinct EOAs the wallet has been linked to
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn eoas(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<Vec<Eoa>> {
+        let links: Vec<IdentityLink> =
+            load_list(ctx, WalletIdentities(self.address.clone())).await?;
+        let mut eoas: Vec<String> = links.into_iter().map(|link| link.eoa).collect();
+        eoas.sort();
+        eoas.dedup();
+        eoas.truncate(first_limit(first));
+        Ok(eoas.into_iter().map(|address| Eoa { address }).collect())
+    }
+
+    /// latest indexed `Set-Delegation` payload
+    async fn delegations(&self, ctx: &Context<'_>) -> Result<Option<Json<Value>>> {
+        let sourced = optional(indexer(ctx).indexed_wallet_delegations(&self.address).await)
+            .map_err(gql_error)?;
+        Ok(sourced.map(|sourced| Json(sourced.data)))
+    }
+
+    /// delegation preference history, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn delegation_history(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+    ) -> Result<Vec<DelegationMappingHistory>> {
+        let mut items = load_list(ctx, WalletMappings(self.address.clone())).await?;
+        items.truncate(first_limit(first));
+        Ok(items)
+    }
+
+    /// positions of the latest cycle of every ticker, per delegated project
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn positions(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+    ) -> Result<Vec<WalletPosition>> {
+        let mut items = load_list(ctx, WalletPositions(self.address.clone())).await?;
+        items.truncate(first_limit(first));
+        Ok(items)
+    }
+
+    /// token messages sent or received by the wallet, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn token_messages(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+        direction: TransferDirection,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<AoTokenMessage>> {
+        let token = parse_token(&token).map_err(gql_error)?;
+        let (sender, recipient) = match direction {
+            TransferDirection::Sent => (Some(self.address.as_str()), None),
+            TransferDirection::Received => (None, Some(self.address.as_str())),
+        };
+        token_messages(ctx, &token, None, None, sender, recipient, first, after).await
+    }
+}
+
+pub struct Eoa {
+    address: String,
+}
+
+#[Object]
+impl Eoa {
+    async fn address(&self) -> &str {
+        &self.address
+    }
+
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn identities(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<Vec<IdentityLink>> {
+        let mut items = load_list(ctx, EoaIdentities(self.address.clone())).await?;
+        items.truncate(first_limit(first));
+        Ok(items)
+    }
+
+    /// distinct Arweave wallets linked to the EOA
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn wallets(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<Vec<Wallet>> {
+        let links: Vec<IdentityLink> = load_list(ctx, EoaIdentities(self.address.clone())).await?;
+        let mut wallets: Vec<String> = links.into_iter().map(|link| link.wallet).collect();
+        wallets.sort();
+        wallets.dedup();
+        wallets.truncate(first_limit(first));
+        Ok(wallets
+            .into_iter()
+            .map(|address| Wallet { address })
+            .collect())
+    }
+}
+
+pub struct FlpProject(Project);
+
+#[Object(name = "Project")]
+impl FlpProject {
+    /// FLP process id
+    async fn id(&self) -> &str {
+        &self.0.pid
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn ticker(&self) -> &str {
+        &self.0.ticker
+    }
+
+    /// token process id
+    async fn token(&self) -> &str {
+        &self.0.token
+    }
+
+    async fn denomination(&self) -> u32 {
+        self.0.denomination.into()
+    }
+
+    /// delegators and totals of the latest cycle of every ticker
+    async fn snapshot(&self, ctx: &Context<'_>) -> Result<Option<ProjectSnapshot>> {
+        optional(indexer(ctx).latest_project_snapshot(&self.0.pid).await).map_err(gql_error)
+    }
+
+    /// delegated totals per oracle cycle, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn cycles(
+        &self,
+        ctx: &Context<'_>,
+        ticker: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<ProjectCycleTotal>> {
+        let page = page_params(first, after)?;
+        let rows = optional(
+            indexer(ctx)
+                .project_cycle_totals(
+                    &self.0.pid,
+                    ticker.as_deref(),
+                    page.fetch_limit(),
+                    page.cursor.as_ref(),
+                )
+                .await,
+        )
+        .map_err(gql_error)?
+        .unwrap_or_default();
+        Ok(Page::from_rows(rows, page.limit, |row| {
+            Cursor::new(row.ts.timestamp_millis() as u64, &row.tx_id)
+        })
+        .into())
+    }
+
+    /// latest indexed `Own-Minting-Report`
+    async fn minting_report(&self, ctx: &Context<'_>) -> Result<Option<Json<Value>>> {
+        let sourced =
+            optional(indexer(ctx).indexed_minting_report(&self.0.pid).await).map_err(gql_error)?;
+        Ok(sourced.map(|sourced| Json(sourced.data)))
+    }
+}
+
+pub struct Oracle {
+    ticker: String,
+    pid: String,
+    evm_address: String,
+}
+
+#[Object]
+impl Oracle {
+    async fn ticker(&self) -> &str {
+        &self.ticker
+    }
+
+    /// oracle ao process id
+    async fn pid(&self) -> &str {
+        &self.pid
+    }
+
+    /// LST staking contract
+    async fn evm_address(&self) -> &str {
+        &self.evm_address
+    }
+
+    /// indexed cycles with their delegated totals, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn cycles(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<Vec<OracleSnapshot>> {
+        let limit = first_limit(first) as u64;
+        let cycles = optional(indexer(ctx).oracle_snapshot_feed(&self.ticker, limit).await)
+            .map_err(gql_error)?;
+        Ok(cycles.unwrap_or_default())
+    }
+
+    /// latest indexed `Set-Balances` payload
+    async fn balances(&self, ctx: &Context<'_>) -> Result<Option<Json<Value>>> {
+        let sourced = optional(indexer(ctx).indexed_oracle_balances(&self.ticker).await)
+            .map_err(gql_error)?;
+        Ok(sourced.map(|sourced| Json(sourced.data)))
+    }
+}
+
+pub struct Token {
+    token: String,
+}
+
+#[Object]
+impl Token {
+    async fn token(&self) -> &str {
+        &self.token
+    }
+
+    async fn info(&self, ctx: &Context<'_>) -> Result<AoTokenIndexingInfo> {
+        indexer(ctx)
+            .ao_token_indexing_info(&self.token)
+            .await
+            .map_err(gql_error)
+    }
+
+    /// indexed messages, newest first
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "`transfer` or `process`")] source: Option<String>,
+        #[graphql(desc = "`Action` tag value")] action: Option<String>,
+        sender: Option<String>,
+        recipient: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<AoTokenMessage>> {
+        token_messages(
+            ctx,
+            &self.token,
+            source.as_deref(),
+            action.as_deref(),
+            sender.as_deref(),
+            recipient.as_deref(),
+            first,
+            after,
+        )
+        .await
+    }
+
+    async fn message(&self, ctx: &Context<'_>, id: String) -> Result<Option<AoTokenMessage>> {
+        let rows = indexer(ctx)
+            .ao_token_message_by_id(&self.token, &id)
+            .await
+            .map_err(gql_error)?;
+        Ok(rows.into_iter().next())
+    }
+
+    /// top spenders/receivers by `Quantity`
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn richlist(&self, ctx: &Context<'_>, first: Option<i32>) -> Result<AoTokenRichlist> {
+        let limit = first_limit(first) as u64;
+        indexer(ctx)
+            .ao_token_richlist(&self.token, limit)
+            .await
+            .map_err(gql_error)
+    }
+
+    /// counts per `Action` and top `Sender`/`Recipient`
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn frequency(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+    ) -> Result<AoTokenFrequencyInfo> {
+        let limit = first_limit(first) as u64;
+        indexer(ctx)
+            .ao_token_frequency(&self.token, limit)
+            .await
+            .map_err(gql_error)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn token_messages(
+    ctx: &Context<'_>,
+    token: &str,
+    source: Option<&str>,
+    action: Option<&str>,
+    sender: Option<&str>,
+    recipient: Option<&str>,
+    first: Option<i32>,
+    after: Option<String>,
+) -> Result<GqlPage<AoTokenMessage>> {
+    let page = page_params(first, after)?;
+    let rows = indexer(ctx)
+        .ao_token_messages(
+            token,
+            source,
+            action,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            recipient,
+            sender,
+            None,
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
+        .await
+        .map_err(gql_error)?;
+    Ok(Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    })
+    .into())
+}
+
+pub struct Explorer {
+    mainnet: bool,
+}
+
+#[Object]
+impl Explorer {
+    /// last indexed blocks, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<ExplorerBlock>> {
+        let page = page_params(first, after)?;
+        let client = indexer(ctx);
+        let rows = if self.mainnet {
+            client
+                .mainnet_explorer_blocks(page.fetch_limit(), page.cursor.as_ref())
+                .await
+        } else {
+            client
+                .latest_explorer_blocks(page.fetch_limit(), page.cursor.as_ref())
+                .await
+        }
+        .map_err(gql_error)?;
+        Ok(Page::from_rows(rows, page.limit, |row| Cursor::new(row.height, "")).into())
+    }
+
+    /// stats of one day, defaults to today (UTC)
+    async fn day(&self, ctx: &Context<'_>, day: Option<NaiveDate>) -> Result<ExplorerDayStats> {
+        let day = day.unwrap_or_else(|| Utc::now().date_naive());
+        let client = indexer(ctx);
+        if self.mainnet {
+            client.mainnet_daily_explorer_stats(day).await
+        } else {
+            client.daily_explorer_stats(day).await
+        }
+        .map_err(gql_error)
+    }
+
+    /// stats of the last days, newest first
+    #[graphql(complexity = "first_limit(first) * child_complexity")]
+    async fn days(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<GqlPage<ExplorerDayStats>> {
+        let page = page_params(first, after)?;
+        let client = indexer(ctx);
+        let rows = if self.mainnet {
+            client
+                .mainnet_recent_explorer_days(page.fetch_limit(), page.cursor.as_ref())
+                .await
+        } else {
+            client
+                .recent_explorer_days(page.fetch_limit(), page.cursor.as_ref())
+                .await
+        }
+        .map_err(gql_error)?;
+        Ok(Page::from_rows(rows, page.limit, day_cursor).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::ServerConfig,
+        graphql::{AtlasSchema, build_schema},
+        indexer::AtlasIndexerClient,
+    };
+
+    fn schema() -> AtlasSchema {
+        let config = ServerConfig {
+            port: "0".to_string(),
+            clickhouse_url: "http://localhost:8123".to_string(),
+            clickhouse_user: "default".to_string(),
+            clickhouse_password: String::new(),
+            clickhouse_database: "atlas_oracles".to_string(),
+            atlas: None,
+        };
+        build_schema(AtlasIndexerClient::new(&config))
+    }
+
+    #[test]
+    fn schema_sdl_test() {
+        let sdl = schema().sdl();
+        for ty in [
+            "type Wallet",
+            "type Eoa",
+            "type Project",
+            "type Oracle",
+            "type MainnetMessagePage",
+        ] {
+            assert!(sdl.contains(ty), "{ty} missing from the schema");
+        }
+    }
+
+    #[tokio::test]
+    async fn complexity_limit_test() {
+        // rejected during validation, before any resolver touches clickhouse
+        let aliases: String = (0..20)
+            .map(|i| {
+                format!(
+                    "m{i}: mainnetMessages(first: 100) {{ data {{ msgId tags {{ key value }} }} }} "
+                )
+            })
+            .collect();
+        let res = schema().execute(format!("{{ {aliases} }}")).await;
+        assert!(!res.errors.is_empty());
+        assert!(res.errors[0].message.contains("too complex"));
+
+        // nested identity lists multiply by their `first`
+        let res = schema()
+            .execute(
+                r#"{ eoa(address: "0x0") { wallets { eoas { wallets { positions { project } } } } } }"#,
+            )
+            .await;
+        assert!(res.errors[0].message.contains("too complex"));
+
+        let res = schema().execute("{ projects { id name ticker } }").await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+    }
+}
@@ -1,17 +1,59 @@
+use crate::{config::ServerConfig, errors::NotFound, pagination::Cursor};
 use anyhow::{Error, anyhow};
+use async_graphql::{ComplexObject, SimpleObject};
 use chrono::{DateTime, NaiveDate, Utc};
 use clickhouse::Row;
+use clickhouse::query::BytesCursor;
 use common::{
+    amount::{AO_DECIMALS, AR_DECIMALS, Amount, LST_DECIMALS, lst_decimals, serialize_human},
     constants::{AO_TOKEN_START, DATA_PROTOCOL_A_START, DATA_PROTOCOL_B_START, PI_TOKEN_START},
-    env::get_env_var,
+    export::{ExportBind, ExportFormat, ExportQuery},
+    ids::{EvmAddress, MessageId},
     mainnet::get_network_height,
+    projects::Project,
 };
+use flp::csv_parser::RejectedRow;
+use flp::types::{DelegationsRes, OwnMintingReport};
+use migrations::Migrator;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use utoipa::ToSchema;
 
 #[derive(Clone)]
 pub struct AtlasIndexerClient {
     client: clickhouse::Client,
+    migrator: Migrator,
+}
+
+/// block time of a delegation mapping row, the indexing time for rows stored
+/// before block times were
+const MAPPING_BLOCK_TS: &str = "if(block_timestamp = 0, ts, toDateTime64(block_timestamp, 3))";
+
+/// a human amount column as stored, empty on rows written before the column
+/// was added
+pub fn stored_amount(value: &str, decimals: u8) -> Result<Amount, Error> {
+    if value.trim().is_empty() {
+        return Ok(Amount::zero(decimals));
+    }
+    Amount::parse_human(value, decimals)
+}
+
+/// a minting report of `project`, with its totals in the project token when
+/// it is a tracked FLP
+pub fn minting_report_value(
+    project: &str,
+    report: OwnMintingReport,
+) -> Result<serde_json::Value, Error> {
+    let value = match Project::get(project) {
+        Some(project) => serde_json::to_value(report.in_project(&project))?,
+        None => serde_json::to_value(report)?,
+    };
+    Ok(value)
+}
+
+/// `?, ?, ?` for an `in (...)` list of `n` bound values
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
 }
 
 enum BindValue {
@@ -31,19 +73,21 @@ impl BindValue {
 }
 
 impl AtlasIndexerClient {
-    pub async fn new() -> Result<Self, Error> {
-        let url = get_env_var("CLICKHOUSE_URL").unwrap_or_else(|_| "http://localhost:8123".into());
-        let user = get_env_var("CLICKHOUSE_USER").unwrap_or_else(|_| "default".into());
-        let password = get_env_var("CLICKHOUSE_PASSWORD").unwrap_or_default();
-        let database =
-            get_env_var("CLICKHOUSE_DATABASE").unwrap_or_else(|_| "atlas_oracles".into());
+    pub fn new(config: &ServerConfig) -> Self {
         let admin = clickhouse::Client::default()
-            .with_url(&url)
-            .with_user(&user)
-            .with_password(&password);
-        let client = admin.clone().with_database(&database);
-        ensure_schema(&admin, &client, &database).await?;
-        Ok(Self { client })
+            .with_url(&config.clickhouse_url)
+            .with_user(&config.clickhouse_user)
+            .with_password(&config.clickhouse_password);
+        let client = admin.clone().with_database(&config.clickhouse_database);
+        let migrator = Migrator::new(admin, &config.clickhouse_database);
+        Self { client, migrator }
+    }
+
+    /// names of the schema migrations the indexer hasn't applied yet, the
+    /// server never migrates itself (run the indexer or `atlas migrate`)
+    pub async fn pending_migrations(&self) -> Result<Vec<&'static str>, Error> {
+        let pending = self.migrator.pending().await?;
+        Ok(pending.into_iter().map(|m| m.name).collect())
     }
 
     pub async fn latest_project_snapshot(&self, project: &str) -> Result<ProjectSnapshot, Error> {
@@ -67,10 +111,10 @@ impl AtlasIndexerClient {
             .fetch_all::<FlpPositionRow>()
             .await?;
         if rows.is_empty() {
-            return Err(anyhow!("no delegations found for project {project}"));
+            return Err(NotFound(format!("no delegations found for project {project}")).into());
         }
         let ts = rows.iter().map(|row| row.ts).max().unwrap();
-        let totals = aggregate_totals(&rows);
+        let totals = aggregate_totals(&rows)?;
         let delegators = rows
             .into_iter()
             .map(|row| Delegator {
@@ -90,36 +134,631 @@ impl AtlasIndexerClient {
         })
     }
 
-    pub async fn wallet_identity_history(&self, wallet: &str) -> Result<Vec<IdentityLink>, Error> {
+    /// latest cycle positions of a wallet, one row per (ticker, project)
+    pub async fn wallet_positions(&self, wallet: &str) -> Result<Vec<WalletPosition>, Error> {
+        let mut positions = self.wallets_positions(&[wallet.to_string()]).await?;
+        Ok(positions.remove(wallet).unwrap_or_default())
+    }
+
+    /// `wallet_positions` of several wallets in one query
+    pub async fn wallets_positions(
+        &self,
+        wallets: &[String],
+    ) -> Result<HashMap<String, Vec<WalletPosition>>, Error> {
+        if wallets.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let query = format!(
+            "with latest as (\
+                select ticker, max(ts) as ts \
+                from flp_positions \
+                group by ticker\
+            ) \
+            select p.ts, p.ticker, p.wallet, p.eoa, toString(p.project) as project, p.factor, p.amount, p.ar_amount \
+            from flp_positions p \
+            inner join latest l on p.ticker = l.ticker and p.ts = l.ts \
+            where p.wallet in ({}) \
+            order by p.ticker, p.project",
+            placeholders(wallets.len())
+        );
+        let mut query = self.client.query(&query);
+        for wallet in wallets {
+            query = query.bind(wallet);
+        }
+        let rows = query.fetch_all::<FlpPositionRow>().await?;
+        let mut out: HashMap<String, Vec<WalletPosition>> = HashMap::new();
+        for row in rows {
+            out.entry(row.wallet).or_default().push(WalletPosition {
+                ts: row.ts,
+                project: row.project,
+                ticker: row.ticker,
+                factor: row.factor,
+                amount: row.amount,
+                ar_amount: row.ar_amount,
+            });
+        }
+        Ok(out)
+    }
+
+    /// every position of the latest cycle of each ticker, `ticker` narrows it
+    /// to one oracle
+    pub async fn latest_flp_position_shares(
+        &self,
+        ticker: Option<&str>,
+    ) -> Result<Vec<FlpPositionShare>, Error> {
+        let ticker_clause = if ticker.is_some() {
+            " where ticker = ?"
+        } else {
+            ""
+        };
+        let query = format!(
+            "with latest as (\
+                select ticker, max(ts) as ts \
+                from flp_positions{ticker_clause} \
+                group by ticker\
+            ) \
+            select p.wallet, p.ticker, toString(p.project) as project, p.factor, \
+                toDecimal128OrZero(p.amount, 18) as amount \
+            from flp_positions p \
+            inner join latest l on p.ticker = l.ticker and p.ts = l.ts \
+            where p.factor > 0 \
+            order by p.wallet, p.ticker, p.project"
+        );
+        let mut query = self.client.query(&query);
+        if let Some(t) = ticker {
+            query = query.bind(t);
+        }
+        let rows: Vec<FlpPositionShare> = query
+            .fetch_all::<FlpPositionShareRow>()
+            .await?
+            .into_iter()
+            .map(FlpPositionShare::from)
+            .collect();
+        if rows.is_empty() {
+            return Err(NotFound("no flp positions indexed yet".to_string()).into());
+        }
+        Ok(rows)
+    }
+
+    /// latest indexed oracle balance of a wallet, one row per ticker
+    pub async fn wallet_balances(&self, wallet: &str) -> Result<Vec<WalletBalance>, Error> {
         let rows = self
             .client
             .query(
-                "select wallet, eoa, ts \
+                "select ticker, argMax(eoa, ts) as eoa, argMax(amount, ts) as amount, \
+                    argMax(ar_balance, ts) as ar_balance, argMax(tx_id, ts) as tx_id, max(ts) as ts \
                  from wallet_balances \
                  where wallet = ? \
-                 order by ts desc",
+                 group by ticker \
+                 order by ticker",
             )
             .bind(wallet)
-            .fetch_all::<IdentityRow>()
+            .fetch_all::<WalletBalanceRow>()
             .await?;
-        Ok(rows.into_iter().map(|row| row.into()).collect())
+        Ok(rows
+            .into_iter()
+            .map(|row| WalletBalance {
+                ticker: row.ticker,
+                eoa: row.eoa,
+                amount: row.amount,
+                ar_balance: row.ar_balance,
+                tx_id: row.tx_id,
+                ts: row.ts,
+            })
+            .collect())
+    }
+
+    /// newest ao/pi token messages tagged with the wallet as `Sender` or
+    /// `Recipient`, paired with their token
+    pub async fn wallet_token_activity(
+        &self,
+        wallet: &str,
+        limit: u64,
+    ) -> Result<Vec<(String, AoTokenMessage)>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select \
+                    m.token, m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
+                    m.bundled_in, m.data_size, m.ts, \
+                    arrayFilter(x -> x.1 != '', groupArray(tuple(ifNull(t.tag_key, ''), ifNull(t.tag_value, '')))) as tags \
+                 from ao_token_messages m \
+                 left join ao_token_message_tags t \
+                   on t.token = m.token and t.source = m.source and t.block_height = m.block_height and t.msg_id = m.msg_id \
+                 where (m.token, m.source, m.block_height, m.msg_id) in (\
+                    select token, source, block_height, msg_id \
+                    from ao_token_message_tags \
+                    where tag_key in ('Sender', 'Recipient') and tag_value = ? \
+                    order by block_height desc, msg_id desc \
+                    limit ?\
+                 ) \
+                 group by m.token, m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
+                 order by m.block_height desc, m.msg_id desc \
+                 limit ?",
+            )
+            .bind(wallet)
+            .bind(limit)
+            .bind(limit)
+            .fetch_all::<TokenActivityRow>()
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let message = AoTokenMessageRow {
+                    source: row.source,
+                    block_height: row.block_height,
+                    block_timestamp: row.block_timestamp,
+                    msg_id: row.msg_id,
+                    owner: row.owner,
+                    recipient: row.recipient,
+                    bundled_in: row.bundled_in,
+                    data_size: row.data_size,
+                    ts: row.ts,
+                    tags: row.tags,
+                };
+                (row.token, message.into())
+            })
+            .collect())
+    }
+
+    pub async fn wallet_identity_history(&self, wallet: &str) -> Result<Vec<IdentityLink>, Error> {
+        self.wallets_identity_history(&[wallet.to_string()]).await
+    }
+
+    /// EOA links of several wallets, newest first
+    pub async fn wallets_identity_history(
+        &self,
+        wallets: &[String],
+    ) -> Result<Vec<IdentityLink>, Error> {
+        self.identity_history("wallet", wallets.iter().map(String::as_str))
+            .await
     }
 
     pub async fn eoa_identity_history(&self, eoa: &str) -> Result<Vec<IdentityLink>, Error> {
+        self.eoas_identity_history(&[eoa.to_string()]).await
+    }
+
+    /// wallet links of several EOAs matched case-insensitively, newest first
+    pub async fn eoas_identity_history(&self, eoas: &[String]) -> Result<Vec<IdentityLink>, Error> {
+        let lower: Vec<String> = eoas.iter().map(|eoa| eoa.to_ascii_lowercase()).collect();
+        self.identity_history("lower(eoa)", lower.iter().map(String::as_str))
+            .await
+    }
+
+    async fn identity_history<'a>(
+        &self,
+        column: &str,
+        values: impl ExactSizeIterator<Item = &'a str>,
+    ) -> Result<Vec<IdentityLink>, Error> {
+        if values.len() == 0 {
+            return Ok(Vec::new());
+        }
+        let sql = format!(
+            "select wallet, eoa, ts \
+             from wallet_balances \
+             where {column} in ({}) \
+             order by ts desc",
+            placeholders(values.len())
+        );
+        let mut query = self.client.query(&sql);
+        for value in values {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all::<IdentityRow>().await?;
+        Ok(rows.into_iter().filter_map(IdentityRow::link).collect())
+    }
+
+    pub async fn indexed_wallet_delegations(&self, wallet: &str) -> Result<Sourced, Error> {
+        let row = self
+            .client
+            .query(
+                "select ts, payload from wallet_delegations \
+                 where wallet = ? \
+                 order by ts desc \
+                 limit 1",
+            )
+            .bind(wallet)
+            .fetch_all::<PayloadRow>()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                NotFound(format!(
+                    "no indexed delegations for wallet {wallet}, retry with ?live=true"
+                ))
+            })?;
+        let data: DelegationsRes = serde_json::from_str(&row.payload)?;
+        Ok(Sourced::indexed(serde_json::to_value(data)?, row.ts, None))
+    }
+
+    /// Set-Balances payload of the latest indexed oracle update
+    pub async fn indexed_oracle_balances(&self, ticker: &str) -> Result<Sourced, Error> {
+        let tx_id = self
+            .client
+            .query(
+                "select tx_id from oracle_snapshots \
+                 where ticker = ? \
+                 order by ts desc \
+                 limit 1",
+            )
+            .bind(ticker)
+            .fetch_all::<TxIdRow>()
+            .await?
+            .into_iter()
+            .next()
+            .map(|row| row.tx_id)
+            .ok_or_else(|| {
+                NotFound(format!(
+                    "no indexed updates for oracle {ticker}, retry with ?live=true"
+                ))
+            })?;
+        self.indexed_set_balances(&tx_id).await
+    }
+
+    /// the Set-Balances payload stored by the indexer for `tx_id`, updates
+    /// indexed before payloads were stored are reported as not found
+    pub async fn indexed_set_balances(&self, tx_id: &str) -> Result<Sourced, Error> {
+        let row = self
+            .client
+            .query("select ts, payload from oracle_payloads where tx_id = ? limit 1")
+            .bind(tx_id)
+            .fetch_all::<PayloadRow>()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                NotFound(format!(
+                    "Set-Balances {tx_id} not indexed, retry with ?live=true"
+                ))
+            })?;
+        let data: serde_json::Value = serde_json::from_str(&row.payload)?;
+        let rejected = self.oracle_csv_rejections(tx_id).await?;
+        Ok(Sourced::indexed(data, row.ts, Some(tx_id.to_string())).with_rejected(rejected))
+    }
+
+    /// Set-Balances rows of `tx_id` the indexer skipped as malformed
+    pub async fn oracle_csv_rejections(&self, tx_id: &str) -> Result<Vec<RejectedRow>, Error> {
         let rows = self
             .client
             .query(
-                "select wallet, eoa, ts \
-                 from wallet_balances \
-                 where eoa = ? \
-                 order by ts desc",
+                "select line, raw, reason from csv_rejections final \
+                 where tx_id = ? and source != 'delegation_mappings' \
+                 order by line",
             )
-            .bind(eoa)
-            .fetch_all::<IdentityRow>()
+            .bind(tx_id)
+            .fetch_all::<CsvRejectionRow>()
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| RejectedRow {
+                line: row.line,
+                raw: row.raw,
+                reason: row.reason,
+            })
+            .collect())
+    }
+
+    pub async fn indexed_minting_report(&self, project: &str) -> Result<Sourced, Error> {
+        let row = self
+            .client
+            .query(
+                "select ts, report_id, payload from flp_minting_reports \
+                 where project = ? \
+                 order by ts desc \
+                 limit 1",
+            )
+            .bind(project)
+            .fetch_all::<MintingReportRow>()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                NotFound(format!(
+                    "no indexed minting report for project {project}, retry with ?live=true"
+                ))
+            })?;
+        let data: OwnMintingReport = serde_json::from_str(&row.payload)?;
+        Ok(Sourced::indexed(
+            minting_report_value(project, data)?,
+            row.ts,
+            Some(row.report_id),
+        ))
+    }
+
+    /// the `limit` newest indexed minting reports of an FLP, newest first
+    pub async fn minting_report_history(
+        &self,
+        project: &str,
+        limit: u64,
+    ) -> Result<Vec<OwnMintingReport>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select ts, report_id, payload from flp_minting_reports \
+                 where project = ? \
+                 order by ts desc \
+                 limit ?",
+            )
+            .bind(project)
+            .bind(limit)
+            .fetch_all::<MintingReportRow>()
+            .await?;
+        if rows.is_empty() {
+            return Err(
+                NotFound(format!("no indexed minting reports for project {project}")).into(),
+            );
+        }
+        rows.into_iter()
+            .map(|row| {
+                let mut report: OwnMintingReport = serde_json::from_str(&row.payload)?;
+                report.report_id = MessageId::parse(&row.report_id).ok();
+                Ok(report)
+            })
+            .collect()
+    }
+
+    /// per (cycle, ticker) amounts a wallet and all delegators delegated to an
+    /// FLP since `since_ms`, oldest first, from the same per-wallet positions
+    /// as the cycle totals
+    pub async fn wallet_cycle_stakes(
+        &self,
+        project: &str,
+        wallet: &str,
+        since_ms: i64,
+    ) -> Result<Vec<WalletCycleStake>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select t.ts as ts, t.ticker as ticker, w.amount as wallet_amount, \
+                    t.amount as total_amount \
+                 from (\
+                    select ts, ticker, amount \
+                    from flp_cycle_project_totals final \
+                    where project = ? and ts >= fromUnixTimestamp64Milli(toInt64(?))\
+                 ) t \
+                 left join (\
+                    select ts, ticker, sum(toDecimal128OrZero(amount, 18)) as amount \
+                    from flp_positions final \
+                    where project = ? and wallet = ? and ts >= fromUnixTimestamp64Milli(toInt64(?)) \
+                    group by ts, ticker\
+                 ) w on t.ts = w.ts and t.ticker = w.ticker \
+                 order by ts, ticker",
+            )
+            .bind(project)
+            .bind(since_ms)
+            .bind(project)
+            .bind(wallet)
+            .bind(since_ms)
+            .fetch_all::<WalletCycleStakeRow>()
+            .await?;
+        Ok(rows.into_iter().map(WalletCycleStake::from).collect())
+    }
+
+    /// progress of the indexers, the response cache drops entries once it moves
+    pub async fn index_heights(&self) -> Result<IndexHeights, Error> {
+        let row = self
+            .client
+            .query(
+                "select \
+                 (select max(height) from ao_mainnet_explorer) as mainnet_explorer, \
+                 (select max(height) from atlas_explorer) as atlas_explorer, \
+                 (select toUInt64(max(last_complete_height)) from ao_token_block_state where token = 'ao') as ao, \
+                 (select toUInt64(max(last_complete_height)) from ao_token_block_state where token = 'pi') as pi, \
+                 (select toUInt64(toUnixTimestamp64Milli(max(ts))) from oracle_snapshots) as flp, \
+                 (select toUInt64(max(height)) from delegation_mappings) as delegation_mappings",
+            )
+            .fetch_one::<IndexHeights>()
+            .await?;
+        Ok(row)
+    }
+
+    /// keys of the `api_keys` table, disabled ones included so they can
+    /// revoke a key also set in atlas.toml
+    pub async fn api_keys(&self) -> Result<Vec<ApiKeyRow>, Error> {
+        let rows = self
+            .client
+            .query("select key_hash, name, rpm, admin, enabled from api_keys final")
+            .fetch_all::<ApiKeyRow>()
+            .await?;
+        Ok(rows)
+    }
+
+    /// raw export response, formatted by clickhouse and read chunk by chunk
+    pub fn export_bytes(
+        &self,
+        export: &ExportQuery,
+        format: ExportFormat,
+    ) -> Result<BytesCursor, Error> {
+        let mut query = self.client.query(&export.sql);
+        for bind in &export.binds {
+            query = match bind {
+                ExportBind::Str(val) => query.bind(val),
+                ExportBind::U32(val) => query.bind(val),
+            };
+        }
+        Ok(query.fetch_bytes(format.clickhouse_format())?)
+    }
+
+    /// newest `ts` of a table, where a fresh live subscription starts from
+    pub async fn latest_indexed_ms(&self, table: &str) -> Result<i64, Error> {
+        let row = self
+            .client
+            .query(&format!(
+                "select toInt64(toUnixTimestamp64Milli(max(ts))) as ms from {table}"
+            ))
+            .fetch_one::<MsRow>()
+            .await?;
+        Ok(row.ms)
+    }
+
+    /// mainnet messages written after the `(ts, msg_id)` position, oldest first.
+    /// rows of the last seconds are held back until their tags are written too
+    pub async fn mainnet_messages_since(
+        &self,
+        since_ms: i64,
+        since_id: &str,
+        limit: u64,
+    ) -> Result<Vec<MainnetMessage>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select \
+                    m.protocol, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
+                    m.bundled_in, m.data_size, m.ts, \
+                    arrayFilter(x -> x.1 != '', groupArray(tuple(ifNull(t.tag_key, ''), ifNull(t.tag_value, '')))) as tags \
+                 from ao_mainnet_messages m \
+                 left join ao_mainnet_message_tags t \
+                   on t.protocol = m.protocol and t.block_height = m.block_height and t.msg_id = m.msg_id \
+                 where (m.ts, m.msg_id) > (fromUnixTimestamp64Milli(toInt64(?)), ?) \
+                   and m.ts <= now64(3) - interval 2 second \
+                 group by m.protocol, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
+                 order by m.ts, m.msg_id \
+                 limit ?",
+            )
+            .bind(since_ms)
+            .bind(since_id)
+            .bind(limit)
+            .fetch_all::<MainnetMessageRow>()
             .await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
 
+    /// token messages written after the `(ts, msg_id)` position, oldest first
+    pub async fn ao_token_messages_since(
+        &self,
+        token: &str,
+        since_ms: i64,
+        since_id: &str,
+        limit: u64,
+    ) -> Result<Vec<AoTokenMessage>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select \
+                    m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
+                    m.bundled_in, m.data_size, m.ts, \
+                    arrayFilter(x -> x.1 != '', groupArray(tuple(ifNull(t.tag_key, ''), ifNull(t.tag_value, '')))) as tags \
+                 from ao_token_messages m \
+                 left join ao_token_message_tags t \
+                   on t.token = m.token and t.source = m.source and t.block_height = m.block_height and t.msg_id = m.msg_id \
+                 where m.token = ? \
+                   and (m.ts, m.msg_id) > (fromUnixTimestamp64Milli(toInt64(?)), ?) \
+                   and m.ts <= now64(3) - interval 2 second \
+                 group by m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
+                 order by m.ts, m.msg_id \
+                 limit ?",
+            )
+            .bind(token)
+            .bind(since_ms)
+            .bind(since_id)
+            .bind(limit)
+            .fetch_all::<AoTokenMessageRow>()
+            .await?;
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    /// highest delegation mapping height indexed so far
+    pub async fn latest_delegation_mapping_height(&self) -> Result<u32, Error> {
+        let row = self
+            .client
+            .query("select toUInt32(max(height)) as height from delegation_mappings")
+            .fetch_one::<HeightRow>()
+            .await?;
+        Ok(row.height)
+    }
+
+    /// delegation mapping txs after the `(height, tx_id)` position, oldest
+    /// first. the position is on-chain so txs rewritten by a repair or a
+    /// backfill aren't sent again, and rows of the last seconds are held back
+    /// until their whole tx is written. a tx cut by `limit` is left for the
+    /// next call
+    pub async fn delegation_mappings_since(
+        &self,
+        since_height: u32,
+        since_id: &str,
+        limit: u64,
+    ) -> Result<Vec<DelegationMappingHistory>, Error> {
+        let mut rows = self
+            .client
+            .query(
+                "select ts, height, tx_id, wallet_from, wallet_to, factor \
+                 from delegation_mappings final \
+                 where (height, tx_id) > (?, ?) \
+                   and ts <= now64(3) - interval 2 second \
+                 order by height, tx_id, wallet_from, wallet_to \
+                 limit ?",
+            )
+            .bind(since_height)
+            .bind(since_id)
+            .bind(limit)
+            .fetch_all::<DelegationMappingRow>()
+            .await?;
+        if rows.len() as u64 == limit {
+            let last = rows.last().map(|row| row.tx_id.clone());
+            if let Some(tx_id) = last {
+                let cut = |row: &DelegationMappingRow| row.tx_id == tx_id;
+                // a single tx larger than `limit` is still sent, truncated
+                if !rows.iter().all(cut) {
+                    rows.retain(|row| !cut(row));
+                }
+            }
+        }
+        let mut out: Vec<DelegationMappingHistory> = Vec::new();
+        for row in rows {
+            let same = out
+                .last()
+                .is_some_and(|entry| entry.tx_id == row.tx_id && entry.wallet == row.wallet_from);
+            if !same {
+                out.push(DelegationMappingHistory {
+                    ts: row.ts,
+                    height: row.height,
+                    tx_id: row.tx_id.clone(),
+                    wallet: row.wallet_from.clone(),
+                    preferences: Vec::new(),
+                });
+            }
+            if let Some(entry) = out.last_mut() {
+                entry.preferences.push(DelegationPreference {
+                    wallet_to: row.wallet_to,
+                    factor: row.factor,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// oracle cycles whose positions were written after the `(ts, ticker)`
+    /// position, oldest first. positions land after the snapshot row, so a
+    /// cycle only shows up once it is complete
+    pub async fn oracle_cycles_since(
+        &self,
+        since_ms: i64,
+        since_ticker: &str,
+        limit: u64,
+    ) -> Result<Vec<OracleSnapshot>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select p.ts as ts, p.ticker as ticker, any(o.tx_id) as tx_id, sum(toDecimal128OrZero(p.amount, 18)) as total, uniqExact(p.wallet) as delegators \
+                 from flp_positions p \
+                 left join oracle_snapshots o \
+                   on o.ticker = p.ticker and o.ts = p.ts \
+                 where (p.ts, p.ticker) > (fromUnixTimestamp64Milli(toInt64(?)), ?) \
+                 group by p.ts, p.ticker \
+                 order by p.ts, p.ticker \
+                 limit ?",
+            )
+            .bind(since_ms)
+            .bind(since_ticker)
+            .bind(limit)
+            .fetch_all::<OracleSnapshotRow>()
+            .await?
+            .into_iter()
+            .map(OracleSnapshot::from)
+            .collect::<Vec<_>>();
+        Ok(rows)
+    }
+
     pub async fn oracle_snapshot_feed(
         &self,
         ticker: &str,
@@ -128,7 +767,7 @@ impl AtlasIndexerClient {
         let rows = self
             .client
             .query(
-                "select o.ts, o.ticker, o.tx_id, toFloat64(sum(toDecimal128(if(length(p.amount) = 0, '0', p.amount), 18))) as total, uniqExact(p.wallet) as delegators \
+                "select o.ts, o.ticker, o.tx_id, sum(toDecimal128OrZero(p.amount, 18)) as total, uniqExact(p.wallet) as delegators \
                  from oracle_snapshots o \
                  left join flp_positions p \
                    on p.ticker = o.ticker and p.ts = o.ts \
@@ -140,10 +779,13 @@ impl AtlasIndexerClient {
            )
             .bind(ticker)
             .bind(limit)
-            .fetch_all::<OracleSnapshot>()
-            .await?;
+            .fetch_all::<OracleSnapshotRow>()
+            .await?
+            .into_iter()
+            .map(OracleSnapshot::from)
+            .collect::<Vec<_>>();
         if rows.is_empty() {
-            return Err(anyhow!("no oracle snapshots found for ticker {ticker}"));
+            return Err(NotFound(format!("no oracle snapshots found for ticker {ticker}")).into());
         }
         Ok(rows)
     }
@@ -152,23 +794,38 @@ impl AtlasIndexerClient {
         &self,
         wallet: &str,
     ) -> Result<Vec<DelegationMappingHistory>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select ts, height, tx_id, wallet_from, wallet_to, factor \
-                 from delegation_mappings \
-                 where wallet_from = ? \
-                 order by height desc",
-            )
-            .bind(wallet)
-            .fetch_all::<DelegationMappingRow>()
-            .await?;
-        if rows.is_empty() {
-            return Err(anyhow!("no delegation mappings found for wallet {wallet}"));
+        self.wallets_delegation_mappings(&[wallet.to_string()])
+            .await?
+            .remove(wallet)
+            .ok_or_else(|| {
+                NotFound(format!("no delegation mappings found for wallet {wallet}")).into()
+            })
+    }
+
+    /// delegation mapping history of several wallets, newest first. wallets
+    /// without mappings are left out
+    pub async fn wallets_delegation_mappings(
+        &self,
+        wallets: &[String],
+    ) -> Result<HashMap<String, Vec<DelegationMappingHistory>>, Error> {
+        if wallets.is_empty() {
+            return Ok(HashMap::new());
         }
+        let sql = format!(
+            "select ts, height, tx_id, wallet_from, wallet_to, factor \
+             from delegation_mappings \
+             where wallet_from in ({}) \
+             order by height desc",
+            placeholders(wallets.len())
+        );
+        let mut query = self.client.query(&sql);
+        for wallet in wallets {
+            query = query.bind(wallet);
+        }
+        let rows = query.fetch_all::<DelegationMappingRow>().await?;
         let mut map = BTreeMap::new();
         for row in rows {
-            let key = (row.height, row.tx_id.clone());
+            let key = (row.wallet_from.clone(), row.height, row.tx_id.clone());
             let entry = map.entry(key).or_insert_with(|| DelegationMappingHistory {
                 ts: row.ts,
                 height: row.height,
@@ -181,29 +838,38 @@ impl AtlasIndexerClient {
                 factor: row.factor,
             });
         }
-        let mut out: Vec<_> = map.into_values().collect();
-        out.sort_by(|a, b| b.height.cmp(&a.height));
+        let mut out: HashMap<String, Vec<DelegationMappingHistory>> = HashMap::new();
+        // keys are in ascending height per wallet
+        for history in map.into_values().rev() {
+            out.entry(history.wallet.clone()).or_default().push(history);
+        }
         Ok(out)
     }
 
     pub async fn latest_delegation_heights(
         &self,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<DelegationHeight>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select height, tx_id \
-                 from delegation_mappings \
-                 group by height, tx_id \
-                 order by height desc \
-                 limit ?",
-            )
-            .bind(limit)
-            .fetch_all::<DelegationHeightRow>()
-            .await?;
-        if rows.is_empty() {
-            return Err(anyhow!("no delegation mappings indexed yet"));
+        let cursor_clause = if cursor.is_some() {
+            " where (height, tx_id) < (?, ?)"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select height, tx_id \
+             from delegation_mappings{cursor_clause} \
+             group by height, tx_id \
+             order by height desc, tx_id desc \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(&c.id);
+        }
+        let rows = query.bind(limit).fetch_all::<DelegationHeightRow>().await?;
+        if rows.is_empty() && cursor.is_none() {
+            return Err(NotFound("no delegation mappings indexed yet".to_string()).into());
         }
         Ok(rows
             .into_iter()
@@ -214,23 +880,32 @@ impl AtlasIndexerClient {
             .collect())
     }
 
-    pub async fn multi_project_delegators(&self, limit: u64) -> Result<Vec<MultiDelegator>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select wallet, any(eoa) as eoa, countDistinct(project) as project_count, \
-                 groupUniqArray(project) as projects \
-                 from flp_positions \
-                 group by wallet \
-                 having project_count >= 2 \
-                 order by project_count desc \
-                 limit ?",
-            )
-            .bind(limit)
-            .fetch_all::<MultiDelegatorRow>()
-            .await?;
-        if rows.is_empty() {
-            return Err(anyhow!("no multi project delegators found"));
+    pub async fn multi_project_delegators(
+        &self,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<MultiDelegator>, Error> {
+        let cursor_clause = if cursor.is_some() {
+            " and (project_count < ? or (project_count = ? and wallet > ?))"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select wallet, any(eoa) as eoa, countDistinct(project) as project_count, \
+             groupUniqArray(project) as projects \
+             from flp_positions \
+             group by wallet \
+             having project_count >= 2{cursor_clause} \
+             order by project_count desc, wallet \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(c.key).bind(&c.id);
+        }
+        let rows = query.bind(limit).fetch_all::<MultiDelegatorRow>().await?;
+        if rows.is_empty() && cursor.is_none() {
+            return Err(NotFound("no multi project delegators found".to_string()).into());
         }
         Ok(rows
             .into_iter()
@@ -248,22 +923,28 @@ impl AtlasIndexerClient {
         project: &str,
         ticker: Option<&str>,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<ProjectCycleTotal>, Error> {
         let ticker_clause = if ticker.is_some() {
             " and p.ticker = ?"
         } else {
             ""
         };
+        let cursor_clause = if cursor.is_some() {
+            " and (toUnixTimestamp64Milli(p.ts), o.tx_id) < (?, ?)"
+        } else {
+            ""
+        };
         let query_str = format!(
             "select o.tx_id, p.ts, \
-             sumIf(toFloat64(p.amount), p.ticker = 'usds') as usds_total, \
-             sumIf(toFloat64(p.amount), p.ticker = 'dai') as dai_total, \
-             sumIf(toFloat64(p.amount), p.ticker = 'steth') as steth_total \
+             sumIf(toDecimal128OrZero(p.amount, 18), p.ticker = 'usds') as usds_total, \
+             sumIf(toDecimal128OrZero(p.amount, 18), p.ticker = 'dai') as dai_total, \
+             sumIf(toDecimal128OrZero(p.amount, 18), p.ticker = 'steth') as steth_total \
              from flp_positions p \
              inner join oracle_snapshots o on o.ticker = p.ticker and o.ts = p.ts \
-             where p.project = ?{ticker_clause} \
+             where p.project = ?{ticker_clause}{cursor_clause} \
              group by o.tx_id, p.ts \
-             order by p.ts desc \
+             order by p.ts desc, o.tx_id desc \
              limit ?",
         );
         let mut query = self.client.query(&query_str);
@@ -271,22 +952,160 @@ impl AtlasIndexerClient {
         if let Some(t) = ticker {
             query = query.bind(t);
         }
-        let rows = query.bind(limit).fetch_all::<ProjectCycleTotal>().await?;
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(&c.id);
+        }
+        let rows: Vec<ProjectCycleTotal> = query
+            .bind(limit)
+            .fetch_all::<ProjectCycleTotalRow>()
+            .await?
+            .into_iter()
+            .map(ProjectCycleTotal::from)
+            .collect();
+        if rows.is_empty() && cursor.is_none() {
+            return Err(NotFound(format!("no cycle totals found for project {project}")).into());
+        }
+        Ok(rows)
+    }
+
+    /// per (ticker, cycle, FLP) totals of the last `days` before the newest
+    /// cycle, read from the per-cycle `flp_cycle_project_totals`
+    pub async fn recent_flp_cycle_totals(
+        &self,
+        days: u32,
+    ) -> Result<Vec<FlpCycleProjectTotal>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select ticker, ts, project, amount, ar_amount, delegators \
+                 from flp_cycle_project_totals final \
+                 where ts >= (select max(ts) from flp_cycle_project_totals) - toIntervalDay(?) \
+                 order by ticker, ts",
+            )
+            .bind(days)
+            .fetch_all::<FlpCycleTotalRow>()
+            .await?;
+        Ok(rows.into_iter().map(FlpCycleProjectTotal::from).collect())
+    }
+
+    /// per FLP totals of the latest cycle of every ticker
+    pub async fn latest_flp_cycle_totals(&self) -> Result<Vec<FlpCycleProjectTotal>, Error> {
+        let rows = self
+            .client
+            .query(
+                "select ticker, ts, project, amount, ar_amount, delegators \
+                 from flp_cycle_project_totals final \
+                 where (ticker, ts) in \
+                    (select ticker, max(ts) from flp_cycle_project_totals group by ticker) \
+                 order by ticker, project",
+            )
+            .fetch_all::<FlpCycleTotalRow>()
+            .await?;
         if rows.is_empty() {
-            return Err(anyhow!("no cycle totals found for project {project}"));
+            return Err(NotFound("no indexed oracle cycles".to_string()).into());
+        }
+        Ok(rows.into_iter().map(FlpCycleProjectTotal::from).collect())
+    }
+
+    /// every delegation mapping tx of the wallets that ever delegated to
+    /// `project`, per wallet oldest first
+    pub async fn project_delegation_mapping_txs(
+        &self,
+        project: &str,
+    ) -> Result<Vec<DelegationMappingTx>, Error> {
+        let sql = format!(
+            "select min({MAPPING_BLOCK_TS}) as ts, height, tx_id, wallet_from as wallet, \
+                groupArray((wallet_to, factor)) as preferences \
+             from delegation_mappings \
+             where wallet_from in \
+                (select wallet_from from delegation_mappings where wallet_to = ?) \
+             group by height, tx_id, wallet_from \
+             order by wallet_from, height, tx_id"
+        );
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(project)
+            .fetch_all::<DelegationMappingTx>()
+            .await?;
+        if rows.is_empty() {
+            return Err(NotFound(format!(
+                "no delegation mappings found for project {project}"
+            ))
+            .into());
         }
         Ok(rows)
     }
 
+    /// every delegation mapping tx since the first one that delegated to
+    /// `project`, oldest first
+    pub async fn project_delegation_mapping_tx_refs(
+        &self,
+        project: &str,
+    ) -> Result<Vec<DelegationMappingTxRef>, Error> {
+        let sql = format!(
+            "select height, tx_id, min({MAPPING_BLOCK_TS}) as ts \
+             from delegation_mappings \
+             where height >= (select min(height) from delegation_mappings where wallet_to = ?) \
+             group by height, tx_id \
+             order by height, tx_id"
+        );
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(project)
+            .fetch_all::<DelegationMappingTxRef>()
+            .await?;
+        Ok(rows)
+    }
+
+    /// delegation mapping txs up to `to_ms` of the wallets that sent one in
+    /// `[from_ms, to_ms)`, per wallet oldest first. the older txs carry the
+    /// state the window's changes start from
+    pub async fn delegation_mapping_txs_between(
+        &self,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> Result<Vec<DelegationMappingTx>, Error> {
+        let sql = format!(
+            "select min({MAPPING_BLOCK_TS}) as ts, height, tx_id, wallet_from as wallet, \
+                groupArray((wallet_to, factor)) as preferences \
+             from delegation_mappings \
+             where {MAPPING_BLOCK_TS} < fromUnixTimestamp64Milli(toInt64(?)) and wallet_from in \
+                (select wallet_from from delegation_mappings \
+                 where {MAPPING_BLOCK_TS} >= fromUnixTimestamp64Milli(toInt64(?)) \
+                 and {MAPPING_BLOCK_TS} < fromUnixTimestamp64Milli(toInt64(?))) \
+             group by height, tx_id, wallet_from \
+             order by wallet_from, height, tx_id"
+        );
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(to_ms)
+            .bind(from_ms)
+            .bind(to_ms)
+            .fetch_all::<DelegationMappingTx>()
+            .await?;
+        Ok(rows)
+    }
+
     pub async fn recent_mainnet_messages(
         &self,
         protocol: Option<&str>,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<MainnetMessage>, Error> {
-        let where_clause = if protocol.is_some() {
-            " where m.protocol = ?"
+        let mut clauses = Vec::new();
+        if protocol.is_some() {
+            clauses.push("m.protocol = ?");
+        }
+        if cursor.is_some() {
+            clauses.push("(m.block_height, m.msg_id) < (?, ?)");
+        }
+        let where_clause = if clauses.is_empty() {
+            String::new()
         } else {
-            ""
+            format!(" where {}", clauses.join(" and "))
         };
         let sql = format!(
             "select \
@@ -305,6 +1124,9 @@ impl AtlasIndexerClient {
         if let Some(p) = protocol {
             query = query.bind(p);
         }
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(&c.id);
+        }
         let rows = query.bind(limit).fetch_all::<MainnetMessageRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
@@ -314,11 +1136,15 @@ impl AtlasIndexerClient {
         protocol: Option<&str>,
         height: u32,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<MainnetMessage>, Error> {
         let mut clauses = vec!["m.block_height = ?".to_string()];
         if protocol.is_some() {
             clauses.push("m.protocol = ?".into());
         }
+        if cursor.is_some() {
+            clauses.push("m.msg_id > ?".into());
+        }
         let where_clause = format!(" where {}", clauses.join(" and "));
         let sql = format!(
             "select \
@@ -337,6 +1163,9 @@ impl AtlasIndexerClient {
         if let Some(p) = protocol {
             query = query.bind(p);
         }
+        if let Some(c) = cursor {
+            query = query.bind(&c.id);
+        }
         let rows = query.bind(limit).fetch_all::<MainnetMessageRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
@@ -347,19 +1176,22 @@ impl AtlasIndexerClient {
         tag_keys: &[String],
         tag_value: &str,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<MainnetMessage>, Error> {
         if tag_keys.is_empty() {
             return Ok(Vec::new());
         }
-        let placeholders = std::iter::repeat("?")
-            .take(tag_keys.len())
-            .collect::<Vec<_>>()
-            .join(", ");
+        let placeholders = placeholders(tag_keys.len());
         let protocol_clause = if protocol.is_some() {
             " and m.protocol = ?"
         } else {
             ""
         };
+        let cursor_clause = if cursor.is_some() {
+            " and (m.block_height, m.msg_id) < (?, ?)"
+        } else {
+            ""
+        };
         let sql = format!(
             "select \
                 m.protocol, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
@@ -370,7 +1202,7 @@ impl AtlasIndexerClient {
                on filter.protocol = m.protocol and filter.block_height = m.block_height and filter.msg_id = m.msg_id \
              left join ao_mainnet_message_tags t \
                on t.protocol = m.protocol and t.block_height = m.block_height and t.msg_id = m.msg_id \
-             where filter.tag_key in ({placeholders}) and filter.tag_value = ?{protocol_clause} \
+             where filter.tag_key in ({placeholders}) and filter.tag_value = ?{protocol_clause}{cursor_clause} \
              group by m.protocol, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
              order by m.block_height desc, m.msg_id desc \
              limit ?"
@@ -383,6 +1215,9 @@ impl AtlasIndexerClient {
         if let Some(p) = protocol {
             query = query.bind(p);
         }
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(&c.id);
+        }
         let rows = query.bind(limit).fetch_all::<MainnetMessageRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
@@ -424,20 +1259,29 @@ impl AtlasIndexerClient {
             .collect())
     }
 
-    pub async fn mainnet_explorer_blocks(&self, limit: u64) -> Result<Vec<ExplorerBlock>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select ts, height, tx_count, eval_count, transfer_count, \
-                 new_process_count, new_module_count, active_users, active_processes, \
-                 tx_count_rolling, processes_rolling, modules_rolling \
-                 from ao_mainnet_explorer \
-                 order by height desc \
-                 limit ?",
-            )
-            .bind(limit)
-            .fetch_all::<ExplorerBlockRow>()
-            .await?;
+    pub async fn mainnet_explorer_blocks(
+        &self,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<ExplorerBlock>, Error> {
+        let cursor_clause = if cursor.is_some() {
+            " where height < ?"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select ts, height, tx_count, eval_count, transfer_count, \
+             new_process_count, new_module_count, active_users, active_processes, \
+             tx_count_rolling, processes_rolling, modules_rolling \
+             from ao_mainnet_explorer{cursor_clause} \
+             order by height desc \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key);
+        }
+        let rows = query.bind(limit).fetch_all::<ExplorerBlockRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
 
@@ -502,23 +1346,32 @@ impl AtlasIndexerClient {
     pub async fn mainnet_recent_explorer_days(
         &self,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<ExplorerDayStats>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select toInt64(toUnixTimestamp(toStartOfDay(ts))) as day_ts, \
-                 count() as blocks, sum(tx_count) as txs, \
-                 sum(eval_count) as evals, sum(transfer_count) as transfers, \
-                 sum(new_process_count) as new_processes, sum(new_module_count) as new_modules, \
-                 sum(active_users) as active_users, sum(active_processes) as active_processes, \
-                 max(tx_count_rolling) as txs_roll, \
-                 max(processes_rolling) as processes_roll, \
-                 max(modules_rolling) as modules_roll \
-                 from ao_mainnet_explorer \
-                 group by day_ts \
-                 order by day_ts desc \
-                 limit ?",
-            )
+        let cursor_clause = if cursor.is_some() {
+            " having day_ts < ?"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select toInt64(toUnixTimestamp(toStartOfDay(ts))) as day_ts, \
+             count() as blocks, sum(tx_count) as txs, \
+             sum(eval_count) as evals, sum(transfer_count) as transfers, \
+             sum(new_process_count) as new_processes, sum(new_module_count) as new_modules, \
+             sum(active_users) as active_users, sum(active_processes) as active_processes, \
+             max(tx_count_rolling) as txs_roll, \
+             max(processes_rolling) as processes_roll, \
+             max(modules_rolling) as modules_roll \
+             from ao_mainnet_explorer \
+             group by day_ts{cursor_clause} \
+             order by day_ts desc \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key as i64);
+        }
+        let rows = query
             .bind(limit)
             .fetch_all::<ExplorerRecentDayRow>()
             .await?;
@@ -742,14 +1595,14 @@ impl AtlasIndexerClient {
                 .into_iter()
                 .map(|row| AoTokenQuantityRank {
                     address: row.address,
-                    total_quantity: format_quantity_human(row.total_quantity),
+                    total_quantity: Amount::new(row.total_quantity, AO_DECIMALS).to_string(),
                 })
                 .collect(),
             top_receivers: receivers
                 .into_iter()
                 .map(|row| AoTokenQuantityRank {
                     address: row.address,
-                    total_quantity: format_quantity_human(row.total_quantity),
+                    total_quantity: Amount::new(row.total_quantity, AO_DECIMALS).to_string(),
                 })
                 .collect(),
         })
@@ -770,7 +1623,7 @@ impl AtlasIndexerClient {
         sender: Option<&str>,
         order: Option<&str>,
         limit: u64,
-        offset: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<AoTokenMessage>, Error> {
         let mut joins = Vec::new();
         let mut where_clauses = Vec::new();
@@ -846,6 +1699,19 @@ impl AtlasIndexerClient {
             where_clauses.push("m.block_height <= ?");
             where_binds.push(BindValue::U32(val));
         }
+        let order_dir = match order {
+            Some("asc") => "asc",
+            _ => "desc",
+        };
+        if let Some(c) = cursor {
+            where_clauses.push(if order_dir == "asc" {
+                "(m.block_height, m.msg_id) > (?, ?)"
+            } else {
+                "(m.block_height, m.msg_id) < (?, ?)"
+            });
+            where_binds.push(BindValue::U64(c.key));
+            where_binds.push(BindValue::Str(c.id.clone()));
+        }
 
         let join_clause = if joins.is_empty() {
             String::new()
@@ -857,10 +1723,6 @@ impl AtlasIndexerClient {
         } else {
             format!("\nwhere {}", where_clauses.join(" and "))
         };
-        let order_dir = match order {
-            Some("asc") => "asc",
-            _ => "desc",
-        };
         let sql = format!(
             "select \
                 m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
@@ -873,17 +1735,13 @@ impl AtlasIndexerClient {
              {where_clause} \
              group by m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
              order by m.block_height {order_dir}, m.msg_id {order_dir} \
-             limit ? offset ?"
+             limit ?"
         );
         let mut query = self.client.query(&sql);
         for bind in join_binds.into_iter().chain(where_binds.into_iter()) {
             query = bind.apply(query);
         }
-        let rows = query
-            .bind(limit)
-            .bind(offset)
-            .fetch_all::<AoTokenMessageRow>()
-            .await?;
+        let rows = query.bind(limit).fetch_all::<AoTokenMessageRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
 
@@ -920,12 +1778,18 @@ impl AtlasIndexerClient {
         tag_key: &str,
         tag_value: &str,
         limit: u64,
+        cursor: Option<&Cursor>,
     ) -> Result<Vec<AoTokenMessage>, Error> {
         let source_clause = if source.is_some() {
             " and m.source = ?"
         } else {
             ""
         };
+        let cursor_clause = if cursor.is_some() {
+            " and (m.block_height, m.msg_id) < (?, ?)"
+        } else {
+            ""
+        };
         let sql = format!(
             "select \
                 m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, \
@@ -936,7 +1800,7 @@ impl AtlasIndexerClient {
                on filter.token = m.token and filter.source = m.source and filter.block_height = m.block_height and filter.msg_id = m.msg_id \
              left join ao_token_message_tags t \
                on t.token = m.token and t.source = m.source and t.block_height = m.block_height and t.msg_id = m.msg_id \
-             where m.token = ? and filter.tag_key = ? and filter.tag_value = ?{source_clause} \
+             where m.token = ? and filter.tag_key = ? and filter.tag_value = ?{source_clause}{cursor_clause} \
              group by m.source, m.block_height, m.block_timestamp, m.msg_id, m.owner, m.recipient, m.bundled_in, m.data_size, m.ts \
              order by m.block_height desc, m.msg_id desc \
              limit ?"
@@ -950,24 +1814,36 @@ impl AtlasIndexerClient {
         if let Some(src) = source {
             query = query.bind(src);
         }
+        if let Some(c) = cursor {
+            query = query.bind(c.key).bind(&c.id);
+        }
         let rows = query.bind(limit).fetch_all::<AoTokenMessageRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
 
-    pub async fn latest_explorer_blocks(&self, limit: u64) -> Result<Vec<ExplorerBlock>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select ts, height, tx_count, eval_count, transfer_count, \
-                 new_process_count, new_module_count, active_users, active_processes, \
-                 tx_count_rolling, processes_rolling, modules_rolling \
-                 from atlas_explorer \
-                 order by height desc \
-                 limit ?",
-            )
-            .bind(limit)
-            .fetch_all::<ExplorerBlockRow>()
-            .await?;
+    pub async fn latest_explorer_blocks(
+        &self,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<ExplorerBlock>, Error> {
+        let cursor_clause = if cursor.is_some() {
+            " where height < ?"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select ts, height, tx_count, eval_count, transfer_count, \
+             new_process_count, new_module_count, active_users, active_processes, \
+             tx_count_rolling, processes_rolling, modules_rolling \
+             from atlas_explorer{cursor_clause} \
+             order by height desc \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key);
+        }
+        let rows = query.bind(limit).fetch_all::<ExplorerBlockRow>().await?;
         Ok(rows.into_iter().map(|row| row.into()).collect())
     }
 
@@ -1026,23 +1902,35 @@ impl AtlasIndexerClient {
         })
     }
 
-    pub async fn recent_explorer_days(&self, limit: u64) -> Result<Vec<ExplorerDayStats>, Error> {
-        let rows = self
-            .client
-            .query(
-                "select toInt64(toUnixTimestamp(toStartOfDay(ts))) as day_ts, \
-                 count() as blocks, sum(tx_count) as txs, \
-                 sum(eval_count) as evals, sum(transfer_count) as transfers, \
-                 sum(new_process_count) as new_processes, sum(new_module_count) as new_modules, \
-                 sum(active_users) as active_users, sum(active_processes) as active_processes, \
-                 max(tx_count_rolling) as txs_roll, \
-                 max(processes_rolling) as processes_roll, \
-                 max(modules_rolling) as modules_roll \
-                 from atlas_explorer \
-                 group by day_ts \
-                 order by day_ts desc \
-                 limit ?",
-            )
+    pub async fn recent_explorer_days(
+        &self,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<Vec<ExplorerDayStats>, Error> {
+        let cursor_clause = if cursor.is_some() {
+            " having day_ts < ?"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "select toInt64(toUnixTimestamp(toStartOfDay(ts))) as day_ts, \
+             count() as blocks, sum(tx_count) as txs, \
+             sum(eval_count) as evals, sum(transfer_count) as transfers, \
+             sum(new_process_count) as new_processes, sum(new_module_count) as new_modules, \
+             sum(active_users) as active_users, sum(active_processes) as active_processes, \
+             max(tx_count_rolling) as txs_roll, \
+             max(processes_rolling) as processes_roll, \
+             max(modules_rolling) as modules_roll \
+             from atlas_explorer \
+             group by day_ts{cursor_clause} \
+             order by day_ts desc \
+             limit ?"
+        );
+        let mut query = self.client.query(&sql);
+        if let Some(c) = cursor {
+            query = query.bind(c.key as i64);
+        }
+        let rows = query
             .bind(limit)
             .fetch_all::<ExplorerRecentDayRow>()
             .await?;
@@ -1068,94 +1956,191 @@ impl AtlasIndexerClient {
     }
 }
 
-async fn ensure_schema(
-    admin: &clickhouse::Client,
-    client: &clickhouse::Client,
-    database: &str,
-) -> Result<(), Error> {
-    let create_db = format!("create database if not exists {database}");
-    admin.query(&create_db).execute().await?;
-    let stmts = [
-        "create table if not exists oracle_snapshots(ts DateTime64(3), ticker String, tx_id String) engine=MergeTree order by (ticker, ts)",
-        "create table if not exists wallet_balances(ts DateTime64(3), ticker String, wallet String, eoa String, amount String, tx_id String) engine=ReplacingMergeTree order by (ticker, wallet, ts)",
-        "create table if not exists wallet_delegations(ts DateTime64(3), wallet String, payload String) engine=ReplacingMergeTree order by (wallet, ts)",
-        "create table if not exists flp_positions(ts DateTime64(3), ticker String, wallet String, eoa String, project String, factor UInt32, amount String) engine=ReplacingMergeTree order by (project, wallet, ts)",
-        "create table if not exists delegation_mappings(ts DateTime64(3), height UInt32, tx_id String, wallet_from String, wallet_to String, factor UInt32) engine=ReplacingMergeTree order by (height, tx_id, wallet_from, wallet_to)",
-        "create table if not exists atlas_explorer(ts DateTime64(3), height UInt64, tx_count UInt64, eval_count UInt64, transfer_count UInt64, new_process_count UInt64, new_module_count UInt64, active_users UInt64, active_processes UInt64, tx_count_rolling UInt64, processes_rolling UInt64, modules_rolling UInt64) engine=ReplacingMergeTree order by height",
-        "create table if not exists ao_token_messages(ts DateTime64(3), token String, source String, block_height UInt32, block_timestamp UInt64, msg_id String, owner String, recipient String, bundled_in String, data_size String) engine=ReplacingMergeTree order by (token, source, block_height, msg_id)",
-        "create table if not exists ao_token_message_tags(ts DateTime64(3), token String, source String, block_height UInt32, msg_id String, tag_key String, tag_value String) engine=ReplacingMergeTree order by (token, source, tag_key, tag_value, block_height, msg_id)",
-        "create table if not exists ao_token_block_state(token String, last_complete_height UInt32, updated_at DateTime64(3)) engine=ReplacingMergeTree order by (token, updated_at)",
-    ];
-    for stmt in stmts {
-        client.query(stmt).execute().await?;
-    }
-    let alters = [
-        "alter table wallet_balances add column if not exists eoa String after wallet",
-        "alter table wallet_balances add column if not exists ar_balance String after amount",
-        "alter table flp_positions add column if not exists eoa String after wallet",
-        "alter table flp_positions add column if not exists ar_amount String after amount",
-        "alter table flp_positions modify column project String",
-        "alter table delegation_mappings add column if not exists ts DateTime64(3) default now()",
-        "alter table ao_token_messages add column if not exists token String default 'ao'",
-        "alter table ao_token_message_tags add column if not exists token String default 'ao'",
-        "alter table ao_token_block_state add column if not exists token String default 'ao'",
-    ];
-    for stmt in alters {
-        client.query(stmt).execute().await?;
-    }
-    Ok(())
-}
-
-fn aggregate_totals(rows: &[FlpPositionRow]) -> Vec<ProjectTotal> {
+fn aggregate_totals(rows: &[FlpPositionRow]) -> Result<Vec<ProjectTotal>, Error> {
     let mut map = BTreeMap::new();
     for row in rows {
+        let decimals = lst_decimals(&row.ticker)
+            .ok_or_else(|| anyhow!("unknown oracle ticker {}", row.ticker))?;
         let entry = map.entry(row.ticker.clone()).or_insert(ProjectTotal {
             ticker: row.ticker.clone(),
-            amount: 0.0,
-            ar_amount: 0.0,
+            amount: Amount::zero(decimals),
+            ar_amount: Amount::zero(AR_DECIMALS),
             delegators_count: 0,
         });
-        entry.amount += row.amount.parse::<f64>().unwrap_or(0.0);
-        entry.ar_amount += row.ar_amount.parse::<f64>().unwrap_or(0.0);
+        let overflow = || anyhow!("{} totals overflow", row.ticker);
+        entry.amount = entry
+            .amount
+            .checked_add(&stored_amount(&row.amount, decimals)?)
+            .ok_or_else(overflow)?;
+        entry.ar_amount = entry
+            .ar_amount
+            .checked_add(&stored_amount(&row.ar_amount, AR_DECIMALS)?)
+            .ok_or_else(overflow)?;
         entry.delegators_count += 1;
     }
-    map.into_values().collect()
+    Ok(map.into_values().collect())
 }
 
 #[derive(Row, serde::Deserialize)]
 struct FlpPositionRow {
-    #[allow(dead_code)]
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     ts: DateTime<Utc>,
     ticker: String,
     wallet: String,
     eoa: String,
-    #[allow(dead_code)]
     project: String,
     factor: u32,
     amount: String,
     ar_amount: String,
 }
 
+#[derive(Clone, Debug)]
+pub struct WalletCycleStake {
+    pub ts: DateTime<Utc>,
+    pub ticker: String,
+    pub wallet_amount: Amount,
+    pub total_amount: Amount,
+}
+
+/// amounts are `Decimal(38, 18)` base units
+#[derive(Row, serde::Deserialize)]
+struct WalletCycleStakeRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    ticker: String,
+    wallet_amount: i128,
+    total_amount: i128,
+}
+
+impl From<WalletCycleStakeRow> for WalletCycleStake {
+    fn from(row: WalletCycleStakeRow) -> Self {
+        WalletCycleStake {
+            ts: row.ts,
+            ticker: row.ticker,
+            wallet_amount: Amount::new(row.wallet_amount.max(0) as u128, LST_DECIMALS),
+            total_amount: Amount::new(row.total_amount.max(0) as u128, LST_DECIMALS),
+        }
+    }
+}
+
+/// a wallet's delegation to one FLP in the latest cycle of a ticker
+#[derive(Clone, Debug)]
+pub struct FlpPositionShare {
+    pub wallet: String,
+    pub ticker: String,
+    pub project: String,
+    pub factor: u32,
+    pub amount: Amount,
+}
+
+/// `amount` is `Decimal(38, 18)` base units
+#[derive(Row, serde::Deserialize)]
+struct FlpPositionShareRow {
+    wallet: String,
+    ticker: String,
+    project: String,
+    factor: u32,
+    amount: i128,
+}
+
+impl From<FlpPositionShareRow> for FlpPositionShare {
+    fn from(row: FlpPositionShareRow) -> Self {
+        FlpPositionShare {
+            wallet: row.wallet,
+            ticker: row.ticker,
+            project: row.project,
+            factor: row.factor,
+            amount: Amount::new(row.amount.max(0) as u128, LST_DECIMALS),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FlpCycleProjectTotal {
+    pub ticker: String,
+    pub ts: DateTime<Utc>,
+    pub project: String,
+    pub amount: Amount,
+    pub ar_amount: Amount,
+    pub delegators: u64,
+}
+
+/// `amount` and `ar_amount` are `Decimal(38, 18)` and `Decimal(38, 12)` base
+/// units
+#[derive(Row, serde::Deserialize)]
+struct FlpCycleTotalRow {
+    ticker: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    project: String,
+    amount: i128,
+    ar_amount: i128,
+    delegators: u64,
+}
+
+impl From<FlpCycleTotalRow> for FlpCycleProjectTotal {
+    fn from(row: FlpCycleTotalRow) -> Self {
+        FlpCycleProjectTotal {
+            ticker: row.ticker,
+            ts: row.ts,
+            project: row.project,
+            amount: Amount::new(row.amount.max(0) as u128, LST_DECIMALS),
+            ar_amount: Amount::new(row.ar_amount.max(0) as u128, AR_DECIMALS),
+            delegators: row.delegators,
+        }
+    }
+}
+
+#[derive(Row, serde::Deserialize)]
+struct WalletBalanceRow {
+    ticker: String,
+    eoa: String,
+    amount: String,
+    ar_balance: String,
+    tx_id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct TokenActivityRow {
+    token: String,
+    source: String,
+    block_height: u32,
+    block_timestamp: u64,
+    msg_id: String,
+    owner: String,
+    recipient: String,
+    bundled_in: String,
+    data_size: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    tags: Vec<(String, String)>,
+}
+
 #[derive(Row, serde::Deserialize)]
 struct IdentityRow {
     wallet: String,
+    /// as stored, rows indexed before EIP-55 normalization hold lowercase
+    /// EOAs and older ones an empty string
     eoa: String,
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     ts: DateTime<Utc>,
 }
 
-impl From<IdentityRow> for IdentityLink {
-    fn from(value: IdentityRow) -> Self {
-        IdentityLink {
-            wallet: value.wallet,
-            eoa: value.eoa,
-            ts: value.ts,
-        }
+impl IdentityRow {
+    /// the link with a checksummed EOA, `None` for rows without a valid one
+    fn link(self) -> Option<IdentityLink> {
+        let eoa = EvmAddress::parse(&self.eoa).ok()?;
+        Some(IdentityLink {
+            wallet: self.wallet,
+            eoa: eoa.into(),
+            ts: self.ts,
+        })
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema, SimpleObject)]
 pub struct ProjectSnapshot {
     pub project: String,
     pub ts: DateTime<Utc>,
@@ -1163,15 +2148,33 @@ pub struct ProjectSnapshot {
     pub delegators: Vec<Delegator>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct ProjectTotal {
     pub ticker: String,
-    pub amount: f64,
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub amount: Amount,
     pub delegators_count: u32,
-    pub ar_amount: f64,
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub ar_amount: Amount,
+}
+
+#[ComplexObject]
+impl ProjectTotal {
+    async fn amount(&self) -> String {
+        self.amount.to_string()
+    }
+
+    async fn ar_amount(&self) -> String {
+        self.ar_amount.to_string()
+    }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct Delegator {
     pub wallet: String,
     pub eoa: String,
@@ -1181,24 +2184,199 @@ pub struct Delegator {
     pub ar_amount: String,
 }
 
-#[derive(Serialize, Clone)]
+/// a wallet's LST balance in the latest oracle cycle it appears in
+#[derive(Serialize, Clone, ToSchema)]
+pub struct WalletBalance {
+    pub ticker: String,
+    pub eoa: String,
+    pub amount: String,
+    /// AR balance snapshotted with the cycle, empty on rows indexed before it
+    /// was tracked
+    pub ar_balance: String,
+    pub tx_id: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
+    pub ts: DateTime<Utc>,
+}
+
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
+pub struct WalletPosition {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
+    pub ts: DateTime<Utc>,
+    pub project: String,
+    pub ticker: String,
+    pub factor: u32,
+    pub amount: String,
+    pub ar_amount: String,
+}
+
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct IdentityLink {
     pub wallet: String,
     pub eoa: String,
     #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
     pub ts: DateTime<Utc>,
 }
 
-#[derive(Row, serde::Deserialize, Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct OracleSnapshot {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    #[schema(value_type = i64)]
     pub ts: DateTime<Utc>,
     pub ticker: String,
     pub tx_id: String,
-    pub total: f64,
+    /// LST delegated in the cycle
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub total: Amount,
     pub delegators: u64,
 }
 
+#[ComplexObject]
+impl OracleSnapshot {
+    async fn total(&self) -> String {
+        self.total.to_string()
+    }
+}
+
+/// `total` is `Decimal(38, 18)` base units
+#[derive(Row, serde::Deserialize)]
+struct OracleSnapshotRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    ticker: String,
+    tx_id: String,
+    total: i128,
+    delegators: u64,
+}
+
+impl From<OracleSnapshotRow> for OracleSnapshot {
+    fn from(row: OracleSnapshotRow) -> Self {
+        OracleSnapshot {
+            ts: row.ts,
+            ticker: row.ticker,
+            tx_id: row.tx_id,
+            total: Amount::new(row.total.max(0) as u128, LST_DECIMALS),
+            delegators: row.delegators,
+        }
+    }
+}
+
+/// payload served from the index or, with `?live=true`, from the gateway
+#[derive(Serialize, Clone, ToSchema)]
+pub struct Sourced {
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+    /// `index` or `live`
+    pub source: &'static str,
+    /// when the payload was indexed, null for live responses
+    #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    #[schema(value_type = Option<i64>)]
+    pub indexed_at: Option<DateTime<Utc>>,
+    /// Arweave tx the payload was read from, when known
+    pub tx_id: Option<String>,
+    /// CSV rows left out of `data` as malformed, for parsed CSV payloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub rejected: Option<Vec<RejectedRow>>,
+}
+
+impl Sourced {
+    pub fn indexed(data: serde_json::Value, at: DateTime<Utc>, tx_id: Option<String>) -> Self {
+        Sourced {
+            data,
+            source: "index",
+            indexed_at: Some(at),
+            tx_id,
+            rejected: None,
+        }
+    }
+
+    pub fn live(data: serde_json::Value, tx_id: Option<String>) -> Self {
+        Sourced {
+            data,
+            source: "live",
+            indexed_at: None,
+            tx_id,
+            rejected: None,
+        }
+    }
+
+    pub fn with_rejected(mut self, rejected: Vec<RejectedRow>) -> Self {
+        self.rejected = Some(rejected);
+        self
+    }
+}
+
+#[derive(Row, serde::Deserialize, Clone, Copy, Default, Debug)]
+pub struct IndexHeights {
+    pub mainnet_explorer: u64,
+    pub atlas_explorer: u64,
+    pub ao: u64,
+    pub pi: u64,
+    /// ms timestamp of the latest oracle snapshot
+    pub flp: u64,
+    pub delegation_mappings: u64,
+}
+
+#[derive(Row, serde::Deserialize, Debug)]
+pub struct ApiKeyRow {
+    pub key_hash: String,
+    pub name: String,
+    pub rpm: u32,
+    pub admin: bool,
+    pub enabled: bool,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct MsRow {
+    ms: i64,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct HeightRow {
+    height: u32,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct PayloadRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    payload: String,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct MintingReportRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    /// as stored, not validated
+    report_id: String,
+    payload: String,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct CsvRejectionRow {
+    line: u64,
+    raw: String,
+    reason: String,
+}
+
+#[derive(Row, serde::Deserialize)]
+struct TxIdRow {
+    tx_id: String,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct OracleFeed {
+    pub oracle_pid: String,
+    pub oracle_evm_address: String,
+    pub recent_indexed_feeds: Vec<OracleSnapshot>,
+}
+
 #[derive(Row, serde::Deserialize)]
 struct DelegationMappingRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
@@ -1210,7 +2388,29 @@ struct DelegationMappingRow {
     factor: u32,
 }
 
-#[derive(Serialize, Clone)]
+/// one wallet's full preference set as of a delegation mapping tx
+#[derive(Row, serde::Deserialize, Clone, Debug)]
+pub struct DelegationMappingTxRef {
+    pub height: u32,
+    pub tx_id: String,
+    /// block time
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+}
+
+#[derive(Row, serde::Deserialize, Clone, Debug)]
+pub struct DelegationMappingTx {
+    /// block time
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ts: DateTime<Utc>,
+    pub height: u32,
+    pub tx_id: String,
+    pub wallet: String,
+    /// `(wallet_to, factor)` pairs
+    pub preferences: Vec<(String, u32)>,
+}
+
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct DelegationMappingHistory {
     pub ts: DateTime<Utc>,
     pub height: u32,
@@ -1219,7 +2419,7 @@ pub struct DelegationMappingHistory {
     pub preferences: Vec<DelegationPreference>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct DelegationPreference {
     pub wallet_to: String,
     pub factor: u32,
@@ -1231,7 +2431,7 @@ struct DelegationHeightRow {
     tx_id: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct DelegationHeight {
     pub height: u32,
     pub tx_id: String,
@@ -1245,7 +2445,7 @@ struct MultiDelegatorRow {
     projects: Vec<String>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct MultiDelegator {
     pub wallet: String,
     pub eoa: String,
@@ -1253,14 +2453,64 @@ pub struct MultiDelegator {
     pub projects: Vec<String>,
 }
 
-#[derive(Row, serde::Deserialize, Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct ProjectCycleTotal {
     pub tx_id: String,
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    #[schema(value_type = i64)]
     pub ts: DateTime<Utc>,
-    pub usds_total: f64,
-    pub dai_total: f64,
-    pub steth_total: f64,
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub usds_total: Amount,
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub dai_total: Amount,
+    #[serde(serialize_with = "serialize_human")]
+    #[schema(value_type = String)]
+    #[graphql(skip)]
+    pub steth_total: Amount,
+}
+
+#[ComplexObject]
+impl ProjectCycleTotal {
+    async fn usds_total(&self) -> String {
+        self.usds_total.to_string()
+    }
+
+    async fn dai_total(&self) -> String {
+        self.dai_total.to_string()
+    }
+
+    async fn steth_total(&self) -> String {
+        self.steth_total.to_string()
+    }
+}
+
+/// totals are `Decimal(38, 18)` base units
+#[derive(Row, serde::Deserialize)]
+struct ProjectCycleTotalRow {
+    tx_id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    ts: DateTime<Utc>,
+    usds_total: i128,
+    dai_total: i128,
+    steth_total: i128,
+}
+
+impl From<ProjectCycleTotalRow> for ProjectCycleTotal {
+    fn from(row: ProjectCycleTotalRow) -> Self {
+        let lst = |raw: i128| Amount::new(raw.max(0) as u128, LST_DECIMALS);
+        ProjectCycleTotal {
+            tx_id: row.tx_id,
+            ts: row.ts,
+            usds_total: lst(row.usds_total),
+            dai_total: lst(row.dai_total),
+            steth_total: lst(row.steth_total),
+        }
+    }
 }
 
 #[derive(Row, serde::Deserialize)]
@@ -1299,9 +2549,10 @@ impl From<ExplorerBlockRow> for ExplorerBlock {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct ExplorerBlock {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    #[schema(value_type = i64)]
     pub ts: DateTime<Utc>,
     pub height: u64,
     pub tx_count: u64,
@@ -1331,7 +2582,7 @@ struct ExplorerDayAggRow {
     modules_roll: u64,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct ExplorerDayStats {
     pub day: NaiveDate,
     pub processed_blocks: u64,
@@ -1384,7 +2635,7 @@ impl From<MainnetMessageRow> for MainnetMessage {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct MainnetMessage {
     pub protocol: String,
     pub block_height: u32,
@@ -1396,10 +2647,11 @@ pub struct MainnetMessage {
     pub data_size: String,
     pub tags: Vec<MainnetMessageTag>,
     #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
     pub indexed_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct MainnetMessageTag {
     pub key: String,
     pub value: String,
@@ -1442,7 +2694,7 @@ impl From<AoTokenMessageRow> for AoTokenMessage {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenMessage {
     pub source: String,
     pub block_height: u32,
@@ -1454,10 +2706,11 @@ pub struct AoTokenMessage {
     pub data_size: String,
     pub tags: Vec<AoTokenMessageTag>,
     #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
     pub indexed_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenMessageTag {
     pub key: String,
     pub value: String,
@@ -1489,15 +2742,17 @@ struct AoTokenStateRow {
     updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenIndexingInfo {
     pub start_height: u32,
     pub arweave_tip: Option<u64>,
     pub last_processed_height: Option<u32>,
     #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    #[schema(value_type = Option<i64>)]
     pub last_processed_at: Option<DateTime<Utc>>,
     pub max_block_height: Option<u32>,
     #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    #[schema(value_type = Option<i64>)]
     pub latest_indexed_at: Option<DateTime<Utc>>,
     pub total_messages: u64,
     pub transfer_messages: u64,
@@ -1511,19 +2766,19 @@ struct AoTokenTagCountRow {
     cnt: u64,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenActionCount {
     pub action: String,
     pub count: u64,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenTagCount {
     pub value: String,
     pub count: u64,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenFrequencyInfo {
     pub actions: Vec<AoTokenActionCount>,
     pub top_senders: Vec<AoTokenTagCount>,
@@ -1536,32 +2791,18 @@ struct AoTokenSumRow {
     total_quantity: u128,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenQuantityRank {
     pub address: String,
     pub total_quantity: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema, SimpleObject)]
 pub struct AoTokenRichlist {
     pub top_spenders: Vec<AoTokenQuantityRank>,
     pub top_receivers: Vec<AoTokenQuantityRank>,
 }
 
-fn format_quantity_human(value: u128) -> String {
-    let scale: u128 = 1_000_000_000_000;
-    let whole = value / scale;
-    let frac = value % scale;
-    if frac == 0 {
-        return whole.to_string();
-    }
-    let mut frac_str = format!("{frac:012}");
-    while frac_str.ends_with('0') {
-        frac_str.pop();
-    }
-    format!("{whole}.{frac_str}")
-}
-
 impl From<MainnetProgressRow> for MainnetProtocolInfo {
     fn from(row: MainnetProgressRow) -> Self {
         let protocol = row.protocol;
@@ -1577,15 +2818,17 @@ impl From<MainnetProgressRow> for MainnetProtocolInfo {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, ToSchema)]
 pub struct MainnetProtocolInfo {
     pub protocol: String,
     pub block_height: u32,
     pub start_height: u32,
     #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
     pub indexed_at: DateTime<Utc>,
     pub last_processed_height: Option<u32>,
     #[serde(with = "chrono::serde::ts_milliseconds_option")]
+    #[schema(value_type = Option<i64>)]
     pub last_processed_at: Option<DateTime<Utc>>,
     pub last_cursor: Option<String>,
 }
@@ -1629,3 +2872,55 @@ struct MainnetStateRow {
     #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
     updated_at: DateTime<Utc>,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::indexer::{FlpPositionRow, IdentityRow, aggregate_totals};
+    use chrono::Utc;
+
+    #[test]
+    fn legacy_identity_rows_test() {
+        let row = |eoa: &str| IdentityRow {
+            wallet: "4hXj_E-5fAKmo4E8KjgQvuDJKAFk9P2grhycVmISDLs".to_string(),
+            eoa: eoa.to_string(),
+            ts: Utc::now(),
+        };
+        // indexed before EOAs were stored
+        assert!(row("").link().is_none());
+        assert!(row("0xnot-an-eoa").link().is_none());
+        // indexed before EIP-55 normalization
+        let link = row("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed")
+            .link()
+            .unwrap();
+        assert_eq!(link.eoa, "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+    }
+
+    #[test]
+    fn exact_project_totals_test() {
+        let row = |ticker: &str, amount: &str, ar_amount: &str| FlpPositionRow {
+            ts: Utc::now(),
+            ticker: ticker.to_string(),
+            wallet: "wallet".to_string(),
+            eoa: "0x".to_string(),
+            project: "flp".to_string(),
+            factor: 10000,
+            amount: amount.to_string(),
+            ar_amount: ar_amount.to_string(),
+        };
+        let rows = vec![
+            row("dai", "9007199254.740993", "1.5"),
+            row("dai", "0.000000000000000001", ""),
+            row("usds", "2", "0.25"),
+        ];
+        let totals = aggregate_totals(&rows).unwrap();
+        // past f64's 53 bits
+        assert_eq!(
+            totals[0].amount.to_string(),
+            "9007199254.740993000000000001"
+        );
+        assert_eq!(totals[0].ar_amount.to_string(), "1.5");
+        assert_eq!(totals[0].delegators_count, 2);
+        assert_eq!(totals[1].amount.to_string(), "2");
+        assert!(aggregate_totals(&[row("dai", "lots", "0")]).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    errors::ServerError,
+    indexer::{AtlasIndexerClient, FlpCycleProjectTotal},
+};
+use chrono::{DateTime, Duration, Utc};
+use common::{
+    amount::{AR_DECIMALS, Amount, LST_DECIMALS},
+    projects::Project,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// history read for the deltas, the longest window plus slack for a late cycle
+const HISTORY_DAYS: u32 = 35;
+const DELTA_WINDOWS: [i64; 3] = [1, 7, 30];
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum LeaderboardSort {
+    #[default]
+    Amount,
+    ArAmount,
+    Delegators,
+}
+
+impl LeaderboardSort {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "amount" => Some(Self::Amount),
+            "ar_amount" => Some(Self::ArAmount),
+            "delegators" => Some(Self::Delegators),
+            _ => None,
+        }
+    }
+
+    fn key(&self, row: &FlpCycleProjectTotal) -> u128 {
+        match self {
+            Self::Amount => row.amount.raw,
+            Self::ArAmount => row.ar_amount.raw,
+            Self::Delegators => row.delegators as u128,
+        }
+    }
+}
+
+/// FLPs of one ticker ranked on its latest oracle cycle
+#[derive(Serialize, ToSchema, Debug)]
+pub struct FlpLeaderboard {
+    pub ticker: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
+    pub ts: DateTime<Utc>,
+    pub total_amount: String,
+    pub total_ar_amount: String,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: u32,
+    pub project: String,
+    /// FLP name, null for projects missing from the tracked metadata
+    pub name: Option<String>,
+    /// delegated LST amount
+    pub amount: String,
+    /// delegated AR
+    pub ar_amount: String,
+    pub delegators: u64,
+    /// share of the ticker's delegated amount, 0 to 1
+    pub share: f64,
+    pub deltas: LeaderboardDeltas,
+}
+
+/// change against the latest cycle at least 1, 7 and 30 days older, null
+/// when the index doesn't go back that far
+#[derive(Serialize, ToSchema, Debug, Default)]
+pub struct LeaderboardDeltas {
+    pub d1: Option<LeaderboardDelta>,
+    pub d7: Option<LeaderboardDelta>,
+    pub d30: Option<LeaderboardDelta>,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct LeaderboardDelta {
+    /// cycle compared against
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    #[schema(value_type = i64)]
+    pub since: DateTime<Utc>,
+    /// signed, e.g. `-12.5`
+    pub amount: String,
+    /// signed
+    pub ar_amount: String,
+    pub delegators: i64,
+    pub share: f64,
+}
+
+pub async fn flp_leaderboards(
+    indexer: &AtlasIndexerClient,
+    ticker: Option<&str>,
+    sort: LeaderboardSort,
+) -> Result<Vec<FlpLeaderboard>, ServerError> {
+    let rows = indexer.recent_flp_cycle_totals(HISTORY_DAYS).await?;
+    let names: HashMap<String, String> = Project::get_all()
+        .into_iter()
+        .map(|project| (project.pid, project.name))
+        .collect();
+    let mut by_ticker: HashMap<String, Vec<FlpCycleProjectTotal>> = HashMap::new();
+    for row in rows {
+        if ticker.is_some_and(|t| t != row.ticker) {
+            continue;
+        }
+        by_ticker.entry(row.ticker.clone()).or_default().push(row);
+    }
+    if by_ticker.is_empty() {
+        return Err(ServerError::not_found("no indexed oracle cycles"));
+    }
+    let mut boards: Vec<FlpLeaderboard> = by_ticker
+        .into_iter()
+        .filter_map(|(ticker, rows)| build_leaderboard(ticker, &rows, &names, sort))
+        .collect();
+    boards.sort_by(|a, b| a.ticker.cmp(&b.ticker));
+    Ok(boards)
+}
+
+/// ranks the FLPs of the newest cycle in `rows` (one ticker) and diffs them
+/// against older cycles
+fn build_leaderboard(
+    ticker: String,
+    rows: &[FlpCycleProjectTotal],
+    names: &HashMap<String, String>,
+    sort: LeaderboardSort,
+) -> Option<FlpLeaderboard> {
+    let latest = rows.iter().map(|row| row.ts).max()?;
+    let cycle = |ts: DateTime<Utc>| -> Vec<&FlpCycleProjectTotal> {
+        rows.iter().filter(|row| row.ts == ts).collect()
+    };
+    let mut current = cycle(latest);
+    current.sort_by(|a, b| {
+        sort.key(b)
+            .cmp(&sort.key(a))
+            .then_with(|| a.project.cmp(&b.project))
+    });
+    let total_amount = total(current.iter().map(|row| row.amount), LST_DECIMALS);
+    let total_ar_amount = total(current.iter().map(|row| row.ar_amount), AR_DECIMALS);
+    let references: Vec<Option<(DateTime<Utc>, Vec<&FlpCycleProjectTotal>)>> = DELTA_WINDOWS
+        .iter()
+        .map(|days| {
+            let cutoff = latest - Duration::days(*days);
+            rows.iter()
+                .map(|row| row.ts)
+                .filter(|ts| *ts <= cutoff)
+                .max()
+                .map(|ts| (ts, cycle(ts)))
+        })
+        .collect();
+    let entries: Vec<LeaderboardEntry> = current
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let share = ratio(row.amount, total_amount);
+            let mut deltas = references.iter().map(|reference| {
+                reference.as_ref().map(|(since, past)| {
+                    let past_total = total(past.iter().map(|p| p.amount), LST_DECIMALS);
+                    let before = past.iter().find(|p| p.project == row.project);
+                    let (amount, ar_amount, delegators) = before
+                        .map(|p| (p.amount, p.ar_amount, p.delegators))
+                        .unwrap_or((Amount::zero(LST_DECIMALS), Amount::zero(AR_DECIMALS), 0));
+                    LeaderboardDelta {
+                        since: *since,
+                        amount: signed_delta(row.amount, amount),
+                        ar_amount: signed_delta(row.ar_amount, ar_amount),
+                        delegators: row.delegators as i64 - delegators as i64,
+                        share: share - ratio(amount, past_total),
+                    }
+                })
+            });
+            LeaderboardEntry {
+                rank: idx as u32 + 1,
+                project: row.project.clone(),
+                name: names.get(&row.project).cloned(),
+                amount: row.amount.to_string(),
+                ar_amount: row.ar_amount.to_string(),
+                delegators: row.delegators,
+                share,
+                deltas: LeaderboardDeltas {
+                    d1: deltas.next().flatten(),
+                    d7: deltas.next().flatten(),
+                    d30: deltas.next().flatten(),
+                },
+            }
+        })
+        .collect();
+    Some(FlpLeaderboard {
+        ticker,
+        ts: latest,
+        total_amount: total_amount.to_string(),
+        total_ar_amount: total_ar_amount.to_string(),
+        entries,
+    })
+}
+
+fn total(amounts: impl Iterator<Item = Amount>, decimals: u8) -> Amount {
+    let raw = amounts.fold(0u128, |sum, amount| sum.saturating_add(amount.raw));
+    Amount::new(raw, decimals)
+}
+
+/// `now - before` as a human decimal, `-` prefixed when it shrank
+fn signed_delta(now: Amount, before: Amount) -> String {
+    if now.raw >= before.raw {
+        Amount::new(now.raw - before.raw, now.decimals).to_string()
+    } else {
+        format!("-{}", Amount::new(before.raw - now.raw, now.decimals))
+    }
+}
+
+fn ratio(part: Amount, total: Amount) -> f64 {
+    if total.is_zero() {
+        0.0
+    } else {
+        part.raw as f64 / total.raw as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        indexer::FlpCycleProjectTotal,
+        leaderboard::{LeaderboardSort, build_leaderboard},
+    };
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use common::amount::{AR_DECIMALS, Amount, LST_DECIMALS};
+    use std::collections::HashMap;
+
+    fn row(
+        ts: DateTime<Utc>,
+        project: &str,
+        amount: &str,
+        delegators: u64,
+    ) -> FlpCycleProjectTotal {
+        let amount = Amount::parse_human(amount, LST_DECIMALS).unwrap();
+        FlpCycleProjectTotal {
+            ticker: "usds".to_string(),
+            ts,
+            project: project.to_string(),
+            amount,
+            // a tenth of the LST amount
+            ar_amount: Amount::new(amount.raw / 10_000_000, AR_DECIMALS),
+            delegators,
+        }
+    }
+
+    #[test]
+    fn leaderboard_rank_and_deltas_test() {
+        let latest = Utc.timestamp_millis_opt(1_750_000_000_000).unwrap();
+        let day_before = latest - Duration::days(1);
+        let week_before = latest - Duration::days(8);
+        let rows = vec![
+            row(week_before, "a", "50", 5),
+            row(day_before, "a", "60", 6),
+            row(day_before, "b", "40", 2),
+            row(latest, "a", "75.000000000000000001", 7),
+            row(latest, "b", "25", 9),
+        ];
+        let names = HashMap::from([("a".to_string(), "Alpha".to_string())]);
+
+        let board =
+            build_leaderboard("usds".to_string(), &rows, &names, LeaderboardSort::Amount).unwrap();
+        assert_eq!(board.ts, latest);
+        // exact, beyond f64's precision
+        assert_eq!(board.total_amount, "100.000000000000000001");
+        let a = &board.entries[0];
+        assert_eq!((a.rank, a.project.as_str()), (1, "a"));
+        assert!((a.share - 0.75).abs() < 1e-9);
+        assert_eq!(a.name.as_deref(), Some("Alpha"));
+        let d1 = a.deltas.d1.as_ref().unwrap();
+        assert_eq!(
+            (d1.since, d1.amount.as_str(), d1.delegators),
+            (day_before, "15.000000000000000001", 1)
+        );
+        assert!((d1.share - 0.15).abs() < 1e-9);
+        // the 7d reference is the newest cycle at least 7 days older
+        assert_eq!(a.deltas.d7.as_ref().unwrap().since, week_before);
+        assert!(a.deltas.d30.is_none());
+        // absent from the reference cycle, the whole amount is new
+        let b = &board.entries[1];
+        assert_eq!(b.deltas.d7.as_ref().unwrap().amount, "25");
+        assert_eq!(b.deltas.d1.as_ref().unwrap().amount, "-15");
+
+        let board = build_leaderboard(
+            "usds".to_string(),
+            &rows,
+            &names,
+            LeaderboardSort::Delegators,
+        )
+        .unwrap();
+        assert_eq!(board.entries[0].project, "b");
+    }
+}
@@ -0,0 +1,371 @@
+use crate::{config::ApiConfig, errors::ServerError, indexer::AtlasIndexerClient, state::AppState};
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{Instrument, info_span, warn};
+use utoipa::ToSchema;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+const KEY_REFRESH: Duration = Duration::from_secs(60);
+const MAX_BUCKETS: usize = 10_000;
+const ANONYMOUS: &str = "anonymous";
+
+#[derive(Clone, Debug)]
+struct ApiKey {
+    name: String,
+    rpm: u32,
+    admin: bool,
+}
+
+/// token bucket holding up to `rpm` cost units, refilled continuously
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(rpm: u32, now: Instant) -> Self {
+        Bucket {
+            tokens: rpm as f64,
+            updated: now,
+        }
+    }
+
+    /// spends `cost` units, returns what is left or the seconds to wait
+    fn take(&mut self, cost: u32, rpm: u32, now: Instant) -> Result<u32, u64> {
+        let per_sec = rpm as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(rpm as f64);
+        self.updated = now;
+        // a route costing more than the whole budget would never pass
+        let cost = cost.min(rpm) as f64;
+        if self.tokens >= cost {
+            self.tokens -= cost;
+            Ok(self.tokens as u32)
+        } else {
+            Err(((cost - self.tokens) / per_sec).ceil() as u64)
+        }
+    }
+}
+
+/// usage of one API key since the server started, anonymous clients are
+/// summed under `anonymous`
+#[derive(Serialize, ToSchema, Clone, Default, Debug)]
+pub struct KeyUsage {
+    pub name: String,
+    pub requests: u64,
+    /// summed route costs of the accepted requests
+    pub cost: u64,
+    /// requests rejected with a 429
+    pub throttled: u64,
+    /// ms timestamp of the last request
+    pub last_seen: i64,
+}
+
+/// API keys, per client rate limits and usage counters, shared through `AppState`
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<ApiConfig>,
+    /// keyed by the hex sha256 of the secret
+    keys: Arc<RwLock<HashMap<String, ApiKey>>>,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+    usage: Arc<Mutex<HashMap<String, KeyUsage>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: ApiConfig) -> Self {
+        let limiter = RateLimiter {
+            keys: Arc::new(RwLock::new(HashMap::new())),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            usage: Arc::new(Mutex::new(HashMap::new())),
+            config: Arc::new(config),
+        };
+        *limiter.keys.write().unwrap() = limiter.configured_keys();
+        limiter
+    }
+
+    fn configured_keys(&self) -> HashMap<String, ApiKey> {
+        self.config
+            .keys
+            .iter()
+            .map(|key| {
+                let api_key = ApiKey {
+                    name: key.name.clone(),
+                    rpm: key.rpm.unwrap_or(self.config.key_rpm),
+                    admin: key.admin,
+                };
+                (hash_key(&key.key), api_key)
+            })
+            .collect()
+    }
+
+    /// reloads the `api_keys` table on top of the atlas.toml keys, a disabled
+    /// row revokes the key even if atlas.toml still lists it
+    pub fn spawn_key_refresh(&self, indexer: AtlasIndexerClient) {
+        let limiter = self.clone();
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval(KEY_REFRESH);
+                loop {
+                    interval.tick().await;
+                    let rows = match indexer.api_keys().await {
+                        Ok(rows) => rows,
+                        Err(err) => {
+                            warn!(error = ?err, "api keys refresh failed");
+                            continue;
+                        }
+                    };
+                    let mut keys = limiter.configured_keys();
+                    for row in rows {
+                        if !row.enabled {
+                            keys.remove(&row.key_hash);
+                            continue;
+                        }
+                        let rpm = if row.rpm == 0 {
+                            limiter.config.key_rpm
+                        } else {
+                            row.rpm
+                        };
+                        let api_key = ApiKey {
+                            name: row.name,
+                            rpm,
+                            admin: row.admin,
+                        };
+                        keys.insert(row.key_hash, api_key);
+                    }
+                    *limiter.keys.write().unwrap() = keys;
+                }
+            }
+            .instrument(info_span!("api_keys_refresh")),
+        );
+    }
+
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Option<ApiKey>, ServerError> {
+        let Some(secret) = headers.get(API_KEY_HEADER) else {
+            if self.config.require_key {
+                return Err(ServerError::Unauthorized(format!(
+                    "missing API key, pass it in the `{API_KEY_HEADER}` header"
+                )));
+            }
+            return Ok(None);
+        };
+        let secret = secret.to_str().unwrap_or_default().trim();
+        let keys = self.keys.read().unwrap();
+        match keys.get(&hash_key(secret)) {
+            Some(key) => Ok(Some(key.clone())),
+            None => Err(ServerError::Unauthorized("unknown API key".to_string())),
+        }
+    }
+
+    /// 401 without a key, 403 for keys not flagged `admin`
+    pub fn require_admin(&self, headers: &HeaderMap) -> Result<(), ServerError> {
+        match self.authenticate(headers)? {
+            Some(key) if key.admin => Ok(()),
+            Some(_) => Err(ServerError::Forbidden("admin API key required".to_string())),
+            None => Err(ServerError::Unauthorized(format!(
+                "missing API key, pass it in the `{API_KEY_HEADER}` header"
+            ))),
+        }
+    }
+
+    fn route_cost(&self, route: &str) -> u32 {
+        self.config
+            .route_costs
+            .get(route)
+            .copied()
+            .unwrap_or_else(|| default_route_cost(route))
+    }
+
+    fn take(&self, client: &str, cost: u32, rpm: u32) -> Result<u32, u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(client) {
+            // idle clients are full again after a minute, forgetting them is free
+            buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated) < Duration::from_secs(60));
+        }
+        buckets
+            .entry(client.to_string())
+            .or_insert_with(|| Bucket::full(rpm, now))
+            .take(cost, rpm, now)
+    }
+
+    fn record(&self, name: &str, cost: u32, throttled: bool) {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(name.to_string()).or_insert_with(|| KeyUsage {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        if throttled {
+            entry.throttled += 1;
+        } else {
+            entry.requests += 1;
+            entry.cost += cost as u64;
+        }
+        entry.last_seen = Utc::now().timestamp_millis();
+    }
+
+    /// usage per key, most expensive first
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let mut usage: Vec<KeyUsage> = self.usage.lock().unwrap().values().cloned().collect();
+        usage.sort_by(|a, b| b.cost.cmp(&a.cost).then_with(|| a.name.cmp(&b.name)));
+        usage
+    }
+
+    fn client_ip(&self, req: &Request) -> String {
+        let forwarded = self
+            .config
+            .trust_forwarded_for
+            .then(|| req.headers().get("x-forwarded-for"))
+            .flatten()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|ip| ip.trim().to_string());
+        forwarded
+            .or_else(|| {
+                req.extensions()
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|info| info.0.ip().to_string())
+            })
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// heavier scans cost more of the per minute budget, override in
+/// `[api.route_costs]`
+/// routes costing more than 1 unit, the rest cost 1
+const WEIGHTED_ROUTES: &[(&str, u32)] = &[
+    ("/token/{token}/top/frequency", 10),
+    ("/token/{token}/top/richlist", 10),
+    ("/flp/delegators/multi", 10),
+    ("/flp/{project}/analytics", 10),
+    ("/flp/flows", 10),
+    ("/flp/overlap", 10),
+    ("/export/{dataset}", 10),
+    ("/token/{token}/txs", 5),
+    ("/token/{token}/txs/tags", 5),
+    ("/mainnet/messages/tags", 5),
+    ("/explorer/days", 5),
+    ("/mainnet/explorer/days", 5),
+    ("/wallet/portfolio/{address}", 5),
+    ("/wallet/portfolio/eoa/{eoa}", 5),
+    ("/flp/{project}/yield/{wallet}", 5),
+    ("/flp/simulate", 5),
+    ("/graphql", 5),
+];
+
+fn default_route_cost(route: &str) -> u32 {
+    WEIGHTED_ROUTES
+        .iter()
+        .find(|(pattern, _)| *pattern == route)
+        .map_or(1, |(_, cost)| *cost)
+}
+
+fn hash_key(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// route layer checking the `x-api-key` header and charging the route cost
+/// to the key, or to the client IP for anonymous requests
+pub async fn limit_requests(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let limits = &state.limits;
+    let key = match limits.authenticate(req.headers()) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+    let cost = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|route| limits.route_cost(route.as_str()))
+        .unwrap_or(1);
+    let (client, name, rpm) = match key {
+        Some(key) => (format!("key:{}", key.name), key.name, key.rpm),
+        None => (
+            format!("ip:{}", limits.client_ip(&req)),
+            ANONYMOUS.to_string(),
+            limits.config.ip_rpm,
+        ),
+    };
+    let remaining = match limits.take(&client, cost, rpm) {
+        Ok(remaining) => remaining,
+        Err(retry_after) => {
+            limits.record(&name, cost, true);
+            return ServerError::TooManyRequests { retry_after }.into_response();
+        }
+    };
+    limits.record(&name, cost, false);
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(rpm));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{ApiConfig, ApiKeyConfig},
+        limits::{API_KEY_HEADER, Bucket, RateLimiter, WEIGHTED_ROUTES, default_route_cost},
+        router::api_routes,
+    };
+    use axum::http::{HeaderMap, HeaderValue};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bucket_refill_test() {
+        let now = Instant::now();
+        let mut bucket = Bucket::full(60, now);
+        assert_eq!(bucket.take(50, 60, now), Ok(10));
+        assert_eq!(bucket.take(15, 60, now), Err(5));
+        assert_eq!(bucket.take(15, 60, now + Duration::from_secs(5)), Ok(0));
+        // costs above the budget are capped to it
+        assert!(bucket.take(500, 60, now + Duration::from_secs(120)).is_ok());
+    }
+
+    #[test]
+    fn api_key_auth_test() {
+        let config = ApiConfig {
+            keys: vec![ApiKeyConfig {
+                name: "ops".to_string(),
+                key: "s3cret".to_string(),
+                rpm: None,
+                admin: true,
+            }],
+            ..Default::default()
+        };
+        let limits = RateLimiter::new(config);
+        let mut headers = HeaderMap::new();
+        assert!(limits.authenticate(&headers).unwrap().is_none());
+        assert!(limits.require_admin(&headers).is_err());
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("s3cret"));
+        let key = limits.authenticate(&headers).unwrap().unwrap();
+        assert_eq!((key.name.as_str(), key.rpm), ("ops", 1200));
+        assert!(limits.require_admin(&headers).is_ok());
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("nope"));
+        assert!(limits.authenticate(&headers).is_err());
+    }
+
+    #[test]
+    fn weighted_routes_exist_test() {
+        // every weighted pattern must match a routed path, a typo would silently cost 1
+        let routes: Vec<&str> = api_routes().into_iter().map(|(path, _)| path).collect();
+        for (pattern, cost) in WEIGHTED_ROUTES {
+            assert!(routes.contains(pattern), "{pattern} is not routed");
+            assert_eq!(default_route_cost(pattern), *cost);
+        }
+        assert_eq!(default_route_cost("/"), 1);
+    }
+}
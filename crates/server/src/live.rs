@@ -0,0 +1,467 @@
+use crate::indexer::{
+    AoTokenMessage, AtlasIndexerClient, DelegationMappingHistory, MainnetMessage, OracleSnapshot,
+};
+use anyhow::Error;
+use axum::{
+    extract::ws::{Message, WebSocket},
+    response::sse::Event,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
+use tokio::sync::broadcast::{self, Receiver, Sender, error::RecvError};
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
+};
+use tracing::{Instrument, info_span, warn};
+use utoipa::ToSchema;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_BATCH: u64 = 5000;
+/// events buffered per feed before slow subscribers start skipping
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feed {
+    Mainnet,
+    Token,
+    Delegations,
+    Oracle,
+}
+
+impl Feed {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mainnet" => Some(Feed::Mainnet),
+            "token" => Some(Feed::Token),
+            "delegations" => Some(Feed::Delegations),
+            "oracle" => Some(Feed::Oracle),
+            _ => None,
+        }
+    }
+}
+
+/// token message with the token it belongs to
+#[derive(Serialize, Clone, ToSchema)]
+pub struct TokenMessageEvent {
+    pub token: String,
+    #[serde(flatten)]
+    pub message: AoTokenMessage,
+}
+
+/// one newly indexed row, sent as `{"type": ..., "data": ...}`
+#[derive(Serialize, Clone, ToSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum LiveEvent {
+    MainnetMessage(MainnetMessage),
+    TokenMessage(TokenMessageEvent),
+    DelegationMapping(DelegationMappingHistory),
+    OracleCycle(OracleSnapshot),
+}
+
+impl LiveEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::MainnetMessage(_) => "mainnet_message",
+            LiveEvent::TokenMessage(_) => "token_message",
+            LiveEvent::DelegationMapping(_) => "delegation_mapping",
+            LiveEvent::OracleCycle(_) => "oracle_cycle",
+        }
+    }
+}
+
+/// subscription filters, same semantics as the matching REST endpoints
+#[derive(Clone, Debug, Default)]
+pub struct LiveFilter {
+    /// mainnet `A` or `B`
+    pub protocol: Option<String>,
+    /// accepted spellings of the `key` tag, see `build_tag_key_variants`
+    pub tag_keys: Vec<String>,
+    pub tag_value: Option<String>,
+    pub token: Option<String>,
+    /// token `transfer` or `process`
+    pub source: Option<String>,
+    /// token `Action` tag, case insensitive
+    pub action: Option<String>,
+    /// token `Sender` tag, mainnet message owner
+    pub sender: Option<String>,
+    /// token `Recipient` tag, mainnet message recipient
+    pub recipient: Option<String>,
+    pub ticker: Option<String>,
+    /// delegating wallet or delegated to project
+    pub wallet: Option<String>,
+}
+
+impl LiveFilter {
+    pub fn matches(&self, event: &LiveEvent) -> bool {
+        match event {
+            LiveEvent::MainnetMessage(msg) => {
+                let tags: Vec<(&str, &str)> = msg
+                    .tags
+                    .iter()
+                    .map(|tag| (tag.key.as_str(), tag.value.as_str()))
+                    .collect();
+                eq(&self.protocol, &msg.protocol)
+                    && eq(&self.sender, &msg.owner)
+                    && eq(&self.recipient, &msg.recipient)
+                    && self.tag_matches(&tags)
+            }
+            LiveEvent::TokenMessage(event) => {
+                let tags: Vec<(&str, &str)> = event
+                    .message
+                    .tags
+                    .iter()
+                    .map(|tag| (tag.key.as_str(), tag.value.as_str()))
+                    .collect();
+                let tag = |key: &str| tags.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+                eq(&self.token, &event.token)
+                    && eq(&self.source, &event.message.source)
+                    && self.action.as_ref().is_none_or(|action| {
+                        tag("Action").is_some_and(|v| v.eq_ignore_ascii_case(action))
+                    })
+                    && self
+                        .sender
+                        .as_ref()
+                        .is_none_or(|sender| tag("Sender") == Some(sender.as_str()))
+                    && self
+                        .recipient
+                        .as_ref()
+                        .is_none_or(|recipient| tag("Recipient") == Some(recipient.as_str()))
+                    && self.tag_matches(&tags)
+            }
+            LiveEvent::DelegationMapping(mapping) => self.wallet.as_ref().is_none_or(|wallet| {
+                mapping.wallet == *wallet
+                    || mapping.preferences.iter().any(|p| p.wallet_to == *wallet)
+            }),
+            LiveEvent::OracleCycle(cycle) => eq(&self.ticker, &cycle.ticker),
+        }
+    }
+
+    fn tag_matches(&self, tags: &[(&str, &str)]) -> bool {
+        if self.tag_keys.is_empty() && self.tag_value.is_none() {
+            return true;
+        }
+        tags.iter().any(|(key, value)| {
+            (self.tag_keys.is_empty() || self.tag_keys.iter().any(|k| k == key))
+                && eq(&self.tag_value, value)
+        })
+    }
+}
+
+fn eq(filter: &Option<String>, value: &str) -> bool {
+    filter.as_ref().is_none_or(|f| f == value)
+}
+
+/// position of the last row pushed for a stream: the indexing time in ms,
+/// or the block height for delegation mappings, and the row id
+#[derive(Clone, Debug, Default)]
+struct Watermark {
+    pos: i64,
+    id: String,
+}
+
+/// fans newly indexed rows out to SSE/WebSocket subscribers. ClickHouse is
+/// only polled for feeds somebody is subscribed to
+#[derive(Clone)]
+pub struct LiveHub {
+    mainnet: Sender<Arc<LiveEvent>>,
+    token: Sender<Arc<LiveEvent>>,
+    delegations: Sender<Arc<LiveEvent>>,
+    oracle: Sender<Arc<LiveEvent>>,
+}
+
+impl Default for LiveHub {
+    fn default() -> Self {
+        LiveHub {
+            mainnet: broadcast::channel(CHANNEL_CAPACITY).0,
+            token: broadcast::channel(CHANNEL_CAPACITY).0,
+            delegations: broadcast::channel(CHANNEL_CAPACITY).0,
+            oracle: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl LiveHub {
+    fn sender(&self, feed: Feed) -> &Sender<Arc<LiveEvent>> {
+        match feed {
+            Feed::Mainnet => &self.mainnet,
+            Feed::Token => &self.token,
+            Feed::Delegations => &self.delegations,
+            Feed::Oracle => &self.oracle,
+        }
+    }
+
+    pub fn subscribe(&self, feed: Feed) -> Receiver<Arc<LiveEvent>> {
+        self.sender(feed).subscribe()
+    }
+
+    pub fn spawn_poller(&self, indexer: AtlasIndexerClient) {
+        let hub = self.clone();
+        tokio::spawn(
+            async move {
+                // a stream without subscribers drops its watermark, the next
+                // subscriber starts from the newest row instead of a backlog
+                let mut marks: HashMap<&'static str, Watermark> = HashMap::new();
+                let mut interval = tokio::time::interval(POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    for (stream, feed) in [
+                        ("mainnet", Feed::Mainnet),
+                        ("ao", Feed::Token),
+                        ("pi", Feed::Token),
+                        ("delegations", Feed::Delegations),
+                        ("oracle", Feed::Oracle),
+                    ] {
+                        let sender = hub.sender(feed);
+                        if sender.receiver_count() == 0 {
+                            marks.remove(stream);
+                            continue;
+                        }
+                        if let Err(err) = poll_stream(&indexer, stream, sender, &mut marks).await {
+                            warn!(error = ?err, %stream, "live feed poll failed");
+                        }
+                    }
+                }
+            }
+            .instrument(info_span!("live_feed_poller")),
+        );
+    }
+}
+
+async fn poll_stream(
+    indexer: &AtlasIndexerClient,
+    stream: &'static str,
+    sender: &Sender<Arc<LiveEvent>>,
+    marks: &mut HashMap<&'static str, Watermark>,
+) -> Result<(), Error> {
+    let Some(mark) = marks.get(stream) else {
+        let pos = match stream {
+            "mainnet" => indexer.latest_indexed_ms("ao_mainnet_messages").await?,
+            "ao" | "pi" => indexer.latest_indexed_ms("ao_token_messages").await?,
+            "delegations" => indexer.latest_delegation_mapping_height().await? as i64,
+            _ => indexer.latest_indexed_ms("flp_positions").await?,
+        };
+        marks.insert(
+            stream,
+            Watermark {
+                pos,
+                id: String::new(),
+            },
+        );
+        return Ok(());
+    };
+    let (pos, id) = (mark.pos, mark.id.as_str());
+    let events: Vec<(Watermark, LiveEvent)> = match stream {
+        "mainnet" => indexer
+            .mainnet_messages_since(pos, id, POLL_BATCH)
+            .await?
+            .into_iter()
+            .map(|msg| {
+                let mark = Watermark {
+                    pos: msg.indexed_at.timestamp_millis(),
+                    id: msg.msg_id.clone(),
+                };
+                (mark, LiveEvent::MainnetMessage(msg))
+            })
+            .collect(),
+        "ao" | "pi" => indexer
+            .ao_token_messages_since(stream, pos, id, POLL_BATCH)
+            .await?
+            .into_iter()
+            .map(|message| {
+                let mark = Watermark {
+                    pos: message.indexed_at.timestamp_millis(),
+                    id: message.msg_id.clone(),
+                };
+                let event = TokenMessageEvent {
+                    token: stream.to_string(),
+                    message,
+                };
+                (mark, LiveEvent::TokenMessage(event))
+            })
+            .collect(),
+        "delegations" => indexer
+            .delegation_mappings_since(pos as u32, id, POLL_BATCH)
+            .await?
+            .into_iter()
+            .map(|mapping| {
+                let mark = Watermark {
+                    pos: mapping.height as i64,
+                    id: mapping.tx_id.clone(),
+                };
+                (mark, LiveEvent::DelegationMapping(mapping))
+            })
+            .collect(),
+        _ => indexer
+            .oracle_cycles_since(pos, id, POLL_BATCH)
+            .await?
+            .into_iter()
+            .map(|cycle| {
+                let mark = Watermark {
+                    pos: cycle.ts.timestamp_millis(),
+                    id: cycle.ticker.clone(),
+                };
+                (mark, LiveEvent::OracleCycle(cycle))
+            })
+            .collect(),
+    };
+    for (mark, event) in events {
+        marks.insert(stream, mark);
+        // no receivers left is fine, the next poll drops the watermark
+        let _ = sender.send(Arc::new(event));
+    }
+    Ok(())
+}
+
+fn lagged(skipped: u64) -> serde_json::Value {
+    json!({ "type": "lagged", "data": { "skipped": skipped } })
+}
+
+/// SSE events named after the event type, a `lagged` event reports events
+/// dropped because the client read too slowly
+pub fn sse_events(
+    rx: Receiver<Arc<LiveEvent>>,
+    filter: LiveFilter,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    BroadcastStream::new(rx).filter_map(move |item| {
+        let event = match item {
+            Ok(event) if filter.matches(&event) => {
+                Event::default().event(event.name()).json_data(&*event)
+            }
+            Ok(_) => return None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                Event::default().event("lagged").json_data(lagged(skipped))
+            }
+        };
+        event.ok().map(Ok)
+    })
+}
+
+/// one JSON text frame per event until the client closes the socket
+pub async fn forward_ws(
+    mut socket: WebSocket,
+    mut rx: Receiver<Arc<LiveEvent>>,
+    filter: LiveFilter,
+) {
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let text = match event {
+                    Ok(event) if filter.matches(&event) => serde_json::to_string(&*event),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => serde_json::to_string(&lagged(skipped)),
+                    Err(RecvError::Closed) => break,
+                };
+                let Ok(text) = text else {
+                    continue;
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        indexer::{AoTokenMessage, AoTokenMessageTag, MainnetMessage, MainnetMessageTag},
+        live::{LiveEvent, LiveFilter, TokenMessageEvent},
+    };
+    use chrono::Utc;
+
+    fn mainnet_message(protocol: &str, tags: &[(&str, &str)]) -> LiveEvent {
+        LiveEvent::MainnetMessage(MainnetMessage {
+            protocol: protocol.to_string(),
+            block_height: 1_700_000,
+            block_timestamp: 0,
+            msg_id: "msg".to_string(),
+            owner: "owner".to_string(),
+            recipient: "process".to_string(),
+            bundled_in: String::new(),
+            data_size: "0".to_string(),
+            tags: tags
+                .iter()
+                .map(|(key, value)| MainnetMessageTag {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            indexed_at: Utc::now(),
+        })
+    }
+
+    fn token_message(token: &str, tags: &[(&str, &str)]) -> LiveEvent {
+        LiveEvent::TokenMessage(TokenMessageEvent {
+            token: token.to_string(),
+            message: AoTokenMessage {
+                source: "transfer".to_string(),
+                block_height: 1_700_000,
+                block_timestamp: 0,
+                msg_id: "msg".to_string(),
+                owner: "owner".to_string(),
+                recipient: "process".to_string(),
+                bundled_in: String::new(),
+                data_size: "0".to_string(),
+                tags: tags
+                    .iter()
+                    .map(|(key, value)| AoTokenMessageTag {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    })
+                    .collect(),
+                indexed_at: Utc::now(),
+            },
+        })
+    }
+
+    #[test]
+    fn mainnet_filter_test() {
+        let msg = mainnet_message("B", &[("Action", "Eval")]);
+        assert!(LiveFilter::default().matches(&msg));
+        let filter = LiveFilter {
+            protocol: Some("B".to_string()),
+            tag_keys: vec!["action".to_string(), "Action".to_string()],
+            tag_value: Some("Eval".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&msg));
+        assert!(!filter.matches(&mainnet_message("A", &[("action", "Eval")])));
+        assert!(!filter.matches(&mainnet_message("B", &[("Action", "Transfer")])));
+    }
+
+    #[test]
+    fn token_filter_test() {
+        let msg = token_message("ao", &[("Action", "Transfer"), ("Sender", "alice")]);
+        let filter = LiveFilter {
+            token: Some("ao".to_string()),
+            action: Some("transfer".to_string()),
+            sender: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&msg));
+        assert!(!filter.matches(&token_message(
+            "pi",
+            &[("Action", "Transfer"), ("Sender", "alice")]
+        )));
+        let filter = LiveFilter {
+            recipient: Some("bob".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&msg));
+    }
+
+    #[test]
+    fn event_shape_test() {
+        let event = serde_json::to_value(token_message("pi", &[])).unwrap();
+        assert_eq!(event["type"], "token_message");
+        assert_eq!(event["data"]["token"], "pi");
+        assert_eq!(event["data"]["msg_id"], "msg");
+    }
+}
@@ -1,98 +1,80 @@
-use crate::routes::{
-    get_all_projects_metadata_handler, get_ao_token_frequency, get_ao_token_indexing_info,
-    get_ao_token_messages_by_tag, get_ao_token_richlist, get_ao_token_tx, get_ao_token_txs,
-    get_ar_wallet_identity, get_delegation_mapping_heights, get_eoa_wallet_identity,
-    get_explorer_blocks, get_explorer_day_stats, get_explorer_recent_days,
-    get_flp_own_minting_report_handler, get_flp_snapshot_handler, get_mainnet_block_messages,
-    get_mainnet_explorer_blocks, get_mainnet_explorer_day_stats, get_mainnet_explorer_recent_days,
-    get_mainnet_indexing_info, get_mainnet_messages_by_tag, get_mainnet_recent_messages,
-    get_multi_project_delegators, get_oracle_data_handler, get_oracle_feed,
-    get_project_cycle_totals, get_wallet_delegation_mappings_history,
-    get_wallet_delegations_handler, handle_route, parse_set_balance_report,
-};
-use axum::{Router, extract::DefaultBodyLimit, routing::get};
-use common::env::get_env_var;
-use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer};
+use crate::{config::ServerConfig, indexer::AtlasIndexerClient, state::AppState};
+use axum::{extract::DefaultBodyLimit, http::Request};
+use std::net::SocketAddr;
+use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer, trace::TraceLayer};
 
 const REQ_SIZE_LIMIT: usize = 50 * 1024 * 1024; // 50 MB
 
+mod analytics;
+mod cache;
+mod config;
 mod errors;
+mod export;
+mod graphql;
 mod indexer;
+mod leaderboard;
+mod limits;
+mod live;
+mod openapi;
+mod overlap;
+mod pagination;
+mod portfolio;
+mod router;
 mod routes;
+mod simulation;
+mod state;
+mod yields;
 
 #[tokio::main]
 async fn main() {
+    common::telemetry::init_tracing();
+    let state = AppState::new(ServerConfig::load());
+    check_schema(&state.indexer).await;
+    state.cache.spawn_progress_refresh(state.indexer.clone());
+    state.limits.spawn_key_refresh(state.indexer.clone());
+    state.live.spawn_poller(state.indexer.clone());
     let cors = CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
         .allow_methods(tower_http::cors::Any)
         .allow_headers(tower_http::cors::Any);
 
-    let router = Router::new()
-        .route("/", get(handle_route))
-        // wallet operations
-        .route(
-            "/wallet/delegations/{address}",
-            get(get_wallet_delegations_handler),
-        )
-        .route("/wallet/identity/eoa/{eoa}", get(get_eoa_wallet_identity))
-        .route(
-            "/wallet/identity/ar-wallet/{address}",
-            get(get_ar_wallet_identity),
-        )
-        .route(
-            "/wallet/delegation-mappings/{address}",
-            get(get_wallet_delegation_mappings_history),
-        )
-        .route(
-            "/delegation-mappings/heights",
-            get(get_delegation_mapping_heights),
-        )
-        .route("/flp/delegators/multi", get(get_multi_project_delegators))
-        .route("/oracle/{ticker}", get(get_oracle_data_handler))
-        .route("/oracle/feed/{ticker}", get(get_oracle_feed))
-        // returns the direct delegation data per FLP ID: LSTs + AR -- factored data
-        .route("/flp/delegators/{project}", get(get_flp_snapshot_handler))
-        .route("/flp/{project}/cycles", get(get_project_cycle_totals))
-        .route(
-            "/flp/minting/{project}",
-            get(get_flp_own_minting_report_handler),
-        )
-        .route("/flp/metadata/all", get(get_all_projects_metadata_handler))
-        .route("/explorer/blocks", get(get_explorer_blocks))
-        .route("/explorer/day", get(get_explorer_day_stats))
-        .route("/explorer/days", get(get_explorer_recent_days))
-        // mainnet (ao.N.1)
-        .route("/mainnet/explorer/blocks", get(get_mainnet_explorer_blocks))
-        .route("/mainnet/explorer/day", get(get_mainnet_explorer_day_stats))
-        .route(
-            "/mainnet/explorer/days",
-            get(get_mainnet_explorer_recent_days),
-        )
-        .route("/mainnet/messages/recent", get(get_mainnet_recent_messages))
-        .route(
-            "/mainnet/messages/block/{height}",
-            get(get_mainnet_block_messages),
-        )
-        .route("/mainnet/messages/tags", get(get_mainnet_messages_by_tag))
-        .route("/mainnet/info", get(get_mainnet_indexing_info))
-        .route("/token/{token}/txs", get(get_ao_token_txs))
-        .route("/token/{token}/txs/{msg_id}", get(get_ao_token_tx))
-        .route("/token/{token}/txs/tags", get(get_ao_token_messages_by_tag))
-        .route("/token/{token}/info", get(get_ao_token_indexing_info))
-        .route("/token/{token}/top/frequency", get(get_ao_token_frequency))
-        .route("/token/{token}/top/richlist", get(get_ao_token_richlist))
-        .route(
-            "/codec/parse/set-balances/{msg_id}",
-            get(parse_set_balance_report),
-        )
+    let router = router::router(state.clone())
         .layer(DefaultBodyLimit::max(REQ_SIZE_LIMIT))
         .layer(RequestBodyLimitLayer::new(REQ_SIZE_LIMIT))
-        .layer(cors);
-    // 12 titans :D
-    let port = get_env_var("SERVER_PORT").unwrap_or_else(|_| "1212".to_string());
+        .layer(cors)
+        // one span per request, gateway + clickhouse calls made by the handler nest under it
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &Request<_>| {
+                tracing::info_span!(
+                    "http_request",
+                    method = %req.method(),
+                    path = %req.uri().path(),
+                )
+            }),
+        );
+    let port = &state.config.port;
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}"))
         .await
         .unwrap();
-    println!("Server running on PORT: {port}");
-    axum::serve(listener, router).await.unwrap();
+    tracing::info!(%port, "server running");
+    // peer addresses feed the per-IP rate limits
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+async fn check_schema(client: &AtlasIndexerClient) {
+    match client.pending_migrations().await {
+        Ok(pending) if pending.is_empty() => {}
+        Ok(pending) => {
+            tracing::warn!(
+                ?pending,
+                "clickhouse schema has pending migrations, run the indexer or `atlas migrate`"
+            )
+        }
+        Err(err) => tracing::error!(error = ?err, "clickhouse schema check failed"),
+    }
 }
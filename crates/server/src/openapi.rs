@@ -0,0 +1,173 @@
+use crate::{
+    analytics::{
+        ChurnDay, DelegationLifetime, FlowMatrix, ProjectAnalytics, RetentionCohort, StakeFlow,
+    },
+    errors::{ErrorBody, ErrorDetail},
+    indexer::{
+        AoTokenActionCount, AoTokenFrequencyInfo, AoTokenIndexingInfo, AoTokenMessage,
+        AoTokenMessageTag, AoTokenQuantityRank, AoTokenRichlist, AoTokenTagCount, DelegationHeight,
+        DelegationMappingHistory, DelegationPreference, Delegator, ExplorerBlock, ExplorerDayStats,
+        IdentityLink, MainnetMessage, MainnetMessageTag, MainnetProtocolInfo, MultiDelegator,
+        OracleFeed, OracleSnapshot, ProjectCycleTotal, ProjectSnapshot, ProjectTotal, Sourced,
+        WalletBalance, WalletPosition,
+    },
+    leaderboard::{FlpLeaderboard, LeaderboardDelta, LeaderboardDeltas, LeaderboardEntry},
+    limits::KeyUsage,
+    live::{LiveEvent, TokenMessageEvent},
+    overlap::{
+        FactorSplit, OverlapMatrix, OverlapProject, ProjectOverlap, SharedStake, SplitBucket,
+    },
+    portfolio::{EoaPortfolio, PositionTotal, TickerTotal, WalletPortfolio},
+    routes,
+    simulation::SimulationRequest,
+};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Atlas API",
+        description = "Fairlaunch Bridge, FLPs and ao network indexes"
+    ),
+    paths(
+        routes::handle_route,
+        routes::parse_set_balance_report,
+        routes::get_wallet_delegations_handler,
+        routes::get_oracle_data_handler,
+        routes::get_flp_snapshot_handler,
+        routes::get_eoa_wallet_identity,
+        routes::get_ar_wallet_identity,
+        routes::get_oracle_feed,
+        routes::get_wallet_delegation_mappings_history,
+        routes::get_wallet_portfolio,
+        routes::get_eoa_portfolio,
+        routes::get_delegation_mapping_heights,
+        routes::get_multi_project_delegators,
+        routes::get_project_cycle_totals,
+        routes::get_flp_leaderboard,
+        routes::get_project_analytics,
+        routes::get_flp_flows,
+        routes::get_flp_overlap,
+        routes::get_flp_wallet_yield,
+        routes::post_flp_simulation,
+        routes::get_flp_own_minting_report_handler,
+        routes::get_all_projects_metadata_handler,
+        routes::get_explorer_blocks,
+        routes::get_explorer_day_stats,
+        routes::get_explorer_recent_days,
+        routes::get_mainnet_explorer_blocks,
+        routes::get_mainnet_explorer_day_stats,
+        routes::get_mainnet_explorer_recent_days,
+        routes::get_mainnet_recent_messages,
+        routes::get_mainnet_block_messages,
+        routes::get_mainnet_messages_by_tag,
+        routes::get_mainnet_indexing_info,
+        routes::get_ao_token_txs,
+        routes::get_ao_token_tx,
+        routes::get_ao_token_messages_by_tag,
+        routes::get_ao_token_indexing_info,
+        routes::get_ao_token_frequency,
+        routes::get_ao_token_richlist,
+        routes::get_export,
+        routes::get_live_events,
+        routes::get_live_ws,
+        routes::graphiql,
+        routes::post_graphql,
+        routes::get_api_usage,
+    ),
+    components(schemas(
+        ErrorBody,
+        ErrorDetail,
+        ProjectSnapshot,
+        ProjectTotal,
+        Delegator,
+        IdentityLink,
+        OracleFeed,
+        OracleSnapshot,
+        DelegationMappingHistory,
+        DelegationPreference,
+        DelegationHeight,
+        MultiDelegator,
+        ProjectCycleTotal,
+        ExplorerBlock,
+        ExplorerDayStats,
+        MainnetMessage,
+        MainnetMessageTag,
+        MainnetProtocolInfo,
+        AoTokenMessage,
+        AoTokenMessageTag,
+        AoTokenIndexingInfo,
+        AoTokenActionCount,
+        AoTokenTagCount,
+        AoTokenFrequencyInfo,
+        AoTokenQuantityRank,
+        AoTokenRichlist,
+        Sourced,
+        WalletBalance,
+        WalletPosition,
+        WalletPortfolio,
+        EoaPortfolio,
+        TickerTotal,
+        PositionTotal,
+        KeyUsage,
+        FlpLeaderboard,
+        LeaderboardEntry,
+        LeaderboardDeltas,
+        LeaderboardDelta,
+        ProjectAnalytics,
+        DelegationLifetime,
+        RetentionCohort,
+        ChurnDay,
+        FlowMatrix,
+        StakeFlow,
+        OverlapMatrix,
+        OverlapProject,
+        ProjectOverlap,
+        SharedStake,
+        SplitBucket,
+        FactorSplit,
+        SimulationRequest,
+        LiveEvent,
+        TokenMessageEvent,
+    ))
+)]
+pub struct ApiDoc;
+
+/// serves the spec at `/openapi.json` and the bundled swagger UI at `/docs`
+pub fn docs_router() -> SwaggerUi {
+    SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{openapi::ApiDoc, router::api_routes};
+    use utoipa::OpenApi;
+
+    #[test]
+    fn routes_documented_test() {
+        let spec = ApiDoc::openapi();
+        for (path, _) in api_routes() {
+            let item = spec
+                .paths
+                .paths
+                .get(path)
+                .unwrap_or_else(|| panic!("{path} is missing from the OpenAPI spec"));
+            assert!(
+                item.get.is_some() || item.post.is_some(),
+                "{path} has no GET or POST operation in the spec"
+            );
+        }
+    }
+
+    #[test]
+    fn spec_paths_routed_test() {
+        let routes: Vec<&str> = api_routes().into_iter().map(|(path, _)| path).collect();
+        for path in ApiDoc::openapi().paths.paths.keys() {
+            assert!(
+                routes.contains(&path.as_str()),
+                "{path} is documented but not routed"
+            );
+        }
+    }
+}
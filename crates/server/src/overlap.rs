@@ -0,0 +1,282 @@
+use crate::{
+    errors::ServerError,
+    indexer::{AtlasIndexerClient, FlpPositionShare},
+};
+use common::{
+    amount::{Amount, LST_DECIMALS},
+    projects::Project,
+};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+use utoipa::ToSchema;
+
+/// most common factor splits returned
+const TOP_SPLITS: usize = 20;
+
+/// how delegators of the latest cycles spread over the FLPs
+#[derive(Serialize, ToSchema, Debug)]
+pub struct OverlapMatrix {
+    pub delegators: u64,
+    pub projects: Vec<OverlapProject>,
+    /// FLP pairs sharing at least one delegator, most shared first
+    pub pairs: Vec<ProjectOverlap>,
+    /// wallets per number of FLPs they delegate to
+    pub split_distribution: Vec<SplitBucket>,
+    /// most common factor sets, largest factor first
+    pub common_splits: Vec<FactorSplit>,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct OverlapProject {
+    pub project: String,
+    pub name: Option<String>,
+    pub delegators: u64,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct ProjectOverlap {
+    /// the pair's FLP ids, `a` < `b`
+    pub a: String,
+    pub b: String,
+    pub shared_delegators: u64,
+    /// shared delegators over the delegators of either FLP, 0 to 1
+    pub jaccard: f64,
+    pub shared_stake: Vec<SharedStake>,
+}
+
+/// LST delegated by the shared delegators to each side of the pair
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct SharedStake {
+    pub ticker: String,
+    pub a_amount: String,
+    pub b_amount: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct SplitBucket {
+    pub projects: u64,
+    pub wallets: u64,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct FactorSplit {
+    /// factors out of 10000, largest first
+    pub factors: Vec<u32>,
+    pub wallets: u64,
+}
+
+pub async fn overlap_matrix(
+    indexer: &AtlasIndexerClient,
+    ticker: Option<&str>,
+) -> Result<OverlapMatrix, ServerError> {
+    let rows = indexer.latest_flp_position_shares(ticker).await?;
+    let names: HashMap<String, String> = Project::get_all()
+        .into_iter()
+        .map(|project| (project.pid, project.name))
+        .collect();
+    Ok(build_overlap(&rows, &names))
+}
+
+/// one wallet's delegation to an FLP, the amount summed per ticker
+#[derive(Default)]
+struct ProjectSplit<'a> {
+    factor: u32,
+    amounts: BTreeMap<&'a str, Amount>,
+}
+
+/// delegators shared by an FLP pair and their stake per ticker on each side
+#[derive(Default)]
+struct PairTotals<'a> {
+    delegators: u64,
+    stake: BTreeMap<&'a str, (Amount, Amount)>,
+}
+
+/// saturating, the amounts are only reported
+fn add(sum: &mut Amount, amount: Amount) {
+    sum.raw = sum.raw.saturating_add(amount.raw);
+}
+
+/// `rows` sorted by wallet, as the indexer returns them
+fn build_overlap(rows: &[FlpPositionShare], names: &HashMap<String, String>) -> OverlapMatrix {
+    let zero = Amount::zero(LST_DECIMALS);
+    let mut wallets: Vec<BTreeMap<&str, ProjectSplit>> = Vec::new();
+    for (idx, row) in rows.iter().enumerate() {
+        if idx == 0 || rows[idx - 1].wallet != row.wallet {
+            wallets.push(BTreeMap::new());
+        }
+        let Some(wallet) = wallets.last_mut() else {
+            continue;
+        };
+        // the factor comes from the wallet's mapping, the same for every ticker
+        let split = wallet.entry(&row.project).or_default();
+        split.factor = split.factor.max(row.factor);
+        add(split.amounts.entry(&row.ticker).or_insert(zero), row.amount);
+    }
+
+    let mut delegators: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut shared: BTreeMap<(&str, &str), PairTotals> = BTreeMap::new();
+    let mut buckets: BTreeMap<u64, u64> = BTreeMap::new();
+    let mut splits: HashMap<Vec<u32>, u64> = HashMap::new();
+    for wallet in &wallets {
+        let projects: Vec<_> = wallet.iter().collect();
+        for (idx, (a, a_split)) in projects.iter().enumerate() {
+            *delegators.entry(a).or_insert(0) += 1;
+            for (b, b_split) in &projects[idx + 1..] {
+                let pair = shared.entry((a, b)).or_default();
+                pair.delegators += 1;
+                for (ticker, amount) in &a_split.amounts {
+                    add(
+                        &mut pair.stake.entry(ticker).or_insert((zero, zero)).0,
+                        *amount,
+                    );
+                }
+                for (ticker, amount) in &b_split.amounts {
+                    add(
+                        &mut pair.stake.entry(ticker).or_insert((zero, zero)).1,
+                        *amount,
+                    );
+                }
+            }
+        }
+        *buckets.entry(projects.len() as u64).or_insert(0) += 1;
+        let mut factors: Vec<u32> = projects.iter().map(|(_, split)| split.factor).collect();
+        factors.sort_by(|a, b| b.cmp(a));
+        *splits.entry(factors).or_insert(0) += 1;
+    }
+
+    let mut pairs: Vec<ProjectOverlap> = shared
+        .into_iter()
+        .map(|((a, b), pair)| {
+            let count = pair.delegators;
+            let union = delegators[a] + delegators[b] - count;
+            ProjectOverlap {
+                a: a.to_string(),
+                b: b.to_string(),
+                shared_delegators: count,
+                jaccard: count as f64 / union as f64,
+                shared_stake: pair
+                    .stake
+                    .into_iter()
+                    .map(|(ticker, (a_amount, b_amount))| SharedStake {
+                        ticker: ticker.to_string(),
+                        a_amount: a_amount.to_string(),
+                        b_amount: b_amount.to_string(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    pairs.sort_by_key(|pair| Reverse(pair.shared_delegators));
+    let mut common_splits: Vec<FactorSplit> = splits
+        .into_iter()
+        .map(|(factors, wallets)| FactorSplit { factors, wallets })
+        .collect();
+    common_splits.sort_by(|x, y| {
+        y.wallets
+            .cmp(&x.wallets)
+            .then_with(|| x.factors.cmp(&y.factors))
+    });
+    common_splits.truncate(TOP_SPLITS);
+    OverlapMatrix {
+        delegators: wallets.len() as u64,
+        projects: delegators
+            .into_iter()
+            .map(|(project, count)| OverlapProject {
+                project: project.to_string(),
+                name: names.get(project).cloned(),
+                delegators: count,
+            })
+            .collect(),
+        pairs,
+        split_distribution: buckets
+            .into_iter()
+            .map(|(projects, wallets)| SplitBucket { projects, wallets })
+            .collect(),
+        common_splits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        indexer::FlpPositionShare,
+        overlap::{FactorSplit, SharedStake, SplitBucket, build_overlap},
+    };
+    use common::amount::{Amount, LST_DECIMALS};
+    use std::collections::HashMap;
+
+    fn row(
+        wallet: &str,
+        ticker: &str,
+        project: &str,
+        factor: u32,
+        amount: &str,
+    ) -> FlpPositionShare {
+        FlpPositionShare {
+            wallet: wallet.to_string(),
+            ticker: ticker.to_string(),
+            project: project.to_string(),
+            factor,
+            amount: Amount::parse_human(amount, LST_DECIMALS).unwrap(),
+        }
+    }
+
+    #[test]
+    fn overlap_matrix_test() {
+        let rows = vec![
+            row("w1", "dai", "x", 5000, "1"),
+            row("w1", "dai", "y", 5000, "1"),
+            row("w1", "usds", "x", 5000, "2"),
+            row("w2", "usds", "x", 10000, "4"),
+            row("w3", "usds", "x", 5000, "3"),
+            row("w3", "usds", "y", 5000, "3"),
+        ];
+        let matrix = build_overlap(&rows, &HashMap::new());
+        assert_eq!(matrix.delegators, 3);
+        assert_eq!(matrix.projects[0].delegators, 3);
+        assert_eq!(matrix.pairs.len(), 1);
+        let pair = &matrix.pairs[0];
+        assert_eq!((pair.a.as_str(), pair.b.as_str()), ("x", "y"));
+        assert_eq!(pair.shared_delegators, 2);
+        // 2 shared out of 3 wallets on x or y
+        assert!((pair.jaccard - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            pair.shared_stake,
+            vec![
+                SharedStake {
+                    ticker: "dai".to_string(),
+                    a_amount: "1".to_string(),
+                    b_amount: "1".to_string(),
+                },
+                SharedStake {
+                    ticker: "usds".to_string(),
+                    a_amount: "5".to_string(),
+                    b_amount: "3".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            matrix.split_distribution,
+            vec![
+                SplitBucket {
+                    projects: 1,
+                    wallets: 1
+                },
+                SplitBucket {
+                    projects: 2,
+                    wallets: 2
+                },
+            ]
+        );
+        assert_eq!(
+            matrix.common_splits[0],
+            FactorSplit {
+                factors: vec![5000, 5000],
+                wallets: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::errors::ServerError;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// upper bound of `limit` on every list endpoint
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// keyset position of the last row of a page: the numeric sort key (block
+/// height, timestamp, count...) and a string tie breaker (msg id, tx id,
+/// wallet). clients only see it base64 encoded as `next_cursor`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub key: u64,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn new(key: impl Into<u64>, id: impl Into<String>) -> Self {
+        Cursor {
+            key: key.into(),
+            id: id.into(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.key, self.id))
+    }
+
+    pub fn decode(raw: &str) -> Result<Self, ServerError> {
+        let invalid = || ServerError::bad_param("cursor", "invalid cursor");
+        let bytes = URL_SAFE_NO_PAD.decode(raw.trim()).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (key, id) = text.split_once(':').ok_or_else(invalid)?;
+        Ok(Cursor {
+            key: key.parse().map_err(|_| invalid())?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// envelope of every list endpoint
+#[derive(Serialize, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    /// pass back as `?cursor=` to fetch the next page, null on the last page
+    pub next_cursor: Option<String>,
+    pub limit: u64,
+}
+
+impl<T> Page<T> {
+    /// `rows` must be fetched with `PageParams::fetch_limit`, the extra row
+    /// only tells whether another page exists
+    pub fn from_rows(mut rows: Vec<T>, limit: u64, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let next_cursor = if rows.len() as u64 > limit {
+            rows.truncate(limit as usize);
+            rows.last().map(|row| cursor_of(row).encode())
+        } else {
+            None
+        };
+        Page {
+            data: rows,
+            next_cursor,
+            limit,
+        }
+    }
+}
+
+pub struct PageParams {
+    pub limit: u64,
+    pub cursor: Option<Cursor>,
+}
+
+impl PageParams {
+    /// reads `limit` and `cursor`, an unparsable or zero limit falls back to
+    /// `default` and anything above `max` is clamped
+    pub fn parse(
+        params: &HashMap<String, String>,
+        default: u64,
+        max: u64,
+    ) -> Result<Self, ServerError> {
+        let limit = params
+            .get("limit")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(default)
+            .min(max);
+        let cursor = params
+            .get("cursor")
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| Cursor::decode(v))
+            .transpose()?;
+        Ok(PageParams { limit, cursor })
+    }
+
+    pub fn fetch_limit(&self) -> u64 {
+        self.limit + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pagination::{Cursor, Page, PageParams};
+    use std::collections::HashMap;
+
+    #[test]
+    fn cursor_roundtrip_test() {
+        let cursor = Cursor::new(1_600_000u32, "Q7b-zr9:x");
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a cursor").is_err());
+    }
+
+    #[test]
+    fn page_from_rows_test() {
+        let page = Page::from_rows(vec![5u32, 4, 3], 2, |n| Cursor::new(*n, ""));
+        assert_eq!(page.data, vec![5, 4]);
+        assert_eq!(page.next_cursor, Some(Cursor::new(4u32, "").encode()));
+        let last = Page::from_rows(vec![2u32, 1], 2, |n| Cursor::new(*n, ""));
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn page_params_clamp_test() {
+        let params = HashMap::from([("limit".to_string(), "10000".to_string())]);
+        let page = PageParams::parse(&params, 100, 500).unwrap();
+        assert_eq!(page.limit, 500);
+        assert_eq!(page.fetch_limit(), 501);
+        assert!(page.cursor.is_none());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    errors::{ServerError, optional},
+    indexer::{
+        AtlasIndexerClient, DelegationMappingHistory, WalletBalance, WalletPosition, stored_amount,
+    },
+    live::TokenMessageEvent,
+};
+use anyhow::{Error, anyhow};
+use common::amount::{AR_DECIMALS, Amount, lst_decimals};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+/// everything indexed about an Arweave wallet
+#[derive(Serialize, ToSchema)]
+pub struct WalletPortfolio {
+    pub wallet: String,
+    /// EOAs the bridge linked to the wallet, latest first
+    pub eoas: Vec<String>,
+    /// latest indexed `Set-Delegation` payload, null when none was indexed
+    #[schema(value_type = Option<Object>)]
+    pub delegations: Option<Value>,
+    pub delegation_history: Vec<DelegationMappingHistory>,
+    /// LST balances per ticker
+    pub balances: Vec<WalletBalance>,
+    /// AR balance of the most recent oracle cycle
+    pub ar_balance: Option<String>,
+    /// delegated amounts per (ticker, FLP) in the latest cycle of every ticker
+    pub positions: Vec<WalletPosition>,
+    /// newest ao/pi messages sent or received by the wallet
+    pub token_activity: Vec<TokenMessageEvent>,
+}
+
+/// an EOA's wallets, with their balances and positions summed
+#[derive(Serialize, ToSchema)]
+pub struct EoaPortfolio {
+    pub eoa: String,
+    pub wallets: Vec<String>,
+    pub balances: Vec<TickerTotal>,
+    pub ar_balance: String,
+    pub positions: Vec<PositionTotal>,
+    pub portfolios: Vec<WalletPortfolio>,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct TickerTotal {
+    pub ticker: String,
+    pub amount: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, PartialEq)]
+pub struct PositionTotal {
+    pub project: String,
+    pub ticker: String,
+    pub amount: String,
+    pub ar_amount: String,
+}
+
+pub async fn wallet_portfolio(
+    indexer: &AtlasIndexerClient,
+    wallet: &str,
+    activity_limit: u64,
+) -> Result<WalletPortfolio, ServerError> {
+    let (identities, delegations, history, balances, positions, activity) = tokio::join!(
+        indexer.wallet_identity_history(wallet),
+        indexer.indexed_wallet_delegations(wallet),
+        indexer.wallet_delegation_mappings(wallet),
+        indexer.wallet_balances(wallet),
+        indexer.wallet_positions(wallet),
+        indexer.wallet_token_activity(wallet, activity_limit),
+    );
+    let mut eoas = Vec::new();
+    for link in identities? {
+        if !eoas.contains(&link.eoa) {
+            eoas.push(link.eoa);
+        }
+    }
+    let balances = balances?;
+    let ar_balance = balances
+        .iter()
+        .filter(|balance| !balance.ar_balance.is_empty())
+        .max_by_key(|balance| balance.ts)
+        .map(|balance| balance.ar_balance.clone());
+    Ok(WalletPortfolio {
+        wallet: wallet.to_string(),
+        eoas,
+        delegations: optional(delegations)?.map(|sourced| sourced.data),
+        delegation_history: optional(history)?.unwrap_or_default(),
+        balances,
+        ar_balance,
+        positions: positions?,
+        token_activity: activity?
+            .into_iter()
+            .map(|(token, message)| TokenMessageEvent { token, message })
+            .collect(),
+    })
+}
+
+pub async fn eoa_portfolio(
+    indexer: &AtlasIndexerClient,
+    eoa: &str,
+    activity_limit: u64,
+) -> Result<EoaPortfolio, ServerError> {
+    let mut wallets: Vec<String> = indexer
+        .eoa_identity_history(eoa)
+        .await?
+        .into_iter()
+        .map(|link| link.wallet)
+        .collect();
+    wallets.sort();
+    wallets.dedup();
+    if wallets.is_empty() {
+        return Err(ServerError::not_found(format!(
+            "no wallets linked to eoa {eoa}"
+        )));
+    }
+    let mut tasks = JoinSet::new();
+    for wallet in wallets.clone() {
+        let indexer = indexer.clone();
+        tasks.spawn(async move { wallet_portfolio(&indexer, &wallet, activity_limit).await });
+    }
+    let mut portfolios = Vec::with_capacity(wallets.len());
+    while let Some(res) = tasks.join_next().await {
+        portfolios.push(res.map_err(anyhow::Error::from)??);
+    }
+    portfolios.sort_by(|a, b| a.wallet.cmp(&b.wallet));
+    let (balances, ar_balance, positions) = totals(&portfolios)?;
+    Ok(EoaPortfolio {
+        eoa: eoa.to_string(),
+        wallets,
+        balances,
+        ar_balance,
+        positions,
+        portfolios,
+    })
+}
+
+/// LST balances per ticker, AR balance and positions per (project, ticker)
+/// summed over wallets
+fn totals(
+    portfolios: &[WalletPortfolio],
+) -> Result<(Vec<TickerTotal>, String, Vec<PositionTotal>), Error> {
+    let lst = |ticker: &str, value: &str| {
+        let decimals =
+            lst_decimals(ticker).ok_or_else(|| anyhow!("unsupported ticker {ticker}"))?;
+        stored_amount(value, decimals)
+    };
+    let add = |sum: &Amount, amount: Amount| {
+        sum.checked_add(&amount)
+            .ok_or_else(|| anyhow!("portfolio total overflows"))
+    };
+    let mut balances: BTreeMap<String, Amount> = BTreeMap::new();
+    let mut positions: BTreeMap<(String, String), (Amount, Amount)> = BTreeMap::new();
+    let mut ar_balance = Amount::zero(AR_DECIMALS);
+    for portfolio in portfolios {
+        for balance in &portfolio.balances {
+            let amount = lst(&balance.ticker, &balance.amount)?;
+            let sum = balances
+                .entry(balance.ticker.clone())
+                .or_insert(Amount::zero(amount.decimals));
+            *sum = add(sum, amount)?;
+        }
+        if let Some(ar) = &portfolio.ar_balance {
+            ar_balance = add(&ar_balance, stored_amount(ar, AR_DECIMALS)?)?;
+        }
+        for position in &portfolio.positions {
+            let amount = lst(&position.ticker, &position.amount)?;
+            let ar_amount = stored_amount(&position.ar_amount, AR_DECIMALS)?;
+            let key = (position.project.clone(), position.ticker.clone());
+            let sum = positions
+                .entry(key)
+                .or_insert((Amount::zero(amount.decimals), Amount::zero(AR_DECIMALS)));
+            *sum = (add(&sum.0, amount)?, add(&sum.1, ar_amount)?);
+        }
+    }
+    let balances = balances
+        .into_iter()
+        .map(|(ticker, amount)| TickerTotal {
+            ticker,
+            amount: amount.to_string(),
+        })
+        .collect();
+    let positions = positions
+        .into_iter()
+        .map(|((project, ticker), (amount, ar_amount))| PositionTotal {
+            project,
+            ticker,
+            amount: amount.to_string(),
+            ar_amount: ar_amount.to_string(),
+        })
+        .collect();
+    Ok((balances, ar_balance.to_string(), positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        indexer::{WalletBalance, WalletPosition},
+        portfolio::{PositionTotal, TickerTotal, WalletPortfolio, totals},
+    };
+    use chrono::{TimeZone, Utc};
+
+    fn portfolio(
+        wallet: &str,
+        usds: &str,
+        ar: Option<&str>,
+        project_amount: &str,
+    ) -> WalletPortfolio {
+        let ts = Utc.timestamp_millis_opt(1_750_000_000_000).unwrap();
+        WalletPortfolio {
+            wallet: wallet.to_string(),
+            eoas: vec!["0xabc".to_string()],
+            delegations: None,
+            delegation_history: Vec::new(),
+            balances: vec![WalletBalance {
+                ticker: "usds".to_string(),
+                eoa: "0xabc".to_string(),
+                amount: usds.to_string(),
+                ar_balance: ar.unwrap_or_default().to_string(),
+                tx_id: "tx".to_string(),
+                ts,
+            }],
+            ar_balance: ar.map(str::to_string),
+            positions: vec![WalletPosition {
+                ts,
+                project: "flp".to_string(),
+                ticker: "usds".to_string(),
+                factor: 5000,
+                amount: project_amount.to_string(),
+                ar_amount: "0.5".to_string(),
+            }],
+            token_activity: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn eoa_totals_test() {
+        let portfolios = [
+            portfolio("a", "10.5", Some("2"), "5.25"),
+            portfolio("b", "1.5", None, "0.75"),
+        ];
+        let (balances, ar_balance, positions) = totals(&portfolios).unwrap();
+        assert_eq!(
+            balances,
+            vec![TickerTotal {
+                ticker: "usds".to_string(),
+                amount: "12".to_string()
+            }]
+        );
+        assert_eq!(ar_balance, "2");
+        assert_eq!(
+            positions,
+            vec![PositionTotal {
+                project: "flp".to_string(),
+                ticker: "usds".to_string(),
+                amount: "6".to_string(),
+                ar_amount: "1".to_string(),
+            }]
+        );
+        let corrupt = [portfolio("c", "ten", None, "1")];
+        assert!(totals(&corrupt).is_err());
+    }
+}
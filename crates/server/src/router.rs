@@ -0,0 +1,122 @@
+use crate::{
+    cache::cache_responses,
+    limits::limit_requests,
+    openapi::docs_router,
+    routes::{
+        get_all_projects_metadata_handler, get_ao_token_frequency, get_ao_token_indexing_info,
+        get_ao_token_messages_by_tag, get_ao_token_richlist, get_ao_token_tx, get_ao_token_txs,
+        get_api_usage, get_ar_wallet_identity, get_delegation_mapping_heights, get_eoa_portfolio,
+        get_eoa_wallet_identity, get_explorer_blocks, get_explorer_day_stats,
+        get_explorer_recent_days, get_export, get_flp_flows, get_flp_leaderboard, get_flp_overlap,
+        get_flp_own_minting_report_handler, get_flp_snapshot_handler, get_flp_wallet_yield,
+        get_live_events, get_live_ws, get_mainnet_block_messages, get_mainnet_explorer_blocks,
+        get_mainnet_explorer_day_stats, get_mainnet_explorer_recent_days,
+        get_mainnet_indexing_info, get_mainnet_messages_by_tag, get_mainnet_recent_messages,
+        get_multi_project_delegators, get_oracle_data_handler, get_oracle_feed,
+        get_project_analytics, get_project_cycle_totals, get_wallet_delegation_mappings_history,
+        get_wallet_delegations_handler, get_wallet_portfolio, graphiql, handle_route,
+        parse_set_balance_report, post_flp_simulation, post_graphql,
+    },
+    state::AppState,
+};
+use axum::{
+    Router, middleware,
+    routing::{MethodRouter, get, post},
+};
+
+/// every API route, also checked against the OpenAPI spec in `openapi::tests`
+pub fn api_routes() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        ("/", get(handle_route)),
+        // wallet operations
+        (
+            "/wallet/delegations/{address}",
+            get(get_wallet_delegations_handler),
+        ),
+        ("/wallet/identity/eoa/{eoa}", get(get_eoa_wallet_identity)),
+        (
+            "/wallet/identity/ar-wallet/{address}",
+            get(get_ar_wallet_identity),
+        ),
+        (
+            "/wallet/delegation-mappings/{address}",
+            get(get_wallet_delegation_mappings_history),
+        ),
+        ("/wallet/portfolio/{address}", get(get_wallet_portfolio)),
+        ("/wallet/portfolio/eoa/{eoa}", get(get_eoa_portfolio)),
+        (
+            "/delegation-mappings/heights",
+            get(get_delegation_mapping_heights),
+        ),
+        ("/flp/delegators/multi", get(get_multi_project_delegators)),
+        ("/oracle/{ticker}", get(get_oracle_data_handler)),
+        ("/oracle/feed/{ticker}", get(get_oracle_feed)),
+        // returns the direct delegation data per FLP ID: LSTs + AR -- factored data
+        ("/flp/delegators/{project}", get(get_flp_snapshot_handler)),
+        ("/flp/{project}/cycles", get(get_project_cycle_totals)),
+        ("/flp/{project}/analytics", get(get_project_analytics)),
+        ("/flp/{project}/yield/{wallet}", get(get_flp_wallet_yield)),
+        ("/flp/leaderboard", get(get_flp_leaderboard)),
+        ("/flp/flows", get(get_flp_flows)),
+        ("/flp/overlap", get(get_flp_overlap)),
+        ("/flp/simulate", post(post_flp_simulation)),
+        (
+            "/flp/minting/{project}",
+            get(get_flp_own_minting_report_handler),
+        ),
+        ("/flp/metadata/all", get(get_all_projects_metadata_handler)),
+        ("/explorer/blocks", get(get_explorer_blocks)),
+        ("/explorer/day", get(get_explorer_day_stats)),
+        ("/explorer/days", get(get_explorer_recent_days)),
+        // mainnet (ao.N.1)
+        ("/mainnet/explorer/blocks", get(get_mainnet_explorer_blocks)),
+        ("/mainnet/explorer/day", get(get_mainnet_explorer_day_stats)),
+        (
+            "/mainnet/explorer/days",
+            get(get_mainnet_explorer_recent_days),
+        ),
+        ("/mainnet/messages/recent", get(get_mainnet_recent_messages)),
+        (
+            "/mainnet/messages/block/{height}",
+            get(get_mainnet_block_messages),
+        ),
+        ("/mainnet/messages/tags", get(get_mainnet_messages_by_tag)),
+        ("/mainnet/info", get(get_mainnet_indexing_info)),
+        ("/token/{token}/txs", get(get_ao_token_txs)),
+        ("/token/{token}/txs/{msg_id}", get(get_ao_token_tx)),
+        ("/token/{token}/txs/tags", get(get_ao_token_messages_by_tag)),
+        ("/token/{token}/info", get(get_ao_token_indexing_info)),
+        ("/token/{token}/top/frequency", get(get_ao_token_frequency)),
+        ("/token/{token}/top/richlist", get(get_ao_token_richlist)),
+        (
+            "/codec/parse/set-balances/{msg_id}",
+            get(parse_set_balance_report),
+        ),
+        // bulk downloads, streamed from clickhouse
+        ("/export/{dataset}", get(get_export)),
+        // push feeds of newly indexed rows
+        ("/live/{feed}", get(get_live_events)),
+        ("/live/{feed}/ws", get(get_live_ws)),
+        ("/graphql", get(graphiql).post(post_graphql)),
+        ("/admin/usage", get(get_api_usage)),
+    ]
+}
+
+pub fn router(state: AppState) -> Router {
+    api_routes()
+        .into_iter()
+        .fold(Router::new(), |router, (path, method)| {
+            router.route(path, method)
+        })
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            cache_responses,
+        ))
+        // outermost, so cache hits are still charged
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limit_requests,
+        ))
+        .merge(docs_router())
+        .with_state(state)
+}
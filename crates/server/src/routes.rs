@@ -1,72 +1,61 @@
 use crate::{
-    errors::ServerError,
+    analytics::{self, CohortPeriod, FlowMatrix, ProjectAnalytics},
+    errors::{ErrorBody, ServerError},
+    export,
     indexer::{
-        AoTokenMessage, AtlasIndexerClient, DelegationHeight, DelegationMappingHistory,
-        ExplorerBlock, ExplorerDayStats, MultiDelegator, ProjectCycleTotal,
+        AoTokenFrequencyInfo, AoTokenIndexingInfo, AoTokenMessage, AoTokenRichlist,
+        DelegationHeight, DelegationMappingHistory, ExplorerBlock, ExplorerDayStats, IdentityLink,
+        MainnetMessage, MainnetProtocolInfo, MultiDelegator, OracleFeed, ProjectCycleTotal,
+        ProjectSnapshot, Sourced, minting_report_value,
     },
+    leaderboard::{self, FlpLeaderboard, LeaderboardSort},
+    limits::KeyUsage,
+    live::{self, Feed, LiveEvent, LiveFilter},
+    overlap::{self, OverlapMatrix},
+    pagination::{Cursor, MAX_PAGE_SIZE, Page, PageParams},
+    portfolio::{self, EoaPortfolio, WalletPortfolio},
+    simulation::{self, SimulationRequest},
+    state::AppState,
+    yields,
 };
-use anyhow::anyhow;
+use async_graphql::http::GraphiQLSource;
 use axum::{
     Json,
-    extract::{Path, Query},
+    extract::{Path, Query, State, ws::WebSocketUpgrade},
+    http::HeaderMap,
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
-use chrono::{NaiveDate, Utc};
+use chrono::{Duration, NaiveDate, Utc};
 use common::{
-    env::get_env_var, gql::OracleStakers, minting::get_flp_own_minting_report, projects::Project,
+    amount::{AO_DECIMALS, Amount},
+    export::{ExportDataset, ExportFilter, ExportFormat},
+    gql::OracleStakers,
+    ids::{ArAddress, EvmAddress, MessageId, ProcessId},
+    minting::get_flp_own_minting_report,
+    projects::Project,
 };
-use flp::csv_parser::parse_flp_balances_setting_res;
+use flp::csv_parser::parse_flp_balances_setting_res_lenient;
 use flp::json_parser::parse_own_minting_report;
+use flp::simulator::Simulation;
 use flp::wallet::get_wallet_delegations;
-use serde::{Deserialize, Serialize};
+use flp::yields::{WalletYield, YieldCalculator};
 use serde_json::{Value, json};
-use std::collections::HashMap;
-use std::{fs, io::ErrorKind};
-
-#[derive(Deserialize, Serialize, Default)]
-struct AtlasConfig {
-    #[serde(default)]
-    indexers: AtlasIndexersConfig,
-    #[serde(rename = "PRIMARY_ARWEAVE_GATEWAY", alias = "primary_arweave_gateway")]
-    primary_arweave_gateway: Option<String>,
-}
-
-#[derive(Deserialize, Serialize, Default)]
-struct AtlasIndexersConfig {
-    ao: Option<bool>,
-    pi: Option<bool>,
-    flp: Option<bool>,
-    explorer: Option<bool>,
-    mainnet: Option<bool>,
-}
-
-fn load_atlas_config() -> Option<AtlasConfig> {
-    let path = get_env_var("ATLAS_CONFIG").ok();
-    if let Some(path) = path {
-        return read_atlas_config(&path);
-    }
-    read_atlas_config("atlas.toml").or_else(|| read_atlas_config("../atlas.toml"))
-}
+use std::{collections::HashMap, convert::Infallible, str::FromStr};
+use tokio_stream::Stream;
 
-fn read_atlas_config(path: &str) -> Option<AtlasConfig> {
-    let contents = match fs::read_to_string(path) {
-        Ok(contents) => contents,
-        Err(err) if err.kind() == ErrorKind::NotFound => return None,
-        Err(err) => {
-            eprintln!("failed to read atlas config {path}: {err}");
-            return None;
-        }
-    };
-    match toml::from_str::<AtlasConfig>(&contents) {
-        Ok(config) => Some(config),
-        Err(err) => {
-            eprintln!("failed to parse atlas config {path}: {err}");
-            None
-        }
-    }
-}
-
-pub async fn handle_route() -> Json<Value> {
-    let config = load_atlas_config();
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "health",
+    responses(
+        (status = 200, description = "health info and the loaded atlas.toml", body = Object),
+    )
+)]
+pub async fn handle_route(State(state): State<AppState>) -> Json<Value> {
+    let config = &state.config.atlas;
     Json(serde_json::json!({
         "status": "running",
         "name": "atlas-server",
@@ -75,141 +64,690 @@ pub async fn handle_route() -> Json<Value> {
     }))
 }
 
-pub async fn parse_set_balance_report(Path(id): Path<String>) -> Result<Json<Value>, ServerError> {
-    let res = parse_flp_balances_setting_res(&id)?;
-    Ok(Json(serde_json::to_value(&res)?))
+#[utoipa::path(
+    get,
+    path = "/codec/parse/set-balances/{msg_id}",
+    tag = "codec",
+    params(
+        ("msg_id" = String, Path, description = "oracle `Set-Balances` message id"),
+        ("live" = Option<bool>, Query, description = "read from the Arweave gateway instead of the index"),
+    ),
+    responses(
+        (status = 200, description = "parsed `Set-Balances` payload, malformed rows listed in `rejected` with their line and reason", body = Sourced),
+        (status = 400, description = "malformed message id", body = ErrorBody),
+        (status = 404, description = "not indexed", body = ErrorBody),
+        (status = 502, description = "gateway error", body = ErrorBody),
+    )
+)]
+pub async fn parse_set_balance_report(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Sourced>, ServerError> {
+    let id: MessageId = parse_id("msg_id", &id)?;
+    if !is_live(&params) {
+        return Ok(Json(state.indexer.indexed_set_balances(&id).await?));
+    }
+    let msg_id = id.to_string();
+    let res = live_call(move || parse_flp_balances_setting_res_lenient(&msg_id)).await?;
+    Ok(Json(
+        Sourced::live(serde_json::to_value(&res.rows)?, Some(id.into()))
+            .with_rejected(res.rejected),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/wallet/delegations/{address}",
+    tag = "wallet",
+    params(
+        ("address" = String, Path, description = "Arweave address"),
+        ("live" = Option<bool>, Query, description = "read from the Arweave gateway instead of the index"),
+    ),
+    responses(
+        (status = 200, description = "latest `Set-Delegation` payload", body = Sourced),
+        (status = 400, description = "malformed Arweave address", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+        (status = 502, description = "gateway error", body = ErrorBody),
+    )
+)]
 pub async fn get_wallet_delegations_handler(
+    State(state): State<AppState>,
     Path(address): Path<String>,
-) -> Result<Json<Value>, ServerError> {
-    let res = get_wallet_delegations(&address)
-        .map_err(|err| ServerError::from(anyhow!("wallet delegations error: {err}")))?;
-    Ok(Json(serde_json::to_value(&res)?))
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Sourced>, ServerError> {
+    let address: ArAddress = parse_id("address", &address)?;
+    if !is_live(&params) {
+        return Ok(Json(
+            state.indexer.indexed_wallet_delegations(&address).await?,
+        ));
+    }
+    let wallet = address.to_string();
+    let res = live_call(move || get_wallet_delegations(&wallet)).await?;
+    Ok(Json(Sourced::live(serde_json::to_value(&res)?, None)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/oracle/{ticker}",
+    tag = "oracle",
+    params(
+        ("ticker" = String, Path, description = "`usds`, `dai` or `steth`"),
+        ("live" = Option<bool>, Query, description = "read from the Arweave gateway instead of the index"),
+    ),
+    responses(
+        (status = 200, description = "latest `Set-Balances` data of the oracle", body = Sourced),
+        (status = 404, description = "not found", body = ErrorBody),
+        (status = 502, description = "gateway error", body = ErrorBody),
+    )
+)]
 pub async fn get_oracle_data_handler(
+    State(state): State<AppState>,
     Path(ticker): Path<String>,
-) -> Result<Json<Value>, ServerError> {
-    let oracle = OracleStakers::new(&ticker).build()?.send()?;
-    let last_update = oracle.last_update()?;
-    let set_balances_parsed_data = parse_flp_balances_setting_res(&last_update)?;
-    Ok(Json(serde_json::to_value(&set_balances_parsed_data)?))
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Sourced>, ServerError> {
+    let oracle = OracleStakers::new(&ticker)
+        .build()
+        .map_err(|_| ServerError::not_found(format!("unknown oracle ticker {ticker}")))?;
+    if !is_live(&params) {
+        return Ok(Json(state.indexer.indexed_oracle_balances(&ticker).await?));
+    }
+    let (last_update, parsed) = live_call(move || {
+        let last_update = oracle.send()?.last_update()?;
+        let parsed = parse_flp_balances_setting_res_lenient(&last_update)?;
+        Ok((last_update, parsed))
+    })
+    .await?;
+    Ok(Json(
+        Sourced::live(serde_json::to_value(&parsed.rows)?, Some(last_update))
+            .with_rejected(parsed.rejected),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flp/delegators/{project}",
+    tag = "flp",
+    params(
+        ("project" = String, Path, description = "FLP process id"),
+    ),
+    responses(
+        (status = 200, description = "merged delegators snapshot (LSTs + AR)", body = ProjectSnapshot),
+        (status = 400, description = "malformed process id", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_flp_snapshot_handler(
+    State(state): State<AppState>,
     Path(project): Path<String>,
 ) -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+    let project: ProcessId = parse_id("project", &project)?;
+    let client = &state.indexer;
     let snapshot = client.latest_project_snapshot(&project).await?;
     Ok(Json(serde_json::to_value(snapshot)?))
 }
 
-pub async fn get_eoa_wallet_identity(Path(eoa): Path<String>) -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+#[utoipa::path(
+    get,
+    path = "/wallet/identity/eoa/{eoa}",
+    tag = "wallet",
+    params(
+        ("eoa" = String, Path, description = "EVM address"),
+    ),
+    responses(
+        (status = 200, description = "Arweave addresses linked to the EOA", body = Vec<IdentityLink>),
+        (status = 400, description = "malformed EVM address", body = ErrorBody),
+    )
+)]
+pub async fn get_eoa_wallet_identity(
+    State(state): State<AppState>,
+    Path(eoa): Path<String>,
+) -> Result<Json<Value>, ServerError> {
+    let eoa: EvmAddress = parse_id("eoa", &eoa)?;
+    let client = &state.indexer;
     let identities = client.eoa_identity_history(&eoa).await?;
     Ok(Json(serde_json::to_value(&identities)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/wallet/identity/ar-wallet/{address}",
+    tag = "wallet",
+    params(
+        ("address" = String, Path, description = "Arweave address"),
+    ),
+    responses(
+        (status = 200, description = "EOAs linked to the Arweave address", body = Vec<IdentityLink>),
+        (status = 400, description = "malformed Arweave address", body = ErrorBody),
+    )
+)]
 pub async fn get_ar_wallet_identity(
+    State(state): State<AppState>,
     Path(address): Path<String>,
 ) -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+    let address: ArAddress = parse_id("address", &address)?;
+    let client = &state.indexer;
     let identities = client.wallet_identity_history(&address).await?;
     Ok(Json(serde_json::to_value(&identities)?))
 }
 
-pub async fn get_oracle_feed(Path(ticker): Path<String>) -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+/// recent token messages in a portfolio unless `?limit=` says otherwise
+const PORTFOLIO_ACTIVITY_LIMIT: u64 = 25;
+const MAX_PORTFOLIO_ACTIVITY: u64 = 100;
+
+fn portfolio_activity_limit(params: &HashMap<String, String>) -> u64 {
+    params
+        .get("limit")
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(PORTFOLIO_ACTIVITY_LIMIT)
+        .min(MAX_PORTFOLIO_ACTIVITY)
+}
+
+#[utoipa::path(
+    get,
+    path = "/wallet/portfolio/{address}",
+    tag = "wallet",
+    params(
+        ("address" = String, Path, description = "Arweave address"),
+        ("limit" = Option<u64>, Query, description = "max recent token messages, defaults to 25, max 100"),
+    ),
+    responses(
+        (status = 200, description = "linked EOAs, delegations, balances, positions and recent token activity of the wallet", body = WalletPortfolio),
+        (status = 400, description = "malformed Arweave address", body = ErrorBody),
+    )
+)]
+pub async fn get_wallet_portfolio(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<WalletPortfolio>, ServerError> {
+    let address: ArAddress = parse_id("address", &address)?;
+    let limit = portfolio_activity_limit(&params);
+    let portfolio = portfolio::wallet_portfolio(&state.indexer, &address, limit).await?;
+    Ok(Json(portfolio))
+}
+
+#[utoipa::path(
+    get,
+    path = "/wallet/portfolio/eoa/{eoa}",
+    tag = "wallet",
+    params(
+        ("eoa" = String, Path, description = "EVM address"),
+        ("limit" = Option<u64>, Query, description = "max recent token messages per wallet, defaults to 25, max 100"),
+    ),
+    responses(
+        (status = 200, description = "portfolios of every Arweave wallet linked to the EOA, with summed balances and positions", body = EoaPortfolio),
+        (status = 400, description = "malformed EVM address", body = ErrorBody),
+        (status = 404, description = "no wallet linked to the EOA", body = ErrorBody),
+    )
+)]
+pub async fn get_eoa_portfolio(
+    State(state): State<AppState>,
+    Path(eoa): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<EoaPortfolio>, ServerError> {
+    let eoa: EvmAddress = parse_id("eoa", &eoa)?;
+    let limit = portfolio_activity_limit(&params);
+    let portfolio = portfolio::eoa_portfolio(&state.indexer, &eoa, limit).await?;
+    Ok(Json(portfolio))
+}
+
+#[utoipa::path(
+    get,
+    path = "/oracle/feed/{ticker}",
+    tag = "oracle",
+    params(
+        ("ticker" = String, Path, description = "`usds`, `dai` or `steth`"),
+    ),
+    responses(
+        (status = 200, description = "recent indexed oracle feeds", body = OracleFeed),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
+pub async fn get_oracle_feed(
+    State(state): State<AppState>,
+    Path(ticker): Path<String>,
+) -> Result<Json<Value>, ServerError> {
+    let client = &state.indexer;
     let feed = client.oracle_snapshot_feed(&ticker, 25).await?;
-    let metadata = OracleStakers::new(&ticker).oracle.metadata()?;
-    let res = json!({
-        "oracle_pid": metadata.ao_pid,
-        "oracle_evm_address": metadata.evm_address,
-        "recent_indexed_feeds": feed
-    });
-    Ok(Json(res))
+    let metadata = OracleStakers::new(&ticker)
+        .oracle
+        .metadata()
+        .map_err(|_| ServerError::not_found(format!("unknown oracle ticker {ticker}")))?;
+    let res = OracleFeed {
+        oracle_pid: metadata.ao_pid.into(),
+        oracle_evm_address: metadata.evm_address.into(),
+        recent_indexed_feeds: feed,
+    };
+    Ok(Json(serde_json::to_value(&res)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/wallet/delegation-mappings/{address}",
+    tag = "wallet",
+    params(
+        ("address" = String, Path, description = "Arweave address"),
+    ),
+    responses(
+        (status = 200, description = "delegation preference history", body = Vec<DelegationMappingHistory>),
+        (status = 400, description = "malformed Arweave address", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_wallet_delegation_mappings_history(
+    State(state): State<AppState>,
     Path(address): Path<String>,
 ) -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+    let address: ArAddress = parse_id("address", &address)?;
+    let client = &state.indexer;
     let history: Vec<DelegationMappingHistory> =
         client.wallet_delegation_mappings(&address).await?;
     Ok(Json(serde_json::to_value(&history)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/delegation-mappings/heights",
+    tag = "wallet",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 25"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "latest indexed delegation mapping txs", body = Page<DelegationHeight>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_delegation_mapping_heights(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(25);
-    let client = AtlasIndexerClient::new().await?;
-    let rows: Vec<DelegationHeight> = client.latest_delegation_heights(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = PageParams::parse(&params, 25, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows: Vec<DelegationHeight> = client
+        .latest_delegation_heights(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, |row| Cursor::new(row.height, &row.tx_id));
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flp/delegators/multi",
+    tag = "flp",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "delegators delegating to at least 2 FLPs", body = Page<MultiDelegator>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_multi_project_delegators(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows: Vec<MultiDelegator> = client
+        .multi_project_delegators(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.project_count, &row.wallet)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/flp/leaderboard",
+    tag = "flp",
+    params(
+        ("ticker" = Option<String>, Query, description = "`usds`, `dai` or `steth`, every ticker when omitted"),
+        ("sort" = Option<String>, Query, description = "`amount` (default), `ar_amount` or `delegators`"),
+    ),
+    responses(
+        (status = 200, description = "FLPs ranked per ticker on the latest oracle cycle, with 1d/7d/30d deltas", body = Vec<FlpLeaderboard>),
+        (status = 400, description = "invalid sort", body = ErrorBody),
+        (status = 404, description = "no indexed cycles", body = ErrorBody),
+    )
+)]
+pub async fn get_flp_leaderboard(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Vec<FlpLeaderboard>>, ServerError> {
+    let ticker = params
+        .get("ticker")
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty());
+    let sort = match params.get("sort") {
+        Some(value) => LeaderboardSort::parse(value).ok_or_else(|| {
+            ServerError::bad_param(
+                "sort",
+                "invalid sort (expected amount, ar_amount or delegators)",
+            )
+        })?,
+        None => LeaderboardSort::default(),
+    };
+    let boards = leaderboard::flp_leaderboards(&state.indexer, ticker.as_deref(), sort).await?;
+    Ok(Json(boards))
+}
+
+#[utoipa::path(
+    get,
+    path = "/flp/overlap",
+    tag = "flp",
+    params(
+        ("ticker" = Option<String>, Query, description = "`usds`, `dai` or `steth`, every ticker when omitted"),
+    ),
+    responses(
+        (status = 200, description = "shared delegators, shared stake and Jaccard similarity per FLP pair, with the spread of wallets over FLPs, from the latest cycles", body = OverlapMatrix),
+        (status = 404, description = "no indexed positions", body = ErrorBody),
+    )
+)]
+pub async fn get_flp_overlap(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<OverlapMatrix>, ServerError> {
+    let ticker = params
+        .get("ticker")
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty());
+    let matrix = overlap::overlap_matrix(&state.indexer, ticker.as_deref()).await?;
+    Ok(Json(matrix))
+}
+
+#[utoipa::path(
+    get,
+    path = "/flp/{project}/analytics",
+    tag = "flp",
+    params(
+        ("project" = String, Path, description = "FLP process id"),
+        ("period" = Option<String>, Query, description = "cohort period, `month` (default) or `week`"),
+        ("cohorts" = Option<u64>, Query, description = "newest cohorts returned, defaults to 12, max 104"),
+        ("days" = Option<u32>, Query, description = "days of churn counts, defaults to 30, max 365"),
+    ),
+    responses(
+        (status = 200, description = "cohort retention, delegation lifetime and daily churn of the FLP's delegators", body = ProjectAnalytics),
+        (status = 400, description = "malformed process id or invalid period", body = ErrorBody),
+        (status = 404, description = "no delegation mappings to the FLP", body = ErrorBody),
+    )
+)]
+pub async fn get_project_analytics(
+    State(state): State<AppState>,
+    Path(project): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<ProjectAnalytics>, ServerError> {
+    let project: ProcessId = parse_id("project", &project)?;
+    let period = match params.get("period") {
+        Some(value) => CohortPeriod::parse(value).ok_or_else(|| {
+            ServerError::bad_param("period", "invalid period (expected month or week)")
+        })?,
+        None => CohortPeriod::default(),
+    };
+    let cohorts = params
+        .get("cohorts")
+        .and_then(|v| v.parse::<usize>().ok())
         .filter(|v| *v > 0)
-        .unwrap_or(100);
-    let client = AtlasIndexerClient::new().await?;
-    let rows: Vec<MultiDelegator> = client.multi_project_delegators(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+        .unwrap_or(12)
+        .min(104);
+    let days = parse_u32_param("days", params.get("days"))?
+        .filter(|v| *v > 0)
+        .unwrap_or(30)
+        .min(365);
+    let analytics =
+        analytics::project_analytics(&state.indexer, &project, period, cohorts, days).await?;
+    Ok(Json(analytics))
+}
+
+#[utoipa::path(
+    get,
+    path = "/flp/flows",
+    tag = "flp",
+    params(
+        ("from" = Option<String>, Query, description = "first day, YYYY-MM-DD, defaults to 30 days before `to`"),
+        ("to" = Option<String>, Query, description = "last day, YYYY-MM-DD, defaults to today"),
+    ),
+    responses(
+        (status = 200, description = "factor-weighted stake moved between FLPs by the delegation mapping changes of the window", body = FlowMatrix),
+        (status = 400, description = "invalid day or window over a year", body = ErrorBody),
+    )
+)]
+pub async fn get_flp_flows(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<FlowMatrix>, ServerError> {
+    let parse_day = |name: &str| {
+        params
+            .get(name)
+            .map(|v| {
+                NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").map_err(|_| {
+                    ServerError::bad_param(name, "invalid day format (expected YYYY-MM-DD)")
+                })
+            })
+            .transpose()
+    };
+    let to_day = parse_day("to")?.unwrap_or_else(|| Utc::now().date_naive());
+    let from_day = parse_day("from")?.unwrap_or(to_day - Duration::days(30));
+    if from_day > to_day || to_day - from_day > Duration::days(366) {
+        return Err(ServerError::bad_param(
+            "from",
+            "`from` must be before `to` and at most a year earlier",
+        ));
+    }
+    let matrix = analytics::flow_matrix(&state.indexer, from_day, to_day).await?;
+    Ok(Json(matrix))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flp/{project}/cycles",
+    tag = "flp",
+    params(
+        ("project" = String, Path, description = "FLP process id"),
+        ("ticker" = Option<String>, Query, description = "oracle ticker filter"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 25"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "delegated totals per oracle cycle", body = Page<ProjectCycleTotal>),
+        (status = 400, description = "malformed process id or invalid cursor", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_project_cycle_totals(
+    State(state): State<AppState>,
     Path(project): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(25);
+    let project: ProcessId = parse_id("project", &project)?;
+    let page = PageParams::parse(&params, 25, MAX_PAGE_SIZE)?;
     let ticker = params.get("ticker").cloned();
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let rows: Vec<ProjectCycleTotal> = client
-        .project_cycle_totals(&project, ticker.as_deref(), limit)
+        .project_cycle_totals(
+            &project,
+            ticker.as_deref(),
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.ts.timestamp_millis() as u64, &row.tx_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flp/minting/{project}",
+    tag = "flp",
+    params(
+        ("project" = String, Path, description = "FLP process id"),
+        ("live" = Option<bool>, Query, description = "read from the Arweave gateway instead of the index"),
+    ),
+    responses(
+        (status = 200, description = "latest `Own-Minting-Report` of the FLP", body = Sourced),
+        (status = 400, description = "malformed process id", body = ErrorBody),
+        (status = 404, description = "not indexed", body = ErrorBody),
+        (status = 502, description = "gateway error", body = ErrorBody),
+    )
+)]
 pub async fn get_flp_own_minting_report_handler(
+    State(state): State<AppState>,
     Path(project): Path<String>,
-) -> Result<Json<Value>, ServerError> {
-    let report_id: String = get_flp_own_minting_report(&project)?;
-    let report = parse_own_minting_report(&report_id)?;
-    Ok(Json(serde_json::to_value(&report)?))
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Sourced>, ServerError> {
+    let project: ProcessId = parse_id("project", &project)?;
+    if !is_live(&params) {
+        return Ok(Json(state.indexer.indexed_minting_report(&project).await?));
+    }
+    let pid = project.to_string();
+    let (report_id, report) = live_call(move || {
+        let report_id: String = get_flp_own_minting_report(&pid)?;
+        let report = parse_own_minting_report(&report_id)?;
+        Ok((report_id, report))
+    })
+    .await?;
+    Ok(Json(Sourced::live(
+        minting_report_value(&project, report)?,
+        Some(report_id),
+    )))
 }
 
+#[utoipa::path(
+    get,
+    path = "/flp/{project}/yield/{wallet}",
+    tag = "flp",
+    params(
+        ("project" = String, Path, description = "FLP process id"),
+        ("wallet" = String, Path, description = "Arweave address of the delegator"),
+        ("limit" = Option<u64>, Query, description = "newest distribution ticks, defaults to 30, max 200"),
+        ("weights" = Option<String>, Query, description = "value of one LST unit per ticker to sum oracles, e.g. `steth:3000,dai:1`, 1 when unset"),
+    ),
+    responses(
+        (status = 200, description = "per-tick estimate of the project tokens minted for the wallet, pro-rata to its delegated stake", body = Object),
+        (status = 400, description = "malformed ids or invalid weights", body = ErrorBody),
+        (status = 404, description = "no indexed minting reports", body = ErrorBody),
+    )
+)]
+pub async fn get_flp_wallet_yield(
+    State(state): State<AppState>,
+    Path((project, wallet)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<WalletYield>, ServerError> {
+    let project: ProcessId = parse_id("project", &project)?;
+    let wallet: ArAddress = parse_id("wallet", &wallet)?;
+    let limit = params
+        .get("limit")
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(30)
+        .min(200);
+    let mut calculator = YieldCalculator::new();
+    for pair in params
+        .get("weights")
+        .map(|v| {
+            v.split(',')
+                .filter(|p| !p.trim().is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+    {
+        let weight = pair
+            .split_once(':')
+            .and_then(|(ticker, weight)| Some((ticker.trim(), weight.trim().parse::<f64>().ok()?)))
+            .filter(|(ticker, weight)| !ticker.is_empty() && weight.is_finite() && *weight >= 0.0);
+        let Some((ticker, weight)) = weight else {
+            return Err(ServerError::bad_param(
+                "weights",
+                "invalid weights (expected ticker:weight pairs, e.g. steth:3000,dai:1)",
+            ));
+        };
+        calculator = calculator.with_weight(ticker, weight);
+    }
+    let history =
+        yields::wallet_yield(&state.indexer, &project, &wallet, limit, &calculator).await?;
+    Ok(Json(history))
+}
+
+#[utoipa::path(
+    post,
+    path = "/flp/simulate",
+    tag = "flp",
+    request_body(content = SimulationRequest, description = "wallet and the preference set to preview"),
+    responses(
+        (status = 200, description = "per (FLP, ticker) totals and wallet share before and after the change", body = Object),
+        (status = 400, description = "malformed wallet, factors over 10000 or duplicate targets", body = ErrorBody),
+        (status = 404, description = "no indexed balances for the wallet", body = ErrorBody),
+    )
+)]
+pub async fn post_flp_simulation(
+    State(state): State<AppState>,
+    Json(request): Json<SimulationRequest>,
+) -> Result<Json<Simulation>, ServerError> {
+    let simulation = simulation::simulate_delegation(&state.indexer, &request).await?;
+    Ok(Json(simulation))
+}
+
+#[utoipa::path(
+    get,
+    path = "/flp/metadata/all",
+    tag = "flp",
+    responses(
+        (status = 200, description = "tracked FLPs and their metadata", body = Vec<Object>),
+    )
+)]
 pub async fn get_all_projects_metadata_handler() -> Result<Json<Value>, ServerError> {
     let projects = Project::get_all();
     Ok(Json(serde_json::to_value(&projects)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/explorer/blocks",
+    tag = "explorer",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "last indexed ao.TN.1 blocks", body = Page<ExplorerBlock>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+    )
+)]
 pub async fn get_explorer_blocks(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
-    let client = AtlasIndexerClient::new().await?;
-    let rows: Vec<ExplorerBlock> = client.latest_explorer_blocks(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows: Vec<ExplorerBlock> = client
+        .latest_explorer_blocks(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, |row| Cursor::new(row.height, ""));
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/explorer/day",
+    tag = "explorer",
+    params(
+        ("day" = Option<String>, Query, description = "YYYY-MM-DD, defaults to today"),
+    ),
+    responses(
+        (status = 200, description = "ao.TN.1 stats of the day", body = ExplorerDayStats),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+    )
+)]
 pub async fn get_explorer_day_stats(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
     let day_str = params
@@ -217,39 +755,78 @@ pub async fn get_explorer_day_stats(
         .map(|s| s.to_string())
         .unwrap_or_else(|| Utc::now().date_naive().to_string());
     let day = NaiveDate::parse_from_str(&day_str, "%Y-%m-%d")
-        .map_err(|_| ServerError::from(anyhow!("invalid day format (expected YYYY-MM-DD)")))?;
-    let client = AtlasIndexerClient::new().await?;
+        .map_err(|_| ServerError::bad_param("day", "invalid day format (expected YYYY-MM-DD)"))?;
+    let client = &state.indexer;
     let stats: ExplorerDayStats = client.daily_explorer_stats(day).await?;
     Ok(Json(serde_json::to_value(&stats)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/explorer/days",
+    tag = "explorer",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 7"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "ao.TN.1 stats of the last N days", body = Page<ExplorerDayStats>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+    )
+)]
 pub async fn get_explorer_recent_days(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(7);
-    let client = AtlasIndexerClient::new().await?;
-    let rows = client.recent_explorer_days(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = PageParams::parse(&params, 7, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows = client
+        .recent_explorer_days(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, day_cursor);
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/explorer/blocks",
+    tag = "mainnet",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "last indexed ao.N.1 blocks", body = Page<ExplorerBlock>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_explorer_blocks(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
-    let client = AtlasIndexerClient::new().await?;
-    let rows = client.mainnet_explorer_blocks(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows = client
+        .mainnet_explorer_blocks(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, |row| Cursor::new(row.height, ""));
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/explorer/day",
+    tag = "mainnet",
+    params(
+        ("day" = Option<String>, Query, description = "YYYY-MM-DD, defaults to today"),
+    ),
+    responses(
+        (status = 200, description = "ao.N.1 stats of the day", body = ExplorerDayStats),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_explorer_day_stats(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
     let day_str = params
@@ -257,108 +834,211 @@ pub async fn get_mainnet_explorer_day_stats(
         .map(|s| s.to_string())
         .unwrap_or_else(|| Utc::now().date_naive().to_string());
     let day = NaiveDate::parse_from_str(&day_str, "%Y-%m-%d")
-        .map_err(|_| ServerError::from(anyhow!("invalid day format (expected YYYY-MM-DD)")))?;
-    let client = AtlasIndexerClient::new().await?;
+        .map_err(|_| ServerError::bad_param("day", "invalid day format (expected YYYY-MM-DD)"))?;
+    let client = &state.indexer;
     let stats = client.mainnet_daily_explorer_stats(day).await?;
     Ok(Json(serde_json::to_value(&stats)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/explorer/days",
+    tag = "mainnet",
+    params(
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 7"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "ao.N.1 stats of the last N days", body = Page<ExplorerDayStats>),
+        (status = 400, description = "invalid cursor", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_explorer_recent_days(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(7);
-    let client = AtlasIndexerClient::new().await?;
-    let rows = client.mainnet_recent_explorer_days(limit).await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = PageParams::parse(&params, 7, MAX_PAGE_SIZE)?;
+    let client = &state.indexer;
+    let rows = client
+        .mainnet_recent_explorer_days(page.fetch_limit(), page.cursor.as_ref())
+        .await?;
+    let page = Page::from_rows(rows, page.limit, day_cursor);
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/messages/recent",
+    tag = "mainnet",
+    params(
+        ("protocol" = Option<String>, Query, description = "data protocol `A` or `B`, both when omitted"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "recently indexed mainnet messages", body = Page<MainnetMessage>),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_recent_messages(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
     let protocol = parse_protocol(params.get("protocol"))?;
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let rows = client
-        .recent_mainnet_messages(protocol.as_deref(), limit)
+        .recent_mainnet_messages(
+            protocol.as_deref(),
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/messages/block/{height}",
+    tag = "mainnet",
+    params(
+        ("height" = u32, Path, description = "Arweave block height"),
+        ("protocol" = Option<String>, Query, description = "data protocol `A` or `B`, both when omitted"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 500"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "mainnet messages settled at the height", body = Page<MainnetMessage>),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_block_messages(
+    State(state): State<AppState>,
     Path(height): Path<u32>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(500);
+    let page = PageParams::parse(&params, 500, MAX_PAGE_SIZE)?;
     let protocol = parse_protocol(params.get("protocol"))?;
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let rows = client
-        .block_mainnet_messages(protocol.as_deref(), height, limit)
+        .block_mainnet_messages(
+            protocol.as_deref(),
+            height,
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/mainnet/messages/tags",
+    tag = "mainnet",
+    params(
+        ("key" = String, Query, description = "tag name, case sensitive"),
+        ("value" = String, Query, description = "tag value"),
+        ("protocol" = Option<String>, Query, description = "data protocol `A` or `B`, both when omitted"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "mainnet messages matching the tag", body = Page<MainnetMessage>),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+    )
+)]
 pub async fn get_mainnet_messages_by_tag(
+    State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
     let protocol = parse_protocol(params.get("protocol"))?;
     let key = params
         .get("key")
         .map(|v| v.trim().to_string())
         .filter(|v| !v.is_empty())
-        .ok_or_else(|| ServerError::from(anyhow!("missing tag key")))?;
+        .ok_or_else(|| ServerError::bad_param("key", "missing tag key"))?;
     let value = params
         .get("value")
         .map(|v| v.trim().to_string())
         .filter(|v| !v.is_empty())
-        .ok_or_else(|| ServerError::from(anyhow!("missing tag value")))?;
-    let client = AtlasIndexerClient::new().await?;
+        .ok_or_else(|| ServerError::bad_param("value", "missing tag value"))?;
+    let client = &state.indexer;
     let tag_keys = build_tag_key_variants(protocol.as_deref(), &key);
     if tag_keys.is_empty() {
-        return Err(ServerError::from(anyhow!("invalid tag key")));
+        return Err(ServerError::bad_param("key", "invalid tag key"));
     }
     let rows = client
-        .mainnet_messages_by_tag(protocol.as_deref(), &tag_keys, &value, limit)
+        .mainnet_messages_by_tag(
+            protocol.as_deref(),
+            &tag_keys,
+            &value,
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
-pub async fn get_mainnet_indexing_info() -> Result<Json<Value>, ServerError> {
-    let client = AtlasIndexerClient::new().await?;
+#[utoipa::path(
+    get,
+    path = "/mainnet/info",
+    tag = "mainnet",
+    responses(
+        (status = 200, description = "mainnet indexer progress per protocol", body = Vec<MainnetProtocolInfo>),
+    )
+)]
+pub async fn get_mainnet_indexing_info(
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ServerError> {
+    let client = &state.indexer;
     let rows = client.mainnet_indexing_info().await?;
     Ok(Json(serde_json::to_value(&rows)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/txs",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+        ("source" = Option<String>, Query, description = "`transfer` or `process`"),
+        ("action" = Option<String>, Query, description = "`Action` tag value"),
+        ("recipient" = Option<String>, Query),
+        ("sender" = Option<String>, Query),
+        ("min_amount" = Option<String>, Query, description = "human units, 12 decimals max"),
+        ("max_amount" = Option<String>, Query, description = "human units, 12 decimals max"),
+        ("from_ts" = Option<u64>, Query),
+        ("to_ts" = Option<u64>, Query),
+        ("block_min" = Option<u32>, Query),
+        ("block_max" = Option<u32>, Query),
+        ("order" = Option<String>, Query, description = "`asc` or `desc` by block height"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "indexed token messages", body = Page<AoTokenMessage>),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+        (status = 422, description = "unusable amount", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_txs(
+    State(state): State<AppState>,
     Path(token): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
     let token = parse_token(&token)?;
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
-    let offset = params
-        .get("offset")
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(0);
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
     let source = params
         .get("source")
         .map(|v| v.trim().to_ascii_lowercase())
@@ -379,13 +1059,13 @@ pub async fn get_ao_token_txs(
         .get("order")
         .map(|v| v.trim().to_ascii_lowercase())
         .filter(|v| v == "asc" || v == "desc");
-    let min_qty = parse_amount_param(params.get("min_amount"))?;
-    let max_qty = parse_amount_param(params.get("max_amount"))?;
-    let from_ts = parse_u64_param(params.get("from_ts"))?;
-    let to_ts = parse_u64_param(params.get("to_ts"))?;
-    let block_min = parse_u32_param(params.get("block_min"))?;
-    let block_max = parse_u32_param(params.get("block_max"))?;
-    let client = AtlasIndexerClient::new().await?;
+    let min_qty = parse_amount_param("min_amount", params.get("min_amount"))?;
+    let max_qty = parse_amount_param("max_amount", params.get("max_amount"))?;
+    let from_ts = parse_u64_param("from_ts", params.get("from_ts"))?;
+    let to_ts = parse_u64_param("to_ts", params.get("to_ts"))?;
+    let block_min = parse_u32_param("block_min", params.get("block_min"))?;
+    let block_max = parse_u32_param("block_max", params.get("block_max"))?;
+    let client = &state.indexer;
     let rows: Vec<AoTokenMessage> = client
         .ao_token_messages(
             &token,
@@ -400,32 +1080,66 @@ pub async fn get_ao_token_txs(
             recipient.as_deref(),
             sender.as_deref(),
             order.as_deref(),
-            limit,
-            offset,
+            page.fetch_limit(),
+            page.cursor.as_ref(),
         )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/txs/{msg_id}",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+        ("msg_id" = String, Path),
+    ),
+    responses(
+        (status = 200, description = "token message by id, with tags", body = Vec<AoTokenMessage>),
+        (status = 400, description = "malformed message id", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_tx(
+    State(state): State<AppState>,
     Path((token, msg_id)): Path<(String, String)>,
 ) -> Result<Json<Value>, ServerError> {
     let token = parse_token(&token)?;
-    let client = AtlasIndexerClient::new().await?;
+    let msg_id: MessageId = parse_id("msg_id", &msg_id)?;
+    let client = &state.indexer;
     let rows = client.ao_token_message_by_id(&token, &msg_id).await?;
     Ok(Json(serde_json::to_value(&rows)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/txs/tags",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+        ("key" = String, Query, description = "tag name"),
+        ("value" = String, Query, description = "tag value"),
+        ("source" = Option<String>, Query, description = "`transfer` or `process`"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 100"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "token messages matching the tag", body = Page<AoTokenMessage>),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_messages_by_tag(
+    State(state): State<AppState>,
     Path(token): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
     let token = parse_token(&token)?;
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<u64>().ok())
-        .filter(|v| *v > 0)
-        .unwrap_or(100);
+    let page = PageParams::parse(&params, 100, MAX_PAGE_SIZE)?;
     let source = params
         .get("source")
         .map(|v| v.trim().to_ascii_lowercase())
@@ -434,29 +1148,66 @@ pub async fn get_ao_token_messages_by_tag(
         .get("key")
         .map(|v| v.trim().to_string())
         .filter(|v| !v.is_empty())
-        .ok_or_else(|| ServerError::from(anyhow!("missing tag key")))?;
+        .ok_or_else(|| ServerError::bad_param("key", "missing tag key"))?;
     let value = params
         .get("value")
         .map(|v| v.trim().to_string())
         .filter(|v| !v.is_empty())
-        .ok_or_else(|| ServerError::from(anyhow!("missing tag value")))?;
-    let client = AtlasIndexerClient::new().await?;
+        .ok_or_else(|| ServerError::bad_param("value", "missing tag value"))?;
+    let client = &state.indexer;
     let rows = client
-        .ao_token_messages_by_tag(&token, source.as_deref(), &key, &value, limit)
+        .ao_token_messages_by_tag(
+            &token,
+            source.as_deref(),
+            &key,
+            &value,
+            page.fetch_limit(),
+            page.cursor.as_ref(),
+        )
         .await?;
-    Ok(Json(serde_json::to_value(&rows)?))
+    let page = Page::from_rows(rows, page.limit, |row| {
+        Cursor::new(row.block_height, &row.msg_id)
+    });
+    Ok(Json(serde_json::to_value(&page)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/info",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+    ),
+    responses(
+        (status = 200, description = "token indexer progress", body = AoTokenIndexingInfo),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_indexing_info(
+    State(state): State<AppState>,
     Path(token): Path<String>,
 ) -> Result<Json<Value>, ServerError> {
     let token = parse_token(&token)?;
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let info = client.ao_token_indexing_info(&token).await?;
     Ok(Json(serde_json::to_value(&info)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/top/frequency",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 25"),
+    ),
+    responses(
+        (status = 200, description = "counts per Action and top senders/recipients", body = AoTokenFrequencyInfo),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_frequency(
+    State(state): State<AppState>,
     Path(token): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
@@ -466,12 +1217,26 @@ pub async fn get_ao_token_frequency(
         .and_then(|v| v.parse::<u64>().ok())
         .filter(|v| *v > 0)
         .unwrap_or(25);
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let info = client.ao_token_frequency(&token, limit).await?;
     Ok(Json(serde_json::to_value(&info)?))
 }
 
+#[utoipa::path(
+    get,
+    path = "/token/{token}/top/richlist",
+    tag = "token",
+    params(
+        ("token" = String, Path, description = "`ao` or `pi`"),
+        ("limit" = Option<u64>, Query, description = "max rows, defaults to 25"),
+    ),
+    responses(
+        (status = 200, description = "top spenders/receivers by Quantity", body = AoTokenRichlist),
+        (status = 404, description = "not found", body = ErrorBody),
+    )
+)]
 pub async fn get_ao_token_richlist(
+    State(state): State<AppState>,
     Path(token): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Value>, ServerError> {
@@ -481,28 +1246,52 @@ pub async fn get_ao_token_richlist(
         .and_then(|v| v.parse::<u64>().ok())
         .filter(|v| *v > 0)
         .unwrap_or(25);
-    let client = AtlasIndexerClient::new().await?;
+    let client = &state.indexer;
     let info = client.ao_token_richlist(&token, limit).await?;
     Ok(Json(serde_json::to_value(&info)?))
 }
 
-fn parse_protocol(value: Option<&String>) -> Result<Option<String>, ServerError> {
+/// runs a blocking gateway read off the async workers, its errors as
+/// upstream ones
+async fn live_call<T: Send + 'static>(
+    call: impl FnOnce() -> Result<T, anyhow::Error> + Send + 'static,
+) -> Result<T, ServerError> {
+    tokio::task::spawn_blocking(call)
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(ServerError::upstream)
+}
+
+/// `?live=true` skips the index and reads straight from the Arweave gateway
+fn is_live(params: &HashMap<String, String>) -> bool {
+    params
+        .get("live")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true") || v == "1")
+}
+
+pub(crate) fn day_cursor(day: &ExplorerDayStats) -> Cursor {
+    let ts = day.day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    Cursor::new(ts as u64, "")
+}
+
+pub(crate) fn parse_protocol(value: Option<&String>) -> Result<Option<String>, ServerError> {
     if let Some(p) = value {
         let normalized = p.trim().to_ascii_uppercase();
         if normalized.is_empty() {
             return Ok(None);
         }
         if normalized != "A" && normalized != "B" {
-            return Err(ServerError::from(anyhow!(
-                "invalid protocol (expected A or B)"
-            )));
+            return Err(ServerError::bad_param(
+                "protocol",
+                "invalid protocol (expected A or B)",
+            ));
         }
         return Ok(Some(normalized));
     }
     Ok(None)
 }
 
-fn build_tag_key_variants(protocol: Option<&str>, key: &str) -> Vec<String> {
+pub(crate) fn build_tag_key_variants(protocol: Option<&str>, key: &str) -> Vec<String> {
     let trimmed = key.trim();
     if trimmed.is_empty() {
         return Vec::new();
@@ -539,7 +1328,7 @@ fn to_header_case(input: &str) -> String {
     result
 }
 
-fn parse_u64_param(value: Option<&String>) -> Result<Option<u64>, ServerError> {
+fn parse_u64_param(name: &str, value: Option<&String>) -> Result<Option<u64>, ServerError> {
     let Some(raw) = value else {
         return Ok(None);
     };
@@ -549,11 +1338,11 @@ fn parse_u64_param(value: Option<&String>) -> Result<Option<u64>, ServerError> {
     }
     let parsed = trimmed
         .parse::<u64>()
-        .map_err(|_| ServerError::from(anyhow!("invalid u64 value")))?;
+        .map_err(|_| ServerError::bad_param(name, "invalid u64 value"))?;
     Ok(Some(parsed))
 }
 
-fn parse_u32_param(value: Option<&String>) -> Result<Option<u32>, ServerError> {
+fn parse_u32_param(name: &str, value: Option<&String>) -> Result<Option<u32>, ServerError> {
     let Some(raw) = value else {
         return Ok(None);
     };
@@ -563,11 +1352,11 @@ fn parse_u32_param(value: Option<&String>) -> Result<Option<u32>, ServerError> {
     }
     let parsed = trimmed
         .parse::<u32>()
-        .map_err(|_| ServerError::from(anyhow!("invalid u32 value")))?;
+        .map_err(|_| ServerError::bad_param(name, "invalid u32 value"))?;
     Ok(Some(parsed))
 }
 
-fn parse_amount_param(value: Option<&String>) -> Result<Option<String>, ServerError> {
+fn parse_amount_param(name: &str, value: Option<&String>) -> Result<Option<String>, ServerError> {
     let Some(raw) = value else {
         return Ok(None);
     };
@@ -575,52 +1364,287 @@ fn parse_amount_param(value: Option<&String>) -> Result<Option<String>, ServerEr
     if trimmed.is_empty() {
         return Ok(None);
     }
-    Ok(Some(parse_human_amount_to_raw(trimmed)?))
+    let amount = Amount::parse_human(trimmed, AO_DECIMALS).map_err(|err| {
+        ServerError::unprocessable(
+            err.to_string(),
+            json!({ "param": name, "value": trimmed, "max_decimals": AO_DECIMALS }),
+        )
+    })?;
+    Ok(Some(amount.raw.to_string()))
 }
 
-fn parse_human_amount_to_raw(input: &str) -> Result<String, ServerError> {
-    let mut parts = input.split('.');
-    let whole_part = parts.next().unwrap_or("");
-    let frac_part = parts.next().unwrap_or("");
-    if parts.next().is_some() {
-        return Err(ServerError::from(anyhow!("invalid amount format")));
-    }
-    let whole = if whole_part.is_empty() {
-        "0"
-    } else {
-        whole_part
+fn parse_feed(value: &str) -> Result<Feed, ServerError> {
+    Feed::parse(value).ok_or_else(|| ServerError::NotFound {
+        message: format!("unknown live feed {value}"),
+        details: Some(json!({ "supported": ["mainnet", "token", "delegations", "oracle"] })),
+    })
+}
+
+/// same filter params as the REST endpoints of each feed
+fn parse_live_filter(params: &HashMap<String, String>) -> Result<LiveFilter, ServerError> {
+    let text = |name: &str| {
+        params
+            .get(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
     };
-    if !whole.chars().all(|c| c.is_ascii_digit()) {
-        return Err(ServerError::from(anyhow!("invalid amount format")));
-    }
-    if !frac_part.chars().all(|c| c.is_ascii_digit()) {
-        return Err(ServerError::from(anyhow!("invalid amount format")));
-    }
-    if frac_part.len() > 12 {
-        return Err(ServerError::from(anyhow!(
-            "amount has more than 12 decimal places"
-        )));
-    }
-    let mut raw = String::with_capacity(whole.len() + 12);
-    raw.push_str(whole);
-    raw.push_str(frac_part);
-    for _ in 0..(12 - frac_part.len()) {
-        raw.push('0');
-    }
-    let trimmed = raw.trim_start_matches('0');
-    if trimmed.is_empty() {
-        return Ok("0".to_string());
+    let protocol = parse_protocol(params.get("protocol"))?;
+    let tag_keys = text("key")
+        .map(|key| build_tag_key_variants(protocol.as_deref(), &key))
+        .unwrap_or_default();
+    let token = text("token").map(|token| parse_token(&token)).transpose()?;
+    let source = text("source").map(|v| v.to_ascii_lowercase());
+    if source
+        .as_deref()
+        .is_some_and(|v| v != "transfer" && v != "process")
+    {
+        return Err(ServerError::bad_param(
+            "source",
+            "invalid source (expected transfer or process)",
+        ));
     }
-    Ok(trimmed.to_string())
+    Ok(LiveFilter {
+        protocol,
+        tag_keys,
+        tag_value: text("value"),
+        token,
+        source,
+        action: text("action"),
+        sender: text("sender"),
+        recipient: text("recipient"),
+        ticker: text("ticker").map(|v| v.to_ascii_lowercase()),
+        wallet: text("wallet"),
+    })
+}
+
+/// a validated id from a path or body value, 400 naming `param` otherwise
+pub(crate) fn parse_id<T>(param: &str, value: &str) -> Result<T, ServerError>
+where
+    T: FromStr<Err = anyhow::Error>,
+{
+    value
+        .parse()
+        .map_err(|err: anyhow::Error| ServerError::bad_param(param, err.to_string()))
 }
 
-fn parse_token(value: &str) -> Result<String, ServerError> {
+pub(crate) fn parse_token(value: &str) -> Result<String, ServerError> {
     let token = value.trim().to_ascii_lowercase();
     if token.is_empty() {
-        return Err(ServerError::from(anyhow!("missing token")));
+        return Err(ServerError::bad_param("token", "missing token"));
     }
     if token != "ao" && token != "pi" {
-        return Err(ServerError::from(anyhow!("unsupported token")));
+        return Err(ServerError::NotFound {
+            message: format!("unsupported token {token}"),
+            details: Some(json!({ "supported": ["ao", "pi"] })),
+        });
     }
     Ok(token)
 }
+
+#[utoipa::path(
+    get,
+    path = "/live/{feed}",
+    tag = "live",
+    params(
+        ("feed" = String, Path, description = "`mainnet`, `token`, `delegations` or `oracle`"),
+        ("protocol" = Option<String>, Query, description = "mainnet `A` or `B`"),
+        ("key" = Option<String>, Query, description = "tag key, mainnet keys match both tag formats"),
+        ("value" = Option<String>, Query, description = "tag value"),
+        ("token" = Option<String>, Query, description = "`ao` or `pi`"),
+        ("source" = Option<String>, Query, description = "token `transfer` or `process`"),
+        ("action" = Option<String>, Query, description = "token `Action` tag value"),
+        ("sender" = Option<String>, Query, description = "token `Sender` tag, mainnet message owner"),
+        ("recipient" = Option<String>, Query, description = "token `Recipient` tag, mainnet message recipient"),
+        ("ticker" = Option<String>, Query, description = "oracle ticker"),
+        ("wallet" = Option<String>, Query, description = "delegating wallet or delegated to project"),
+    ),
+    responses(
+        (status = 200, description = "server-sent events named after the event `type`, plus `lagged` when events were skipped", content_type = "text/event-stream", body = LiveEvent),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "unknown feed or token", body = ErrorBody),
+    )
+)]
+pub async fn get_live_events(
+    State(state): State<AppState>,
+    Path(feed): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ServerError> {
+    let feed = parse_feed(&feed)?;
+    let filter = parse_live_filter(&params)?;
+    let rx = state.live.subscribe(feed);
+    Ok(Sse::new(live::sse_events(rx, filter)).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/live/{feed}/ws",
+    tag = "live",
+    params(
+        ("feed" = String, Path, description = "`mainnet`, `token`, `delegations` or `oracle`"),
+        ("protocol" = Option<String>, Query, description = "same filters as `/live/{feed}`"),
+        ("key" = Option<String>, Query),
+        ("value" = Option<String>, Query),
+        ("token" = Option<String>, Query),
+        ("source" = Option<String>, Query),
+        ("action" = Option<String>, Query),
+        ("sender" = Option<String>, Query),
+        ("recipient" = Option<String>, Query),
+        ("ticker" = Option<String>, Query),
+        ("wallet" = Option<String>, Query),
+    ),
+    responses(
+        (status = 101, description = "websocket upgrade, one JSON text frame per event", body = LiveEvent),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "unknown feed or token", body = ErrorBody),
+    )
+)]
+pub async fn get_live_ws(
+    State(state): State<AppState>,
+    Path(feed): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ServerError> {
+    let feed = parse_feed(&feed)?;
+    let filter = parse_live_filter(&params)?;
+    let rx = state.live.subscribe(feed);
+    Ok(ws
+        .on_upgrade(move |socket| live::forward_ws(socket, rx, filter))
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/usage",
+    tag = "admin",
+    params(
+        ("x-api-key" = String, Header, description = "API key flagged `admin`"),
+    ),
+    responses(
+        (status = 200, description = "per key usage since the server started, most expensive first", body = Vec<KeyUsage>),
+        (status = 401, description = "missing or unknown API key", body = ErrorBody),
+        (status = 403, description = "the key is not an admin key", body = ErrorBody),
+    )
+)]
+pub async fn get_api_usage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<KeyUsage>>, ServerError> {
+    state.limits.require_admin(&headers)?;
+    Ok(Json(state.limits.usage()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/graphql",
+    tag = "graphql",
+    responses(
+        (status = 200, description = "GraphiQL playground for the GraphQL API", content_type = "text/html", body = String),
+    )
+)]
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "`{\"query\": ..., \"variables\": {...}}`"),
+    responses(
+        (status = 200, description = "GraphQL response, resolver errors carry the REST error `code` in `extensions`", body = Object),
+    )
+)]
+pub async fn post_graphql(
+    State(state): State<AppState>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(state.graphql.execute(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/{dataset}",
+    tag = "export",
+    params(
+        ("dataset" = String, Path, description = "`delegators`, `delegation-mappings`, `token-transfers`, `mainnet-messages`, `explorer-days` or `mainnet-explorer-days`"),
+        ("format" = Option<String>, Query, description = "`csv`, `ndjson` (default) or `parquet`"),
+        ("project" = Option<String>, Query, description = "FLP process id, required by `delegators`"),
+        ("token" = Option<String>, Query, description = "`ao` or `pi`, required by `token-transfers`"),
+        ("ticker" = Option<String>, Query, description = "oracle ticker (`delegators`)"),
+        ("wallet" = Option<String>, Query, description = "delegating wallet (`delegation-mappings`)"),
+        ("protocol" = Option<String>, Query, description = "`A` or `B` (`mainnet-messages`)"),
+        ("source" = Option<String>, Query, description = "`transfer` or `process` (`token-transfers`)"),
+        ("action" = Option<String>, Query, description = "`Action` tag value (`token-transfers`)"),
+        ("sender" = Option<String>, Query, description = "`Sender` tag value (`token-transfers`)"),
+        ("recipient" = Option<String>, Query, description = "`Recipient` tag value (`token-transfers`)"),
+        ("block_min" = Option<u32>, Query),
+        ("block_max" = Option<u32>, Query),
+        ("from" = Option<String>, Query, description = "first day, YYYY-MM-DD (explorer days)"),
+        ("to" = Option<String>, Query, description = "last day, YYYY-MM-DD (explorer days)"),
+    ),
+    responses(
+        (status = 200, description = "every matching row, streamed in the requested format", content_type = "text/csv", body = String),
+        (status = 400, description = "invalid parameter", body = ErrorBody),
+        (status = 404, description = "unknown dataset or token", body = ErrorBody),
+    )
+)]
+pub async fn get_export(
+    State(state): State<AppState>,
+    Path(dataset): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, ServerError> {
+    let text = |name: &str| {
+        params
+            .get(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let format = match text("format") {
+        Some(value) => ExportFormat::parse(&value)
+            .map_err(|err| ServerError::bad_param("format", err.to_string()))?,
+        None => ExportFormat::default(),
+    };
+    let dataset = match dataset.as_str() {
+        "delegators" => ExportDataset::Delegators {
+            project: text("project")
+                .ok_or_else(|| ServerError::bad_param("project", "missing project"))?,
+        },
+        "delegation-mappings" => ExportDataset::DelegationMappings,
+        "token-transfers" => ExportDataset::TokenTransfers {
+            token: parse_token(&text("token").unwrap_or_default())?,
+        },
+        "mainnet-messages" => ExportDataset::MainnetMessages,
+        "explorer-days" => ExportDataset::ExplorerDays { mainnet: false },
+        "mainnet-explorer-days" => ExportDataset::ExplorerDays { mainnet: true },
+        other => {
+            return Err(ServerError::not_found(format!(
+                "unknown export dataset {other}"
+            )));
+        }
+    };
+    let parse_day = |name: &str| {
+        text(name)
+            .map(|v| {
+                NaiveDate::parse_from_str(&v, "%Y-%m-%d")
+                    .map(|day| day.to_string())
+                    .map_err(|_| {
+                        ServerError::bad_param(name, "invalid day format (expected YYYY-MM-DD)")
+                    })
+            })
+            .transpose()
+    };
+    let filter = ExportFilter {
+        ticker: text("ticker").map(|v| v.to_ascii_lowercase()),
+        wallet: text("wallet"),
+        protocol: parse_protocol(params.get("protocol"))?,
+        source: text("source").map(|v| v.to_ascii_lowercase()),
+        action: text("action"),
+        sender: text("sender"),
+        recipient: text("recipient"),
+        block_min: parse_u32_param("block_min", params.get("block_min"))?,
+        block_max: parse_u32_param("block_max", params.get("block_max"))?,
+        from_day: parse_day("from")?,
+        to_day: parse_day("to")?,
+    };
+    export::export_response(&state.indexer, &dataset, &filter, format).await
+}
@@ -0,0 +1,83 @@
+use crate::{
+    errors::{NotFound, ServerError},
+    indexer::{AtlasIndexerClient, stored_amount},
+    routes::parse_id,
+};
+use anyhow::anyhow;
+use common::{
+    amount::{AR_DECIMALS, lst_decimals},
+    ids::ArAddress,
+};
+use flp::{
+    simulator::{self, ProjectTotals, Simulation, WalletHolding},
+    types::{DelegationsRes, WalletDelegations},
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct SimulationRequest {
+    /// Arweave address of the delegator
+    pub wallet: String,
+    /// hypothetical preference set, shaped like the `delegationPrefs` of
+    /// `/wallet/delegations`: `[{"walletTo": ..., "factor": ...}]`
+    #[serde(alias = "delegationPrefs")]
+    #[schema(value_type = Vec<Object>)]
+    pub delegation_prefs: Vec<WalletDelegations>,
+}
+
+/// before/after FLP totals if `request.wallet` switched to the proposed
+/// preferences, against its indexed delegations, balances and the latest
+/// cycle of every ticker
+pub async fn simulate_delegation(
+    indexer: &AtlasIndexerClient,
+    request: &SimulationRequest,
+) -> Result<Simulation, ServerError> {
+    simulator::validate_prefs(&request.delegation_prefs)
+        .map_err(|err| ServerError::bad_param("delegation_prefs", err.to_string()))?;
+    let wallet: ArAddress = parse_id("wallet", &request.wallet)?;
+    let wallet = wallet.as_str();
+    let (current, balances, totals) = tokio::join!(
+        indexer.indexed_wallet_delegations(wallet),
+        indexer.wallet_balances(wallet),
+        indexer.latest_flp_cycle_totals(),
+    );
+    let current = match current {
+        Ok(sourced) => serde_json::from_value(sourced.data)?,
+        // wallets that never set a preference delegate everything to PI
+        Err(err) if err.is::<NotFound>() => DelegationsRes::pi_default(wallet),
+        Err(err) => return Err(err.into()),
+    };
+    let holdings = balances?
+        .into_iter()
+        .map(|balance| {
+            let decimals = lst_decimals(&balance.ticker)
+                .ok_or_else(|| anyhow!("unknown oracle ticker {}", balance.ticker))?;
+            Ok(WalletHolding {
+                amount: stored_amount(&balance.amount, decimals)?,
+                ar_amount: stored_amount(&balance.ar_balance, AR_DECIMALS)?,
+                ticker: balance.ticker,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    if holdings.is_empty() {
+        return Err(ServerError::not_found(format!(
+            "no indexed oracle balances for wallet {wallet}"
+        )));
+    }
+    let totals: Vec<ProjectTotals> = totals?
+        .into_iter()
+        .map(|row| ProjectTotals {
+            project: row.project,
+            ticker: row.ticker,
+            amount: row.amount,
+            ar_amount: row.ar_amount,
+        })
+        .collect();
+    Ok(simulator::simulate(
+        &current,
+        &request.delegation_prefs,
+        &holdings,
+        &totals,
+    )?)
+}
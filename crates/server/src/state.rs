@@ -0,0 +1,36 @@
+use crate::{
+    cache::ResponseCache,
+    config::ServerConfig,
+    graphql::{AtlasSchema, build_schema},
+    indexer::AtlasIndexerClient,
+    limits::RateLimiter,
+    live::LiveHub,
+};
+use std::sync::Arc;
+
+/// shared across handlers via axum `State`; the clickhouse client pools its
+/// connections, so clones are cheap and reuse them
+#[derive(Clone)]
+pub struct AppState {
+    pub indexer: AtlasIndexerClient,
+    pub config: Arc<ServerConfig>,
+    pub cache: ResponseCache,
+    pub limits: RateLimiter,
+    pub live: LiveHub,
+    pub graphql: AtlasSchema,
+}
+
+impl AppState {
+    pub fn new(config: ServerConfig) -> Self {
+        let indexer = AtlasIndexerClient::new(&config);
+        let limits = RateLimiter::new(config.api());
+        AppState {
+            graphql: build_schema(indexer.clone()),
+            indexer,
+            config: Arc::new(config),
+            cache: ResponseCache::default(),
+            limits,
+            live: LiveHub::default(),
+        }
+    }
+}
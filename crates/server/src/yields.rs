@@ -0,0 +1,90 @@
+use crate::{
+    errors::ServerError,
+    indexer::{AtlasIndexerClient, WalletCycleStake},
+};
+use common::projects::Project;
+use flp::yields::{TickerStake, WalletYield, YieldCalculator};
+use std::collections::BTreeMap;
+
+/// cycles read before the oldest report, so it finds the stakes in force
+const CYCLE_SLACK_MS: i64 = 7 * 86_400_000;
+
+/// `wallet`'s share of the `limit` newest distribution ticks of `project`
+pub async fn wallet_yield(
+    indexer: &AtlasIndexerClient,
+    project: &str,
+    wallet: &str,
+    limit: u64,
+    calculator: &YieldCalculator,
+) -> Result<WalletYield, ServerError> {
+    let Some(flp) = Project::get(project) else {
+        return Err(ServerError::not_found(format!(
+            "{project} is not a tracked flp"
+        )));
+    };
+    let reports = indexer.minting_report_history(project, limit).await?;
+    let oldest = reports
+        .iter()
+        .map(|report| report.timestamp)
+        .min()
+        .unwrap_or(0) as i64;
+    let cycles = indexer
+        .wallet_cycle_stakes(project, wallet, oldest - CYCLE_SLACK_MS)
+        .await?;
+    let ticks: Vec<_> = reports
+        .into_iter()
+        .map(|report| {
+            let stakes = stakes_at(&cycles, report.timestamp);
+            (report, stakes)
+        })
+        .collect();
+    Ok(calculator.history(wallet, &flp, &ticks)?)
+}
+
+/// per ticker, the latest cycle at or before `ts_ms`. `cycles` oldest first
+fn stakes_at(cycles: &[WalletCycleStake], ts_ms: u64) -> Vec<TickerStake> {
+    let mut latest: BTreeMap<&str, &WalletCycleStake> = BTreeMap::new();
+    for cycle in cycles {
+        if cycle.ts.timestamp_millis() as u64 <= ts_ms {
+            latest.insert(&cycle.ticker, cycle);
+        }
+    }
+    latest
+        .into_values()
+        .map(|cycle| TickerStake {
+            ticker: cycle.ticker.clone(),
+            wallet_amount: cycle.wallet_amount,
+            total_amount: cycle.total_amount,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{indexer::WalletCycleStake, yields::stakes_at};
+    use chrono::{TimeZone, Utc};
+    use common::amount::{Amount, LST_DECIMALS};
+
+    #[test]
+    fn stakes_at_test() {
+        let cycle = |ms: i64, ticker: &str, wallet_amount: u128| WalletCycleStake {
+            ts: Utc.timestamp_millis_opt(ms).unwrap(),
+            ticker: ticker.to_string(),
+            wallet_amount: Amount::new(wallet_amount, LST_DECIMALS),
+            total_amount: Amount::new(100, LST_DECIMALS),
+        };
+        let cycles = vec![
+            cycle(1_000, "dai", 1),
+            cycle(1_000, "usds", 2),
+            cycle(2_000, "usds", 3),
+            cycle(3_000, "usds", 4),
+        ];
+        let stakes = stakes_at(&cycles, 2_500);
+        let amounts: Vec<(&str, u128)> = stakes
+            .iter()
+            .map(|s| (s.ticker.as_str(), s.wallet_amount.raw))
+            .collect();
+        assert_eq!(amounts, vec![("dai", 1), ("usds", 3)]);
+        assert!(stakes_at(&cycles, 500).is_empty());
+    }
+}